@@ -1,43 +1,551 @@
 use crate::expr::{Expr, ExprVisitor};
-use crate::token;
+use crate::stmt::{Param, Stmt, StmtVisitor};
+use crate::token::{self, TokenType};
 
+use std::rc::Rc;
+
+// Prints the AST as Lisp-style S-expressions, e.g. `(print (+ 1 (* 2 3)))`, one line per
+// top-level statement.
+// Node names follow the labels of `DotPrinter`, so the two outputs are easy to compare.
 pub struct AstPrinter;
+
 impl ExprVisitor<String, ()> for AstPrinter {
+    fn visit_array_expr(&mut self, elements: &[Expr]) -> Result<String, ()> {
+        let elements: Vec<&Expr> = elements.iter().collect();
+        self.parenthesize("[]".to_owned(), &elements, &[])
+    }
+    fn visit_assign_expr(&mut self, _id: usize, name: &token::Token, value: &Expr) -> Result<String, ()> {
+        self.parenthesize(format!("= {}", name.lexeme), &[value], &[])
+    }
     fn visit_binary_expr(&mut self, left: &Expr, operator: &token::Token, right: &Expr) -> Result<String, ()> {
-        Ok(self.parenthesize(operator.lexeme.to_owned(), &[left, right]))
+        self.parenthesize(operator.lexeme.to_owned(), &[left, right], &[])
+    }
+    // Keyword arguments follow the positional ones as `name:value`.
+    fn visit_call_expr(&mut self, callee: &Expr, _paren: &token::Token, arguments: &[Expr], keywords: &[(token::Token, Expr)]) -> Result<String, ()> {
+        let mut children = vec![callee];
+        children.extend(arguments);
+        let mut s = self.parenthesize("call".to_owned(), &children, &[])?;
+        s.pop();  // reopen it to add the keyword arguments
+        for (name, argument) in keywords {
+            s.push_str(&format!(" {}:{}", name.lexeme, self.accept_expr(argument)?));
+        }
+        s.push(')');
+        Ok(s)
+    }
+    fn visit_get_expr(&mut self, object: &Expr, name: &token::Token) -> Result<String, ()> {
+        self.parenthesize(format!(".{}", name.lexeme), &[object], &[])
     }
     fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<String, ()> {
-        Ok(self.parenthesize("group".to_owned(), &[expression]))
+        self.parenthesize("group".to_owned(), &[expression], &[])
+    }
+    fn visit_increment_expr(&mut self, _id: usize, name: &token::Token, operator: &token::Token, prefix: bool) -> Result<String, ()> {
+        match prefix {
+            true => Ok(format!("({}{})", operator.lexeme, name.lexeme)),
+            false => Ok(format!("({}{})", name.lexeme, operator.lexeme)),
+        }
+    }
+    fn visit_index_expr(&mut self, object: &Expr, _bracket: &token::Token, index: &Expr) -> Result<String, ()> {
+        self.parenthesize("index".to_owned(), &[object, index], &[])
+    }
+    fn visit_lambda_expr(&mut self, params: &Rc<Vec<Param>>, body: &Rc<Vec<Stmt>>) -> Result<String, ()> {
+        let params = self.params(params)?;
+        let children: Vec<&Stmt> = body.iter().collect();
+        self.parenthesize(format!("fun ({})", params), &[], &children)
     }
+    // Strings are quoted, to tell `"1"` from `1`.
     fn visit_literal_expr(&mut self, value: &token::Literal) -> Result<String, ()> {
-        Ok(value.to_string())
+        match value {
+            token::Literal::String_(x) => Ok(format!("\"{}\"", x)),
+            _ => Ok(value.to_string()),
+        }
+    }
+    fn visit_logical_expr(&mut self, left: &Expr, operator: &token::Token, right: &Expr) -> Result<String, ()> {
+        self.parenthesize(operator.lexeme.to_owned(), &[left, right], &[])
+    }
+    fn visit_map_expr(&mut self, _brace: &token::Token, entries: &[(Expr, Expr)]) -> Result<String, ()> {
+        let children: Vec<&Expr> = entries.iter().flat_map(|(key, value)| [key, value]).collect();
+        self.parenthesize("{}".to_owned(), &children, &[])
+    }
+    fn visit_set_expr(&mut self, object: &Expr, name: &token::Token, value: &Expr) -> Result<String, ()> {
+        self.parenthesize(format!(".{} =", name.lexeme), &[object, value], &[])
+    }
+    fn visit_set_index_expr(&mut self, object: &Expr, _bracket: &token::Token, index: &Expr, value: &Expr) -> Result<String, ()> {
+        self.parenthesize("index =".to_owned(), &[object, index, value], &[])
+    }
+    fn visit_spread_expr(&mut self, _ellipsis: &token::Token, expression: &Expr) -> Result<String, ()> {
+        self.parenthesize("...".to_owned(), &[expression], &[])
+    }
+    fn visit_super_expr(&mut self, _id: usize, _keyword: &token::Token, method: &token::Token) -> Result<String, ()> {
+        Ok(format!("super.{}", method.lexeme))
+    }
+    fn visit_ternary_expr(&mut self, condition: &Expr, _question: &token::Token, then_expr: &Expr, else_expr: &Expr) -> Result<String, ()> {
+        self.parenthesize("?:".to_owned(), &[condition, then_expr, else_expr], &[])
+    }
+    fn visit_this_expr(&mut self, _id: usize, _keyword: &token::Token) -> Result<String, ()> {
+        Ok("this".to_owned())
     }
     fn visit_unary_expr(&mut self, operator: &token::Token, right: &Expr) -> Result<String, ()> {
-        Ok(self.parenthesize(operator.lexeme.to_owned(), &[right]))
+        self.parenthesize(operator.lexeme.to_owned(), &[right], &[])
+    }
+    fn visit_variable_expr(&mut self, _id: usize, name: &token::Token) -> Result<String, ()> {
+        Ok(name.lexeme.to_owned())
+    }
+}
+
+impl StmtVisitor<String, ()> for AstPrinter {
+    fn visit_block_stmt(&mut self, statements: &[Stmt]) -> Result<String, ()> {
+        let children: Vec<&Stmt> = statements.iter().collect();
+        self.parenthesize("block".to_owned(), &[], &children)
+    }
+    fn visit_break_stmt(&mut self, _keyword: &token::Token) -> Result<String, ()> {
+        Ok("(break)".to_owned())
+    }
+    fn visit_class_stmt(&mut self, name: &token::Token, superclass: Option<&Expr>, methods: &[Stmt]) -> Result<String, ()> {
+        let superclass: Vec<&Expr> = superclass.into_iter().collect();
+        let children: Vec<&Stmt> = methods.iter().collect();
+        self.parenthesize(format!("class {}", name.lexeme), &superclass, &children)
+    }
+    fn visit_continue_stmt(&mut self, _keyword: &token::Token) -> Result<String, ()> {
+        Ok("(continue)".to_owned())
+    }
+    fn visit_debug_assert_stmt(&mut self, _keyword: &token::Token, condition: &Expr) -> Result<String, ()> {
+        self.parenthesize("debug_assert".to_owned(), &[condition], &[])
+    }
+    fn visit_empty_stmt(&mut self) -> Result<String, ()> {
+        Ok("(empty)".to_owned())
+    }
+    fn visit_expression_stmt(&mut self, expression: &Expr) -> Result<String, ()> {
+        self.parenthesize(";".to_owned(), &[expression], &[])
+    }
+    fn visit_function_stmt(&mut self, name: &token::Token, params: &Rc<Vec<Param>>, body: &Rc<Vec<Stmt>>) -> Result<String, ()> {
+        let params = self.params(params)?;
+        let children: Vec<&Stmt> = body.iter().collect();
+        self.parenthesize(format!("fun {} ({})", name.lexeme, params), &[], &children)
+    }
+    fn visit_if_stmt(&mut self, _keyword: &token::Token, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) -> Result<String, ()> {
+        let mut branches = vec![then_branch];
+        branches.extend(else_branch);
+        self.parenthesize("if".to_owned(), &[condition], &branches)
+    }
+    fn visit_import_stmt(&mut self, _keyword: &token::Token, path: &str, alias: Option<&token::Token>) -> Result<String, ()> {
+        match alias {
+            Some(alias) => Ok(format!("(import \"{}\" as {})", path, alias.lexeme)),
+            None => Ok(format!("(import \"{}\")", path)),
+        }
     }
-    fn visit_variable_expr(&mut self, name: &token::Token) -> Result<String, ()> {
-        Ok(name.to_string())
+    fn visit_print_stmt(&mut self, _keyword: &token::Token, expression: &Expr) -> Result<String, ()> {
+        self.parenthesize("print".to_owned(), &[expression], &[])
     }
-    fn visit_assign_expr(&mut self, name: &token::Token, value: &Expr) -> Result<String, ()> {
-        Ok(self.parenthesize(name.lexeme.clone(), &[value]))
+    fn visit_while_stmt(&mut self, _keyword: &token::Token, condition: &Expr, body: &Stmt, increment: Option<&Expr>) -> Result<String, ()> {
+        let mut exprs = vec![condition];
+        exprs.extend(increment);
+        self.parenthesize("while".to_owned(), &exprs, &[body])
+    }
+    fn visit_return_stmt(&mut self, _keyword: &token::Token, value: Option<&Expr>) -> Result<String, ()> {
+        let value: Vec<&Expr> = value.into_iter().collect();
+        self.parenthesize("return".to_owned(), &value, &[])
+    }
+    fn visit_var_stmt(&mut self, name: &token::Token, initializer: Option<&Expr>) -> Result<String, ()> {
+        let initializer: Vec<&Expr> = initializer.into_iter().collect();
+        self.parenthesize(format!("var {}", name.lexeme), &initializer, &[])
+    }
+    fn visit_switch_stmt(&mut self, _keyword: &token::Token, discriminant: &Expr, cases: &[(Expr, Vec<Stmt>)], default: Option<&[Stmt]>) -> Result<String, ()> {
+        let mut s = self.parenthesize("switch".to_owned(), &[discriminant], &[])?;
+        s.pop();  // reopen it to add the cases
+        for (value, statements) in cases {
+            let children: Vec<&Stmt> = statements.iter().collect();
+            s.push(' ');
+            s.push_str(&self.parenthesize("case".to_owned(), &[value], &children)?);
+        }
+        if let Some(statements) = default {
+            let children: Vec<&Stmt> = statements.iter().collect();
+            s.push(' ');
+            s.push_str(&self.parenthesize("default".to_owned(), &[], &children)?);
+        }
+        s.push(')');
+        Ok(s)
+    }
+    fn visit_yield_stmt(&mut self, _keyword: &token::Token, value: &Expr) -> Result<String, ()> {
+        self.parenthesize("yield".to_owned(), &[value], &[])
     }
 }
 
 impl AstPrinter {
-    pub fn print(&mut self, expr: &Expr) -> String {
-        self.accept_expr(expr).unwrap()
+    // Interface. The `()` error matches the visitor traits; it is never actually produced.
+    #[allow(clippy::result_unit_err)]
+    pub fn print(&mut self, statements: &[Stmt]) -> Result<String, ()> {
+        let lines = statements.iter().map(|statement| self.accept_stmt(statement)).collect::<Result<Vec<String>, ()>>()?;
+        Ok(lines.join("\n"))
     }
 
-    fn parenthesize(&mut self, name: String, exprs: &[&Expr]) -> String {
+    // The parameters of a function, space-separated, with any default value after an `=`, e.g.
+    // `name greeting="Hello"`, and a rest parameter after a `...`.
+    fn params(&mut self, params: &[Param]) -> Result<String, ()> {
+        let params = params.iter()
+            .map(|param| match &param.default {
+                Some(default) => Ok(format!("{}={}", param.name.lexeme, self.accept_expr(default)?)),
+                None if param.rest => Ok(format!("...{}", param.name.lexeme)),
+                None => Ok(param.name.lexeme.to_owned()),
+            })
+            .collect::<Result<Vec<String>, ()>>()?;
+        Ok(params.join(" "))
+    }
+
+    // `(name expr... stmt...)`. Like in `DotPrinter`, expression children come first.
+    fn parenthesize(&mut self, name: String, exprs: &[&Expr], stmts: &[&Stmt]) -> Result<String, ()> {
         let mut s: String = String::new();
         s.push('(');
         s.push_str(&name);
         for expr in exprs {
             s.push(' ');
-            s.push_str(&self.accept_expr(expr).unwrap());
+            s.push_str(&self.accept_expr(expr)?);
+        }
+        for stmt in stmts {
+            s.push(' ');
+            s.push_str(&self.accept_stmt(stmt)?);
         }
         s.push(')');
-        s
+        Ok(s)
     }
 }
 
+// Prints the AST back as source, one line per top-level statement, with only the parentheses
+// needed to keep the operators grouped as they were: `(1 + 2) * 3` keeps them, but `1 + (2 * 3)`
+// prints as `1 + 2 * 3`. Parsing the output gives the same tree, except for the `Expr::Grouping`s
+// of the parentheses left out. A `for` loop comes out desugared, as a block around a `for` with
+// only a condition and increment.
+#[derive(Default)]
+pub struct SourcePrinter {
+    // How tightly the expression being printed has to bind to do without parentheses where it is,
+    // e.g. `Precedence::Factor` for the left operand of `*`.
+    context: Precedence,
+}
+
+// The levels of the expression grammar (see `Parser`), loosest first.
+#[derive(Clone, Copy, Default, PartialEq, PartialOrd)]
+enum Precedence {
+    #[default]
+    Assignment,
+    Ternary,
+    Or,  // `or` and `xor`
+    And,
+    Equality,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Comparison,
+    Shift,
+    Term,
+    Factor,
+    Exponent,
+    Unary,
+    Postfix,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    fn of_operator(type_: &TokenType) -> Precedence {
+        match type_ {
+            TokenType::Or | TokenType::Xor => Precedence::Or,
+            TokenType::And => Precedence::And,
+            TokenType::EqualEqual | TokenType::BangEqual => Precedence::Equality,
+            TokenType::Pipe => Precedence::BitOr,
+            TokenType::Caret => Precedence::BitXor,
+            TokenType::Ampersand => Precedence::BitAnd,
+            TokenType::Less | TokenType::LessEqual | TokenType::Greater | TokenType::GreaterEqual | TokenType::Is => Precedence::Comparison,
+            TokenType::LessLess | TokenType::GreaterGreater => Precedence::Shift,
+            TokenType::Plus | TokenType::Minus => Precedence::Term,
+            TokenType::Star | TokenType::Slash | TokenType::Percent => Precedence::Factor,
+            TokenType::StarStar => Precedence::Exponent,
+            _ => unreachable!("only binary and logical operators have a precedence"),
+        }
+    }
+
+    // The next level up, which binds more tightly.
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::Assignment => Precedence::Ternary,
+            Precedence::Ternary => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::BitOr,
+            Precedence::BitOr => Precedence::BitXor,
+            Precedence::BitXor => Precedence::BitAnd,
+            Precedence::BitAnd => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Shift,
+            Precedence::Shift => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Exponent,
+            Precedence::Exponent => Precedence::Unary,
+            Precedence::Unary => Precedence::Postfix,
+            Precedence::Postfix => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+impl ExprVisitor<String, ()> for SourcePrinter {
+    fn visit_array_expr(&mut self, elements: &[Expr]) -> Result<String, ()> {
+        Ok(format!("[{}]", self.list(elements)?))
+    }
+    fn visit_assign_expr(&mut self, _id: usize, name: &token::Token, value: &Expr) -> Result<String, ()> {
+        let s = format!("{} = {}", name.lexeme, self.expr(value, Precedence::Assignment)?);
+        Ok(self.wrap(Precedence::Assignment, s))
+    }
+    // The operators on one level group to the left, so only a right operand on the same level
+    // needs parentheses: `a - (b - c)`, but `a - b - c`. `**` is the other way around.
+    fn visit_binary_expr(&mut self, left: &Expr, operator: &token::Token, right: &Expr) -> Result<String, ()> {
+        let precedence = Precedence::of_operator(&operator.type_);
+        let (left_context, right_context) = match precedence {
+            Precedence::Exponent => (Precedence::Unary, Precedence::Exponent),
+            _ => (precedence, precedence.next()),
+        };
+        let s = format!("{} {} {}", self.expr(left, left_context)?, operator.lexeme, self.expr(right, right_context)?);
+        Ok(self.wrap(precedence, s))
+    }
+    fn visit_call_expr(&mut self, callee: &Expr, _paren: &token::Token, arguments: &[Expr], keywords: &[(token::Token, Expr)]) -> Result<String, ()> {
+        let mut arguments = vec![self.list(arguments)?];
+        for (name, argument) in keywords {
+            arguments.push(format!("{}: {}", name.lexeme, self.expr(argument, Precedence::Assignment)?));
+        }
+        arguments.retain(|argument| !argument.is_empty());
+        let s = format!("{}({})", self.expr(callee, Precedence::Call)?, arguments.join(", "));
+        Ok(self.wrap(Precedence::Call, s))
+    }
+    fn visit_get_expr(&mut self, object: &Expr, name: &token::Token) -> Result<String, ()> {
+        let s = format!("{}.{}", self.expr(object, Precedence::Call)?, name.lexeme);
+        Ok(self.wrap(Precedence::Call, s))
+    }
+    // Parentheses are put back where they are needed, whether or not they were there.
+    fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<String, ()> {
+        self.accept_expr(expression)
+    }
+    fn visit_increment_expr(&mut self, _id: usize, name: &token::Token, operator: &token::Token, prefix: bool) -> Result<String, ()> {
+        match prefix {
+            true => Ok(self.wrap(Precedence::Unary, format!("{}{}", operator.lexeme, name.lexeme))),
+            false => Ok(self.wrap(Precedence::Postfix, format!("{}{}", name.lexeme, operator.lexeme))),
+        }
+    }
+    fn visit_index_expr(&mut self, object: &Expr, _bracket: &token::Token, index: &Expr) -> Result<String, ()> {
+        let s = format!("{}[{}]", self.expr(object, Precedence::Call)?, self.expr(index, Precedence::Assignment)?);
+        Ok(self.wrap(Precedence::Call, s))
+    }
+    fn visit_lambda_expr(&mut self, params: &Rc<Vec<Param>>, body: &Rc<Vec<Stmt>>) -> Result<String, ()> {
+        Ok(format!("fun ({}) {}", self.params(params)?, self.block(body)?))
+    }
+    // A whole float keeps a `.0`, to be read back as a float rather than an integer.
+    fn visit_literal_expr(&mut self, value: &token::Literal) -> Result<String, ()> {
+        match value {
+            token::Literal::String_(x) => Ok(format!("\"{}\"", token::escape(x))),
+            token::Literal::Number(x) if x.is_finite() && !x.to_string().contains('.') => Ok(format!("{}.0", x)),
+            _ => Ok(value.to_string()),
+        }
+    }
+    fn visit_logical_expr(&mut self, left: &Expr, operator: &token::Token, right: &Expr) -> Result<String, ()> {
+        self.visit_binary_expr(left, operator, right)
+    }
+    fn visit_map_expr(&mut self, _brace: &token::Token, entries: &[(Expr, Expr)]) -> Result<String, ()> {
+        let entries = entries.iter()
+            .map(|(key, value)| Ok(format!("{}: {}", self.expr(key, Precedence::Assignment)?, self.expr(value, Precedence::Assignment)?)))
+            .collect::<Result<Vec<String>, ()>>()?;
+        Ok(format!("{{{}}}", entries.join(", ")))
+    }
+    fn visit_set_expr(&mut self, object: &Expr, name: &token::Token, value: &Expr) -> Result<String, ()> {
+        let s = format!("{}.{} = {}", self.expr(object, Precedence::Call)?, name.lexeme, self.expr(value, Precedence::Assignment)?);
+        Ok(self.wrap(Precedence::Assignment, s))
+    }
+    fn visit_set_index_expr(&mut self, object: &Expr, _bracket: &token::Token, index: &Expr, value: &Expr) -> Result<String, ()> {
+        let s = format!("{}[{}] = {}",
+            self.expr(object, Precedence::Call)?, self.expr(index, Precedence::Assignment)?, self.expr(value, Precedence::Assignment)?);
+        Ok(self.wrap(Precedence::Assignment, s))
+    }
+    fn visit_spread_expr(&mut self, _ellipsis: &token::Token, expression: &Expr) -> Result<String, ()> {
+        Ok(format!("...{}", self.expr(expression, Precedence::Assignment)?))
+    }
+    fn visit_super_expr(&mut self, _id: usize, _keyword: &token::Token, method: &token::Token) -> Result<String, ()> {
+        Ok(format!("super.{}", method.lexeme))
+    }
+    // The condition can't be a ternary itself without parentheses, but the middle can be anything.
+    fn visit_ternary_expr(&mut self, condition: &Expr, _question: &token::Token, then_expr: &Expr, else_expr: &Expr) -> Result<String, ()> {
+        let s = format!("{} ? {} : {}",
+            self.expr(condition, Precedence::Or)?, self.expr(then_expr, Precedence::Assignment)?, self.expr(else_expr, Precedence::Ternary)?);
+        Ok(self.wrap(Precedence::Ternary, s))
+    }
+    fn visit_this_expr(&mut self, _id: usize, _keyword: &token::Token) -> Result<String, ()> {
+        Ok("this".to_owned())
+    }
+    // `- -x` needs the space, or it would read back as `--x`.
+    fn visit_unary_expr(&mut self, operator: &token::Token, right: &Expr) -> Result<String, ()> {
+        let right = self.expr(right, Precedence::Unary)?;
+        let space = if operator.type_ == TokenType::Minus && right.starts_with('-') { " " } else { "" };
+        Ok(self.wrap(Precedence::Unary, format!("{}{}{}", operator.lexeme, space, right)))
+    }
+    fn visit_variable_expr(&mut self, _id: usize, name: &token::Token) -> Result<String, ()> {
+        Ok(name.lexeme.to_owned())
+    }
+}
+
+impl StmtVisitor<String, ()> for SourcePrinter {
+    fn visit_block_stmt(&mut self, statements: &[Stmt]) -> Result<String, ()> {
+        self.block(statements)
+    }
+    fn visit_break_stmt(&mut self, _keyword: &token::Token) -> Result<String, ()> {
+        Ok("break;".to_owned())
+    }
+    fn visit_class_stmt(&mut self, name: &token::Token, superclass: Option<&Expr>, methods: &[Stmt]) -> Result<String, ()> {
+        let mut s = format!("class {} ", name.lexeme);
+        if let Some(superclass) = superclass {
+            s.push_str(&format!("< {} ", self.expr(superclass, Precedence::Primary)?));
+        }
+        let methods = methods.iter()
+            .map(|method| match method {
+                Stmt::Function { name, params, body } => self.function(&name.lexeme, params, body),
+                _ => unreachable!("the parser only puts function declarations in a class body"),
+            })
+            .collect::<Result<Vec<String>, ()>>()?;
+        match methods.is_empty() {
+            true => s.push_str("{}"),
+            false => s.push_str(&format!("{{ {} }}", methods.join(" "))),
+        }
+        Ok(s)
+    }
+    fn visit_continue_stmt(&mut self, _keyword: &token::Token) -> Result<String, ()> {
+        Ok("continue;".to_owned())
+    }
+    fn visit_debug_assert_stmt(&mut self, _keyword: &token::Token, condition: &Expr) -> Result<String, ()> {
+        Ok(format!("debug_assert({});", self.expr(condition, Precedence::Assignment)?))
+    }
+    fn visit_empty_stmt(&mut self) -> Result<String, ()> {
+        Ok(";".to_owned())
+    }
+    // At the start of a statement, a `{` would start a block and a `fun` a declaration.
+    fn visit_expression_stmt(&mut self, expression: &Expr) -> Result<String, ()> {
+        let s = self.expr(expression, Precedence::Assignment)?;
+        match s.starts_with('{') || s.starts_with("fun ") {
+            true => Ok(format!("({});", s)),
+            false => Ok(format!("{};", s)),
+        }
+    }
+    fn visit_function_stmt(&mut self, name: &token::Token, params: &Rc<Vec<Param>>, body: &Rc<Vec<Stmt>>) -> Result<String, ()> {
+        Ok(format!("fun {}", self.function(&name.lexeme, params, body)?))
+    }
+    fn visit_if_stmt(&mut self, _keyword: &token::Token, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) -> Result<String, ()> {
+        let mut s = format!("if ({}) {}", self.expr(condition, Precedence::Assignment)?, self.accept_stmt(then_branch)?);
+        if let Some(else_branch) = else_branch {
+            s.push_str(&format!(" else {}", self.accept_stmt(else_branch)?));
+        }
+        Ok(s)
+    }
+    fn visit_import_stmt(&mut self, _keyword: &token::Token, path: &str, alias: Option<&token::Token>) -> Result<String, ()> {
+        match alias {
+            Some(alias) => Ok(format!("import \"{}\" as {};", token::escape(path), alias.lexeme)),
+            None => Ok(format!("import \"{}\";", token::escape(path))),
+        }
+    }
+    fn visit_print_stmt(&mut self, _keyword: &token::Token, expression: &Expr) -> Result<String, ()> {
+        Ok(format!("print {};", self.expr(expression, Precedence::Assignment)?))
+    }
+    // Only a desugared `for` has an increment.
+    fn visit_while_stmt(&mut self, _keyword: &token::Token, condition: &Expr, body: &Stmt, increment: Option<&Expr>) -> Result<String, ()> {
+        let condition = self.expr(condition, Precedence::Assignment)?;
+        match increment {
+            Some(increment) => {
+                let increment = self.expr(increment, Precedence::Assignment)?;
+                Ok(format!("for (; {}; {}) {}", condition, increment, self.accept_stmt(body)?))
+            },
+            None => Ok(format!("while ({}) {}", condition, self.accept_stmt(body)?)),
+        }
+    }
+    fn visit_return_stmt(&mut self, _keyword: &token::Token, value: Option<&Expr>) -> Result<String, ()> {
+        match value {
+            Some(value) => Ok(format!("return {};", self.expr(value, Precedence::Assignment)?)),
+            None => Ok("return;".to_owned()),
+        }
+    }
+    fn visit_var_stmt(&mut self, name: &token::Token, initializer: Option<&Expr>) -> Result<String, ()> {
+        match initializer {
+            Some(initializer) => Ok(format!("var {} = {};", name.lexeme, self.expr(initializer, Precedence::Assignment)?)),
+            None => Ok(format!("var {};", name.lexeme)),
+        }
+    }
+    fn visit_switch_stmt(&mut self, _keyword: &token::Token, discriminant: &Expr, cases: &[(Expr, Vec<Stmt>)], default: Option<&[Stmt]>) -> Result<String, ()> {
+        let mut parts = vec![format!("switch ({}) {{", self.expr(discriminant, Precedence::Assignment)?)];
+        for (value, statements) in cases {
+            parts.push(format!("case {}:", self.expr(value, Precedence::Assignment)?));
+            parts.extend(self.stmts(statements)?);
+        }
+        if let Some(statements) = default {
+            parts.push("default:".to_owned());
+            parts.extend(self.stmts(statements)?);
+        }
+        parts.push("}".to_owned());
+        Ok(parts.join(" "))
+    }
+    fn visit_yield_stmt(&mut self, _keyword: &token::Token, value: &Expr) -> Result<String, ()> {
+        Ok(format!("yield {};", self.expr(value, Precedence::Assignment)?))
+    }
+}
+
+impl SourcePrinter {
+    pub fn new() -> Self {
+        Self {
+            context: Precedence::Assignment,
+        }
+    }
+
+    // Interface. The `()` error matches the visitor traits; it is never actually produced.
+    #[allow(clippy::result_unit_err)]
+    pub fn print(&mut self, statements: &[Stmt]) -> Result<String, ()> {
+        Ok(self.stmts(statements)?.join("\n"))
+    }
+
+    // `expr` where it has to bind at least as tightly as `context` to go without parentheses.
+    fn expr(&mut self, expr: &Expr, context: Precedence) -> Result<String, ()> {
+        let enclosing = std::mem::replace(&mut self.context, context);
+        let s = self.accept_expr(expr);
+        self.context = enclosing;
+        s
+    }
+
+    // `s`, an expression on the level of `precedence`, in parentheses if the context needs them.
+    fn wrap(&self, precedence: Precedence, s: String) -> String {
+        match precedence < self.context {
+            true => format!("({})", s),
+            false => s,
+        }
+    }
+
+    // Array elements or positional arguments, comma-separated.
+    fn list(&mut self, exprs: &[Expr]) -> Result<String, ()> {
+        let exprs = exprs.iter().map(|expr| self.expr(expr, Precedence::Assignment)).collect::<Result<Vec<String>, ()>>()?;
+        Ok(exprs.join(", "))
+    }
+
+    fn stmts(&mut self, statements: &[Stmt]) -> Result<Vec<String>, ()> {
+        statements.iter().map(|statement| self.accept_stmt(statement)).collect()
+    }
+
+    fn block(&mut self, statements: &[Stmt]) -> Result<String, ()> {
+        match statements.is_empty() {
+            true => Ok("{}".to_owned()),
+            false => Ok(format!("{{ {} }}", self.stmts(statements)?.join(" "))),
+        }
+    }
+
+    // A function declaration or method after the `fun`, if any: `name(params) { body }`.
+    fn function(&mut self, name: &str, params: &[Param], body: &[Stmt]) -> Result<String, ()> {
+        Ok(format!("{}({}) {}", name, self.params(params)?, self.block(body)?))
+    }
+
+    fn params(&mut self, params: &[Param]) -> Result<String, ()> {
+        let params = params.iter()
+            .map(|param| match &param.default {
+                Some(default) => Ok(format!("{} = {}", param.name.lexeme, self.expr(default, Precedence::Assignment)?)),
+                None if param.rest => Ok(format!("...{}", param.name.lexeme)),
+                None => Ok(param.name.lexeme.to_owned()),
+            })
+            .collect::<Result<Vec<String>, ()>>()?;
+        Ok(params.join(", "))
+    }
+}
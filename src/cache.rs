@@ -0,0 +1,580 @@
+use crate::expr::{self, Expr};
+use crate::stmt::{Param, Stmt};
+use crate::token::{Literal, Token, TokenType};
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// A cache of parsed scripts, so that a script that hasn't changed since it was last run doesn't
+// need scanning and parsing again. The statements are stored in a compact binary form in a `.loxc`
+// file next to the script, along with a hash of the source they were parsed from: the cache is
+// only used while the source still matches it.
+// The file starts with `MAGIC`, `FORMAT` and the version of the interpreter that wrote it, so one
+// written by a different version, whose AST could differ, is ignored rather than misread.
+// Expression ids aren't stored. Loaded expressions get new ones, as if freshly parsed.
+
+const MAGIC: &[u8] = b"LOXC";
+const FORMAT: u8 = 3;
+
+// The `.loxc` file for the script at `path`: the same path with the extension replaced. There is
+// none for a script that has the extension already, which the cache would overwrite.
+pub fn cache_path(path: &Path) -> Option<PathBuf> {
+    match path.extension() {
+        Some(extension) if extension == "loxc" => None,
+        _ => Some(path.with_extension("loxc")),
+    }
+}
+
+// The statements cached in the file at `path` for `source`, if there is a cache there that is up to
+// date with it.
+pub fn load(path: &Path, source: &str) -> Option<Vec<Stmt>> {
+    let bytes = fs::read(path).ok()?;
+    deserialize(&bytes, source)
+}
+
+// Write the cache for `statements`, parsed from `source`, to `path`. It is only a cache, so failing
+// to write it isn't an error.
+pub fn store(path: &Path, source: &str, statements: &[Stmt]) {
+    _ = fs::write(path, serialize(source, statements));
+}
+
+// `statements`, parsed from `source`, in the binary form of the cache.
+pub fn serialize(source: &str, statements: &[Stmt]) -> Vec<u8> {
+    let mut writer = Writer { bytes: MAGIC.to_vec() };
+    writer.bytes.push(FORMAT);
+    writer.str(env!("CARGO_PKG_VERSION"));
+    writer.bytes.extend(hash(source).to_le_bytes());
+    writer.stmts(statements);
+    writer.bytes
+}
+
+// The statements in `bytes`, if it is a cache written by this version of the interpreter for
+// `source`. Anything else, including a truncated or corrupt cache, gives `None`.
+pub fn deserialize(bytes: &[u8], source: &str) -> Option<Vec<Stmt>> {
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.take(MAGIC.len())? != MAGIC || reader.u8()? != FORMAT || reader.str()? != env!("CARGO_PKG_VERSION") {
+        return None;
+    }
+    if reader.take(8)? != hash(source).to_le_bytes() {
+        return None;
+    }
+    let statements = reader.stmts()?;
+    match reader.position == bytes.len() {
+        true => Some(statements),
+        false => None,
+    }
+}
+
+fn hash(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Every token type, in the order of their tags in the cache.
+const TOKEN_TYPES: [TokenType; 63] = [
+    TokenType::LeftParen, TokenType::RightParen, TokenType::LeftBrace, TokenType::RightBrace,
+    TokenType::LeftBracket, TokenType::RightBracket, TokenType::Colon, TokenType::Comma,
+    TokenType::Dot, TokenType::Percent, TokenType::Question, TokenType::Semicolon, TokenType::Slash,
+    TokenType::Ampersand, TokenType::Caret, TokenType::Pipe, TokenType::Tilde,
+    TokenType::Bang, TokenType::BangEqual, TokenType::Equal, TokenType::EqualEqual,
+    TokenType::Greater, TokenType::GreaterEqual, TokenType::GreaterGreater,
+    TokenType::Less, TokenType::LessEqual, TokenType::LessLess,
+    TokenType::Minus, TokenType::MinusMinus, TokenType::Plus, TokenType::PlusPlus,
+    TokenType::Star, TokenType::StarStar,
+    TokenType::Identifier, TokenType::String_, TokenType::Number,
+    TokenType::And, TokenType::Class, TokenType::Else, TokenType::False, TokenType::Fun,
+    TokenType::For, TokenType::If, TokenType::Nil, TokenType::Or, TokenType::Print,
+    TokenType::Return, TokenType::Super, TokenType::This, TokenType::True, TokenType::Var,
+    TokenType::While, TokenType::Xor, TokenType::Break, TokenType::Continue,
+    TokenType::DebugAssert, TokenType::Import, TokenType::Switch, TokenType::Case,
+    TokenType::Default, TokenType::Is, TokenType::Yield, TokenType::DotDotDot,
+];
+
+// The tag of `type_` in the cache. `Eof` never ends up in the AST, so it has none.
+fn token_type_tag(type_: &TokenType) -> u8 {
+    let tag = TOKEN_TYPES.iter().position(|t| t == type_).expect("Only `Eof` is missing from TOKEN_TYPES");
+    tag as u8
+}
+
+// Appends values to `bytes`. Lengths, positions and tags are written as unsigned LEB128, which
+// takes a single byte for the small numbers most of them are.
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn usize(&mut self, mut n: usize) {
+        while n >= 0x80 {
+            self.bytes.push((n & 0x7f) as u8 | 0x80);
+            n >>= 7;
+        }
+        self.bytes.push(n as u8);
+    }
+
+    fn bool(&mut self, x: bool) {
+        self.bytes.push(x as u8);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.usize(s.len());
+        self.bytes.extend(s.as_bytes());
+    }
+
+    fn token(&mut self, token: &Token) {
+        self.bytes.push(token_type_tag(&token.type_));
+        self.str(&token.lexeme);
+        self.literal(&token.literal);
+        self.usize(token.line);
+        self.usize(token.column);
+    }
+
+    fn literal(&mut self, literal: &Literal) {
+        match literal {
+            Literal::Integer(x) => {
+                self.bytes.push(0);
+                self.bytes.extend(x.to_le_bytes());
+            },
+            Literal::Number(x) => {
+                self.bytes.push(1);
+                self.bytes.extend(x.to_bits().to_le_bytes());
+            },
+            Literal::String_(x) => {
+                self.bytes.push(2);
+                self.str(x);
+            },
+            Literal::Bool(x) => {
+                self.bytes.push(3);
+                self.bool(*x);
+            },
+            Literal::Nil => self.bytes.push(4),
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        self.usize(exprs.len());
+        exprs.iter().for_each(|expr| self.expr(expr));
+    }
+
+    fn optional_expr(&mut self, expr: Option<&Expr>) {
+        self.bool(expr.is_some());
+        if let Some(expr) = expr {
+            self.expr(expr);
+        }
+    }
+
+    fn params(&mut self, params: &[Param]) {
+        self.usize(params.len());
+        for param in params {
+            self.token(&param.name);
+            self.optional_expr(param.default.as_ref());
+            self.bool(param.rest);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Array { elements } => {
+                self.bytes.push(0);
+                self.exprs(elements);
+            },
+            Expr::Assign { id: _, name, value } => {
+                self.bytes.push(1);
+                self.token(name);
+                self.expr(value);
+            },
+            Expr::Binary { left, operator, right } => {
+                self.bytes.push(2);
+                self.expr(left);
+                self.token(operator);
+                self.expr(right);
+            },
+            Expr::Call { callee, paren, arguments, keywords } => {
+                self.bytes.push(3);
+                self.expr(callee);
+                self.token(paren);
+                self.exprs(arguments);
+                self.usize(keywords.len());
+                for (name, argument) in keywords {
+                    self.token(name);
+                    self.expr(argument);
+                }
+            },
+            Expr::Get { object, name } => {
+                self.bytes.push(4);
+                self.expr(object);
+                self.token(name);
+            },
+            Expr::Grouping { expression } => {
+                self.bytes.push(5);
+                self.expr(expression);
+            },
+            Expr::Increment { id: _, name, operator, prefix } => {
+                self.bytes.push(6);
+                self.token(name);
+                self.token(operator);
+                self.bool(*prefix);
+            },
+            Expr::Index { object, bracket, index } => {
+                self.bytes.push(7);
+                self.expr(object);
+                self.token(bracket);
+                self.expr(index);
+            },
+            Expr::Lambda { params, body } => {
+                self.bytes.push(8);
+                self.params(params);
+                self.stmts(body);
+            },
+            Expr::Literal { value } => {
+                self.bytes.push(9);
+                self.literal(value);
+            },
+            Expr::Map { brace, entries } => {
+                self.bytes.push(10);
+                self.token(brace);
+                self.usize(entries.len());
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            },
+            Expr::Logical { left, operator, right } => {
+                self.bytes.push(11);
+                self.expr(left);
+                self.token(operator);
+                self.expr(right);
+            },
+            Expr::Set { object, name, value } => {
+                self.bytes.push(12);
+                self.expr(object);
+                self.token(name);
+                self.expr(value);
+            },
+            Expr::SetIndex { object, bracket, index, value } => {
+                self.bytes.push(13);
+                self.expr(object);
+                self.token(bracket);
+                self.expr(index);
+                self.expr(value);
+            },
+            Expr::Spread { ellipsis, expression } => {
+                self.bytes.push(19);
+                self.token(ellipsis);
+                self.expr(expression);
+            },
+            Expr::Super { id: _, keyword, method } => {
+                self.bytes.push(14);
+                self.token(keyword);
+                self.token(method);
+            },
+            Expr::Ternary { condition, question, then_expr, else_expr } => {
+                self.bytes.push(15);
+                self.expr(condition);
+                self.token(question);
+                self.expr(then_expr);
+                self.expr(else_expr);
+            },
+            Expr::This { id: _, keyword } => {
+                self.bytes.push(16);
+                self.token(keyword);
+            },
+            Expr::Unary { operator, right } => {
+                self.bytes.push(17);
+                self.token(operator);
+                self.expr(right);
+            },
+            Expr::Variable { id: _, name } => {
+                self.bytes.push(18);
+                self.token(name);
+            },
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        self.usize(stmts.len());
+        stmts.iter().for_each(|stmt| self.stmt(stmt));
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements } => {
+                self.bytes.push(0);
+                self.stmts(statements);
+            },
+            Stmt::Break { keyword } => {
+                self.bytes.push(1);
+                self.token(keyword);
+            },
+            Stmt::Class { name, superclass, methods } => {
+                self.bytes.push(2);
+                self.token(name);
+                self.optional_expr(superclass.as_ref());
+                self.stmts(methods);
+            },
+            Stmt::Continue { keyword } => {
+                self.bytes.push(3);
+                self.token(keyword);
+            },
+            Stmt::DebugAssert { keyword, condition } => {
+                self.bytes.push(4);
+                self.token(keyword);
+                self.expr(condition);
+            },
+            Stmt::Empty => self.bytes.push(5),
+            Stmt::Expression { expression } => {
+                self.bytes.push(6);
+                self.expr(expression);
+            },
+            Stmt::Function { name, params, body } => {
+                self.bytes.push(7);
+                self.token(name);
+                self.params(params);
+                self.stmts(body);
+            },
+            Stmt::If { keyword, condition, then_branch, else_branch } => {
+                self.bytes.push(8);
+                self.token(keyword);
+                self.expr(condition);
+                self.stmt(then_branch);
+                self.bool(else_branch.is_some());
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+            },
+            Stmt::Import { keyword, path, alias } => {
+                self.bytes.push(9);
+                self.token(keyword);
+                self.str(path);
+                self.bool(alias.is_some());
+                if let Some(alias) = alias {
+                    self.token(alias);
+                }
+            },
+            Stmt::Print { keyword, expression } => {
+                self.bytes.push(10);
+                self.token(keyword);
+                self.expr(expression);
+            },
+            Stmt::While { keyword, condition, body, increment } => {
+                self.bytes.push(11);
+                self.token(keyword);
+                self.expr(condition);
+                self.stmt(body);
+                self.optional_expr(increment.as_ref());
+            },
+            Stmt::Return { keyword, value } => {
+                self.bytes.push(12);
+                self.token(keyword);
+                self.optional_expr(value.as_ref());
+            },
+            Stmt::Var { name, initializer } => {
+                self.bytes.push(13);
+                self.token(name);
+                self.optional_expr(initializer.as_ref());
+            },
+            Stmt::Switch { keyword, discriminant, cases, default } => {
+                self.bytes.push(14);
+                self.token(keyword);
+                self.expr(discriminant);
+                self.usize(cases.len());
+                for (value, statements) in cases {
+                    self.expr(value);
+                    self.stmts(statements);
+                }
+                self.bool(default.is_some());
+                if let Some(statements) = default {
+                    self.stmts(statements);
+                }
+            },
+            Stmt::Yield { keyword, value } => {
+                self.bytes.push(15);
+                self.token(keyword);
+                self.expr(value);
+            },
+        }
+    }
+}
+
+// Reads back what `Writer` wrote, in the same order. Each method gives `None` if the bytes run out
+// or don't make sense.
+struct Reader<'b> {
+    bytes: &'b [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Option<&[u8]> {
+        let bytes = self.bytes.get(self.position..self.position.checked_add(n)?)?;
+        self.position += n;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn usize(&mut self) -> Option<usize> {
+        let mut n: usize = 0;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.u8()?;
+            n |= usize::from(byte & 0x7f).checked_shl(shift)?;
+            if byte < 0x80 {
+                return Some(n);
+            }
+        }
+        None
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    fn eight_bytes(&mut self) -> Option<[u8; 8]> {
+        self.take(8)?.try_into().ok()
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = self.usize()?;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    // A list of `len()` items read by `item`.
+    fn list<T>(&mut self, item: fn(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let len = self.usize()?;
+        (0..len).map(|_| item(self)).collect()
+    }
+
+    fn optional<T>(&mut self, item: fn(&mut Self) -> Option<T>) -> Option<Option<T>> {
+        match self.bool()? {
+            true => Some(Some(item(self)?)),
+            false => Some(None),
+        }
+    }
+
+    fn token(&mut self) -> Option<Token> {
+        let type_ = TOKEN_TYPES.get(usize::from(self.u8()?))?.to_owned();
+        let lexeme = self.str()?;
+        let literal = self.literal()?;
+        Some(Token::new(type_, &lexeme, literal, self.usize()?, self.usize()?))
+    }
+
+    fn literal(&mut self) -> Option<Literal> {
+        match self.u8()? {
+            0 => Some(Literal::Integer(i64::from_le_bytes(self.eight_bytes()?))),
+            1 => Some(Literal::Number(f64::from_bits(u64::from_le_bytes(self.eight_bytes()?)))),
+            2 => Some(Literal::String_(self.str()?)),
+            3 => Some(Literal::Bool(self.bool()?)),
+            4 => Some(Literal::Nil),
+            _ => None,
+        }
+    }
+
+    fn boxed_expr(&mut self) -> Option<Box<Expr>> {
+        Some(Box::new(self.expr()?))
+    }
+
+    fn params(&mut self) -> Option<Rc<Vec<Param>>> {
+        let params = self.list(|reader| Some(Param { name: reader.token()?, default: reader.optional(Self::expr)?, rest: reader.bool()? }))?;
+        Some(Rc::new(params))
+    }
+
+    fn expr(&mut self) -> Option<Expr> {
+        let expr = match self.u8()? {
+            0 => Expr::Array { elements: self.list(Self::expr)? },
+            1 => Expr::Assign { id: expr::next_id(), name: self.token()?, value: self.boxed_expr()? },
+            2 => Expr::Binary { left: self.boxed_expr()?, operator: self.token()?, right: self.boxed_expr()? },
+            3 => Expr::Call {
+                callee: self.boxed_expr()?,
+                paren: self.token()?,
+                arguments: self.list(Self::expr)?,
+                keywords: self.list(|reader| Some((reader.token()?, reader.expr()?)))?,
+            },
+            4 => Expr::Get { object: self.boxed_expr()?, name: self.token()? },
+            5 => Expr::Grouping { expression: self.boxed_expr()? },
+            6 => Expr::Increment { id: expr::next_id(), name: self.token()?, operator: self.token()?, prefix: self.bool()? },
+            7 => Expr::Index { object: self.boxed_expr()?, bracket: self.token()?, index: self.boxed_expr()? },
+            8 => Expr::Lambda { params: self.params()?, body: Rc::new(self.stmts()?) },
+            9 => Expr::Literal { value: self.literal()? },
+            10 => Expr::Map { brace: self.token()?, entries: self.list(|reader| Some((reader.expr()?, reader.expr()?)))? },
+            11 => Expr::Logical { left: self.boxed_expr()?, operator: self.token()?, right: self.boxed_expr()? },
+            12 => Expr::Set { object: self.boxed_expr()?, name: self.token()?, value: self.boxed_expr()? },
+            13 => Expr::SetIndex {
+                object: self.boxed_expr()?,
+                bracket: self.token()?,
+                index: self.boxed_expr()?,
+                value: self.boxed_expr()?,
+            },
+            14 => Expr::Super { id: expr::next_id(), keyword: self.token()?, method: self.token()? },
+            15 => Expr::Ternary {
+                condition: self.boxed_expr()?,
+                question: self.token()?,
+                then_expr: self.boxed_expr()?,
+                else_expr: self.boxed_expr()?,
+            },
+            16 => Expr::This { id: expr::next_id(), keyword: self.token()? },
+            17 => Expr::Unary { operator: self.token()?, right: self.boxed_expr()? },
+            18 => Expr::Variable { id: expr::next_id(), name: self.token()? },
+            19 => Expr::Spread { ellipsis: self.token()?, expression: self.boxed_expr()? },
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    fn stmts(&mut self) -> Option<Vec<Stmt>> {
+        self.list(Self::stmt)
+    }
+
+    fn boxed_stmt(&mut self) -> Option<Box<Stmt>> {
+        Some(Box::new(self.stmt()?))
+    }
+
+    fn stmt(&mut self) -> Option<Stmt> {
+        let stmt = match self.u8()? {
+            0 => Stmt::Block { statements: self.stmts()? },
+            1 => Stmt::Break { keyword: self.token()? },
+            2 => Stmt::Class { name: self.token()?, superclass: self.optional(Self::expr)?, methods: self.stmts()? },
+            3 => Stmt::Continue { keyword: self.token()? },
+            4 => Stmt::DebugAssert { keyword: self.token()?, condition: self.expr()? },
+            5 => Stmt::Empty,
+            6 => Stmt::Expression { expression: self.expr()? },
+            7 => Stmt::Function { name: self.token()?, params: self.params()?, body: Rc::new(self.stmts()?) },
+            8 => Stmt::If {
+                keyword: self.token()?,
+                condition: self.expr()?,
+                then_branch: self.boxed_stmt()?,
+                else_branch: self.optional(Self::boxed_stmt)?,
+            },
+            9 => Stmt::Import {
+                keyword: self.token()?,
+                path: self.str()?,
+                alias: match self.bool()? { true => Some(self.token()?), false => None },
+            },
+            10 => Stmt::Print { keyword: self.token()?, expression: self.expr()? },
+            11 => Stmt::While {
+                keyword: self.token()?,
+                condition: self.expr()?,
+                body: self.boxed_stmt()?,
+                increment: self.optional(Self::expr)?,
+            },
+            12 => Stmt::Return { keyword: self.token()?, value: self.optional(Self::expr)? },
+            13 => Stmt::Var { name: self.token()?, initializer: self.optional(Self::expr)? },
+            14 => Stmt::Switch {
+                keyword: self.token()?,
+                discriminant: self.expr()?,
+                cases: self.list(|reader| Some((reader.expr()?, reader.stmts()?)))?,
+                default: self.optional(Self::stmts)?,
+            },
+            15 => Stmt::Yield { keyword: self.token()?, value: self.expr()? },
+            _ => return None,
+        };
+        Some(stmt)
+    }
+}
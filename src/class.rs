@@ -0,0 +1,104 @@
+use crate::function::Function;
+use crate::token::{Token, Value};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::ptr;
+use std::rc::Rc;
+
+// A class, created when a `class` declaration is executed. Calling it creates an instance.
+pub struct Class {
+    pub name: Token,
+    pub superclass: Option<Rc<Class>>,
+    pub methods: HashMap<String, Rc<Function>>,
+}
+
+impl Class {
+    // A method of this class, or else inherited from the nearest superclass that has it.
+    pub fn find_method(&self, name: &str) -> Option<&Rc<Function>> {
+        self.methods.get(name)
+            .or_else(|| self.superclass.as_ref().and_then(|superclass| superclass.find_method(name)))
+    }
+
+    // Whether this is `other`, or inherits from it.
+    pub fn is_subclass_of(&self, other: &Class) -> bool {
+        self == other || self.superclass.as_ref().is_some_and(|superclass| superclass.is_subclass_of(other))
+    }
+
+    // Calling the class takes the same arguments as its `init` method, if it has one.
+    pub fn arity(&self) -> RangeInclusive<usize> {
+        self.find_method("init").map_or(0..=0, |init| init.arity())
+    }
+}
+
+// Like functions, two classes are only equal if they are the very same class.
+impl PartialEq for Class {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<class {}>", self.name.lexeme)
+    }
+}
+
+// An instance of a class. Cloning an `Instance` (e.g. assigning it to another variable) gives
+// another reference to the same fields, so a change through one is seen through the other.
+#[derive(Clone)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: Rc<RefCell<HashMap<String, Value>>>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        Self {
+            class,
+            fields: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+
+    // Look up a property: a field if there is one, otherwise a method of the class, bound to this
+    // instance.
+    pub fn get(&self, name: &Token) -> Option<Value> {
+        if let Some(value) = self.fields.borrow().get(&name.lexeme) {
+            return Some(value.clone());
+        }
+        self.class.find_method(&name.lexeme)
+            .map(|method| Value::Function(Rc::new(method.bind(self))))
+    }
+
+    // Fields don't need declaring: setting one that doesn't exist yet creates it.
+    pub fn set(&self, name: &Token, value: Value) {
+        self.fields.borrow_mut().insert(name.lexeme.clone(), value);
+    }
+}
+
+// Two instances are equal if they are the same instance.
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.fields, &other.fields)
+    }
+}
+
+impl fmt::Debug for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} instance>", self.class.name.lexeme)
+    }
+}
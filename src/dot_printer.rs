@@ -0,0 +1,239 @@
+use crate::expr::{Expr, ExprVisitor};
+use crate::stmt::{Param, Stmt, StmtVisitor};
+use crate::token;
+
+use std::rc::Rc;
+
+// Emits the AST as a GraphViz DOT digraph, e.g. for `dot -Tpng`.
+// Each visit adds a node (and edges to its children) and returns the id of that node, so parents
+// can link to it.
+#[derive(Default)]
+pub struct DotPrinter {
+    next_id: usize,
+    lines: Vec<String>,
+}
+
+impl ExprVisitor<usize, ()> for DotPrinter {
+    fn visit_array_expr(&mut self, elements: &[Expr]) -> Result<usize, ()> {
+        let children: Vec<&Expr> = elements.iter().collect();
+        self.node("[]", &[], &children)
+    }
+    fn visit_assign_expr(&mut self, _id: usize, name: &token::Token, value: &Expr) -> Result<usize, ()> {
+        self.node(&format!("= {}", name.lexeme), &[], &[value])
+    }
+    fn visit_binary_expr(&mut self, left: &Expr, operator: &token::Token, right: &Expr) -> Result<usize, ()> {
+        self.node(&operator.lexeme, &[], &[left, right])
+    }
+    // Keyword arguments are listed in the label, like default parameter values, and their values
+    // are the last children.
+    fn visit_call_expr(&mut self, callee: &Expr, _paren: &token::Token, arguments: &[Expr], keywords: &[(token::Token, Expr)]) -> Result<usize, ()> {
+        let mut children = vec![callee];
+        children.extend(arguments);
+        children.extend(keywords.iter().map(|(_, argument)| argument));
+        let mut label = "call".to_owned();
+        if !keywords.is_empty() {
+            let names: Vec<String> = keywords.iter().map(|(name, _)| format!("{}:", name.lexeme)).collect();
+            label = format!("call({})", names.join(", "));
+        }
+        self.node(&label, &[], &children)
+    }
+    fn visit_get_expr(&mut self, object: &Expr, name: &token::Token) -> Result<usize, ()> {
+        self.node(&format!(".{}", name.lexeme), &[], &[object])
+    }
+    fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<usize, ()> {
+        self.node("group", &[], &[expression])
+    }
+    fn visit_increment_expr(&mut self, _id: usize, name: &token::Token, operator: &token::Token, prefix: bool) -> Result<usize, ()> {
+        let label = match prefix {
+            true => format!("{}{}", operator.lexeme, name.lexeme),
+            false => format!("{}{}", name.lexeme, operator.lexeme),
+        };
+        self.node(&label, &[], &[])
+    }
+    fn visit_index_expr(&mut self, object: &Expr, _bracket: &token::Token, index: &Expr) -> Result<usize, ()> {
+        self.node("index", &[], &[object, index])
+    }
+    fn visit_lambda_expr(&mut self, params: &Rc<Vec<Param>>, body: &Rc<Vec<Stmt>>) -> Result<usize, ()> {
+        let children: Vec<&Stmt> = body.iter().collect();
+        self.node(&format!("fun ({})", param_list(params)), &children, &defaults(params))
+    }
+    fn visit_literal_expr(&mut self, value: &token::Literal) -> Result<usize, ()> {
+        let label = match value {
+            token::Literal::String_(x) => format!("\"{}\"", x),
+            _ => value.to_string(),
+        };
+        self.node(&label, &[], &[])
+    }
+    fn visit_map_expr(&mut self, _brace: &token::Token, entries: &[(Expr, Expr)]) -> Result<usize, ()> {
+        let children: Vec<&Expr> = entries.iter().flat_map(|(key, value)| [key, value]).collect();
+        self.node("{}", &[], &children)
+    }
+    fn visit_logical_expr(&mut self, left: &Expr, operator: &token::Token, right: &Expr) -> Result<usize, ()> {
+        self.node(&operator.lexeme, &[], &[left, right])
+    }
+    fn visit_set_expr(&mut self, object: &Expr, name: &token::Token, value: &Expr) -> Result<usize, ()> {
+        self.node(&format!(".{} =", name.lexeme), &[], &[object, value])
+    }
+    fn visit_set_index_expr(&mut self, object: &Expr, _bracket: &token::Token, index: &Expr, value: &Expr) -> Result<usize, ()> {
+        self.node("index =", &[], &[object, index, value])
+    }
+    fn visit_spread_expr(&mut self, _ellipsis: &token::Token, expression: &Expr) -> Result<usize, ()> {
+        self.node("...", &[], &[expression])
+    }
+    fn visit_super_expr(&mut self, _id: usize, _keyword: &token::Token, method: &token::Token) -> Result<usize, ()> {
+        self.node(&format!("super.{}", method.lexeme), &[], &[])
+    }
+    fn visit_ternary_expr(&mut self, condition: &Expr, _question: &token::Token, then_expr: &Expr, else_expr: &Expr) -> Result<usize, ()> {
+        self.node("?:", &[], &[condition, then_expr, else_expr])
+    }
+    fn visit_this_expr(&mut self, _id: usize, _keyword: &token::Token) -> Result<usize, ()> {
+        self.node("this", &[], &[])
+    }
+    fn visit_unary_expr(&mut self, operator: &token::Token, right: &Expr) -> Result<usize, ()> {
+        self.node(&operator.lexeme, &[], &[right])
+    }
+    fn visit_variable_expr(&mut self, _id: usize, name: &token::Token) -> Result<usize, ()> {
+        self.node(&name.lexeme, &[], &[])
+    }
+}
+
+impl StmtVisitor<usize, ()> for DotPrinter {
+    fn visit_block_stmt(&mut self, statements: &[Stmt]) -> Result<usize, ()> {
+        let children: Vec<&Stmt> = statements.iter().collect();
+        self.node("block", &children, &[])
+    }
+    fn visit_break_stmt(&mut self, _keyword: &token::Token) -> Result<usize, ()> {
+        self.node("break", &[], &[])
+    }
+    fn visit_class_stmt(&mut self, name: &token::Token, superclass: Option<&Expr>, methods: &[Stmt]) -> Result<usize, ()> {
+        let children: Vec<&Stmt> = methods.iter().collect();
+        let superclass: Vec<&Expr> = superclass.into_iter().collect();
+        self.node(&format!("class {}", name.lexeme), &children, &superclass)
+    }
+    fn visit_continue_stmt(&mut self, _keyword: &token::Token) -> Result<usize, ()> {
+        self.node("continue", &[], &[])
+    }
+    fn visit_debug_assert_stmt(&mut self, _keyword: &token::Token, condition: &Expr) -> Result<usize, ()> {
+        self.node("debug_assert", &[], &[condition])
+    }
+    fn visit_empty_stmt(&mut self) -> Result<usize, ()> {
+        self.node("empty", &[], &[])
+    }
+    fn visit_expression_stmt(&mut self, expression: &Expr) -> Result<usize, ()> {
+        self.node("expression", &[], &[expression])
+    }
+    fn visit_function_stmt(&mut self, name: &token::Token, params: &Rc<Vec<Param>>, body: &Rc<Vec<Stmt>>) -> Result<usize, ()> {
+        let children: Vec<&Stmt> = body.iter().collect();
+        self.node(&format!("fun {}({})", name.lexeme, param_list(params)), &children, &defaults(params))
+    }
+    fn visit_if_stmt(&mut self, _keyword: &token::Token, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) -> Result<usize, ()> {
+        let mut branches = vec![then_branch];
+        branches.extend(else_branch);
+        self.node("if", &branches, &[condition])
+    }
+    fn visit_import_stmt(&mut self, _keyword: &token::Token, path: &str, alias: Option<&token::Token>) -> Result<usize, ()> {
+        match alias {
+            Some(alias) => self.node(&format!("import \"{}\" as {}", path, alias.lexeme), &[], &[]),
+            None => self.node(&format!("import \"{}\"", path), &[], &[]),
+        }
+    }
+    fn visit_print_stmt(&mut self, _keyword: &token::Token, expression: &Expr) -> Result<usize, ()> {
+        self.node("print", &[], &[expression])
+    }
+    fn visit_while_stmt(&mut self, _keyword: &token::Token, condition: &Expr, body: &Stmt, increment: Option<&Expr>) -> Result<usize, ()> {
+        let mut exprs = vec![condition];
+        exprs.extend(increment);
+        self.node("while", &[body], &exprs)
+    }
+    fn visit_return_stmt(&mut self, _keyword: &token::Token, value: Option<&Expr>) -> Result<usize, ()> {
+        let value: Vec<&Expr> = value.into_iter().collect();
+        self.node("return", &[], &value)
+    }
+    fn visit_var_stmt(&mut self, name: &token::Token, initializer: Option<&Expr>) -> Result<usize, ()> {
+        let initializer: Vec<&Expr> = initializer.into_iter().collect();
+        self.node(&format!("var {}", name.lexeme), &[], &initializer)
+    }
+    // Each case is drawn as a block, with its value as the first child.
+    fn visit_switch_stmt(&mut self, _keyword: &token::Token, discriminant: &Expr, cases: &[(Expr, Vec<Stmt>)], default: Option<&[Stmt]>) -> Result<usize, ()> {
+        let id = self.node("switch", &[], &[discriminant])?;
+        for (value, statements) in cases {
+            let children: Vec<&Stmt> = statements.iter().collect();
+            let case = self.node("case", &children, &[value])?;
+            self.lines.push(format!("node{} -> node{};", id, case));
+        }
+        if let Some(statements) = default {
+            let children: Vec<&Stmt> = statements.iter().collect();
+            let case = self.node("default", &children, &[])?;
+            self.lines.push(format!("node{} -> node{};", id, case));
+        }
+        Ok(id)
+    }
+    fn visit_yield_stmt(&mut self, _keyword: &token::Token, value: &Expr) -> Result<usize, ()> {
+        self.node("yield", &[], &[value])
+    }
+}
+
+impl DotPrinter {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            lines: Vec::new(),
+        }
+    }
+
+    // Interface. All statements hang off a single `program` root node.
+    pub fn print(&mut self, statements: &[Stmt]) -> String {
+        let children: Vec<&Stmt> = statements.iter().collect();
+        self.node("program", &children, &[]).unwrap();
+
+        let mut s = String::from("digraph ast {\n");
+        for line in self.lines.drain(..) {
+            s.push_str("    ");
+            s.push_str(&line);
+            s.push('\n');
+        }
+        s.push('}');
+        s
+    }
+
+    // Add a node labelled `label`, then visit its children and add an edge to each.
+    // Expression children are drawn before statement children, which keeps e.g. the condition of
+    // an `if` to the left of its branches.
+    fn node(&mut self, label: &str, stmts: &[&Stmt], exprs: &[&Expr]) -> Result<usize, ()> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.lines.push(format!("node{} [label=\"{}\"];", id, escape(label)));
+
+        for expr in exprs {
+            let child = self.accept_expr(expr)?;
+            self.lines.push(format!("node{} -> node{};", id, child));
+        }
+        for stmt in stmts {
+            let child = self.accept_stmt(stmt)?;
+            self.lines.push(format!("node{} -> node{};", id, child));
+        }
+        Ok(id)
+    }
+}
+
+// The parameters of a function for its label, e.g. `name, greeting=`. The default values are
+// children of the node, in the same order (see `defaults()`).
+fn param_list(params: &[Param]) -> String {
+    let params: Vec<String> = params.iter()
+        .map(|param| match param.default {
+            Some(_) => format!("{}=", param.name.lexeme),
+            None if param.rest => format!("...{}", param.name.lexeme),
+            None => param.name.lexeme.to_owned(),
+        })
+        .collect();
+    params.join(", ")
+}
+
+fn defaults(params: &[Param]) -> Vec<&Expr> {
+    params.iter().filter_map(|param| param.default.as_ref()).collect()
+}
+
+// Escape characters that are special inside a DOT quoted string.
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
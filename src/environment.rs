@@ -1,38 +1,41 @@
 use crate::token::{Value, Token};
 use crate::error::Error;
+use crate::function::Closure;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Environment {
     // Store the `parent` environment.
-    // Note the decision here to store the owned `Environment`, as opposed to a reference. This is
-    // for my convenience, as storing mutable references would involve lifetimes, and I'm not yet
-    // mentally prepared for that. (I'm not sure if it's even possible...!)
-    enclosing: Option<Box<Environment>>,
+    // Scopes are shared by reference rather than cloned: a block and any function declared in it
+    // both hold on to the same enclosing scope, so an assignment through one is seen by the other.
+    enclosing: Option<Rc<RefCell<Environment>>>,
 
     // Uninitialized identifiers will have value `None`.
     values: HashMap<String, Option<Value>>,
-
-    // Store the modified values of the `parent` environment.
-    // This is because `enclosing` is a clone of the `parent` environment. Hence, changes made by
-    // `assign` in this environment will not `be saved` once the environments have been swapped
-    // back in the interpreter. See `update()`.
-    parent_modified: HashMap<String, Value>,
 }
 
 impl Environment {
-    pub fn new(enclosing: Option<Environment>) -> Self {
+    pub fn new(enclosing: Option<Rc<RefCell<Environment>>>) -> Self {
         Self {
-            enclosing: enclosing.map(Box::new),
+            enclosing,
             values: HashMap::new(),
-            parent_modified: HashMap::new(),
         }
     }
 
+    // Empty this scope and make `enclosing` its enclosing scope, keeping the memory of its table, so
+    // that it can be reused as a new scope. See `Interpreter::new_scope()`.
+    pub fn reset(&mut self, enclosing: Option<Rc<RefCell<Environment>>>) {
+        self.enclosing = enclosing;
+        self.values.clear();
+    }
+
     // Define a new identifier. Can be `None` (uninitialized).
     pub fn define(&mut self, name: String, value: Option<&Value>) {
-        self.values.insert(name, value.cloned());
+        let value = value.map(|value| self.store(value));
+        self.values.insert(name, value);
     }
 
     // Get the value assigned to `name`. Return the `Option<>` - the calling function will have to
@@ -40,12 +43,12 @@ impl Environment {
     pub fn get(&self, name: &Token) -> Result<Option<Value>, Error> {
         let v = self.values.get(&name.lexeme);
         match v {
-            Some(x) => Ok(x.clone()),
+            Some(x) => Ok(x.as_ref().map(load)),
             None => {
                 // If the variable is not found in this scope, maybe it is found in the enclosing
                 // scope? Recursively search enclosing scopes for the variable.
                 if let Some(enclosing) = &self.enclosing {
-                    Ok(enclosing.get(name)?)
+                    enclosing.borrow().get(name)
                 } else {
                     // Variable not found and this scope is the outermost.
                     Err(self.undefined_variable_error(name))
@@ -58,52 +61,108 @@ impl Environment {
     // Note here `value` is *not* `Option<Value>`.
     pub fn assign(&mut self, name: &Token, value: &Value) -> Result<(), Error> {
         if self.values.contains_key(&name.lexeme) {
-            self.values.insert(name.lexeme.clone(), Some(value.to_owned()));
+            self.values.insert(name.lexeme.clone(), Some(self.store(value)));
             Ok(())
         } else {
             // See above.
-            if let Some(enclosing) = &mut self.enclosing {
-                enclosing.assign(name, value)?;
-                // Keep track of changes made to the `parent` environment, so they can be `saved`.
-                self.parent_modified.insert(name.lexeme.clone(), value.to_owned());
-                Ok(())
+            if let Some(enclosing) = &self.enclosing {
+                enclosing.borrow_mut().assign(name, value)
             } else {
                 Err(self.undefined_variable_error(name))
             }
         }
     }
 
-    // Given a `child` environment, `save` the changes made from the `child` environment.
-    pub fn update(&mut self, update_from: &mut Environment) {
-        for (key, value) in &update_from.parent_modified {
-            self.assign_string(key, value);
+    // The value of `name` in this scope only, if it is defined and initialized here.
+    pub fn get_here(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned().flatten().as_ref().map(load)
+    }
+
+    // The initialized values defined in this scope only, by name.
+    pub fn values_here(&self) -> Vec<(String, Value)> {
+        self.values.iter()
+            .filter_map(|(name, value)| value.as_ref().map(|value| (name.clone(), load(value))))
+            .collect()
+    }
+
+    // The initialized values visible from this scope, by name: those of this scope first, then
+    // those of each enclosing one in turn that aren't shadowed. Each scope's are in name order.
+    pub fn variables(&self) -> Vec<(String, Value)> {
+        let mut variables = self.values_here();
+        variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+        if let Some(enclosing) = &self.enclosing {
+            for (name, value) in enclosing.borrow().variables() {
+                if !self.values.contains_key(&name) {
+                    variables.push((name, value));
+                }
+            }
         }
+        variables
+    }
+
+    // The names defined in this scope and in each enclosing one, innermost first, for resolving
+    // code that is run in this scope after the fact, like `eval()`'s.
+    pub fn scope_names(&self) -> Vec<Vec<String>> {
+        let mut names = match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().scope_names(),
+            None => Vec::new(),
+        };
+        names.insert(0, self.values.keys().cloned().collect());
+        names
     }
 
-    // Similar to `assign()`, but takes a `String` for a `name` as opposed to a `Token`.
-    // This is because `parent_modified` cannot store `Token`s as they are not hashable.
-    fn assign_string(&mut self, name: &String, value: &Value) {
-        if self.values.contains_key(name) {
-            self.values.insert(name.to_owned(), Some(value.to_owned()));
+    // Like `get()`, but look directly in the scope `distance` levels up, as worked out by the
+    // resolver, instead of searching outwards.
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Option<Value>, Error> {
+        if distance == 0 {
+            let value = self.values.get(&name.lexeme).ok_or_else(|| self.undefined_variable_error(name))?;
+            Ok(value.as_ref().map(load))
         } else {
-            if let Some(enclosing) = &mut self.enclosing {
-                enclosing.assign_string(name, value);
-                self.parent_modified.insert(name.clone(), value.to_owned());
-            } else {
-                // Note this is should not be possible, since all variables in `parent_modified` are
-                // there because the variable has been found in the `parent` environment.
-                // However, there may be some edge cases if it is possible to `drop` a variable.
-                panic!("Variable {name} not found when calling `assign_string`");
-            }
+            self.ancestor().borrow().get_at(distance - 1, name)
         }
     }
 
+    // Like `assign()`, but in the scope `distance` levels up.
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: &Value) -> Result<(), Error> {
+        if distance == 0 {
+            self.values.insert(name.lexeme.clone(), Some(self.store(value)));
+            Ok(())
+        } else {
+            self.ancestor().borrow_mut().assign_at(distance - 1, name, value)
+        }
+    }
+
+    // `value` as it is stored in this scope: a function that closes over this very scope only keeps
+    // a weak reference to it, so that the two don't keep each other alive (see `Closure`).
+    fn store(&self, value: &Value) -> Value {
+        match value {
+            Value::Function(function) if function.closure.is_strong_to(self) => {
+                let weak = Closure::Weak(Rc::downgrade(&function.closure.environment()));
+                Value::Function(Rc::new(function.with_closure(weak)))
+            },
+            _ => value.clone(),
+        }
+    }
+
+    // The enclosing scope. The resolver never gives a distance beyond the outermost scope.
+    fn ancestor(&self) -> &Rc<RefCell<Environment>> {
+        self.enclosing.as_ref().expect("Resolved scope distance is past the outermost scope")
+    }
+
     // Helper function to return a RuntimeError for undefined variables.
     fn undefined_variable_error(&self, token: &Token) -> Error {
-        Error::RuntimeError {
-            token: token.to_owned(),
-            message: format!("Undefined variable '{}'.", token.lexeme)
-        }
+        Error::runtime(token, &format!("Undefined variable '{}'.", token.lexeme))
     }
 }
 
+// A value stored in a scope, as it is read out: a function stored with a weak closure (see
+// `Environment::store()`) gets a strong one again, since it can now outlive the scope.
+fn load(value: &Value) -> Value {
+    match value {
+        Value::Function(function) if matches!(function.closure, Closure::Weak(_)) => {
+            let strong = Closure::Strong(function.closure.environment());
+            Value::Function(Rc::new(function.with_closure(strong)))
+        },
+        _ => value.clone(),
+    }
+}
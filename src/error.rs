@@ -1,12 +1,93 @@
-use crate::token::Token;
+use crate::function::Function;
+use crate::token::{Token, TokenType, Value};
 
+use std::fmt;
+use std::rc::Rc;
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone)]
 pub enum Error {
-    ScanError,
-    ParseError,
+    // The scanner, parser and resolver carry on after an error, so that they can return all of the
+    // errors in the program at once.
+    ScanError {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    // Also used by the resolver.
+    ParseError {
+        token: Token,
+        message: String,
+    },
     // RuntimeError,
     RuntimeError {
         token: Token,
         message: String,
     },
+
+    // Not really an error: a `return` statement unwinds to the function call it is in by bubbling
+    // this up, carrying the returned value. See `Interpreter::call_function()`.
+    Return(Value),
+
+    // Like `Return`, but for `return f(...)`: the call is left for the enclosing call to make in
+    // its place, so that tail recursion runs in constant stack space.
+    TailCall(Box<TailCall>),
+
+    // Similarly, `break` and `continue` unwind to the innermost loop. See `visit_while_stmt()`.
+    Break,
+    Continue,
+}
+
+// A call to a user-defined function in tail position, with its arguments already evaluated.
+#[derive(Debug, Clone)]
+pub struct TailCall {
+    pub callee: Rc<Function>,
+    pub paren: Token,  // where errors from the call are reported
+    pub arguments: Vec<Value>,
+}
+
+impl Error {
+    pub fn scan(line: usize, column: usize, message: &str) -> Self {
+        Error::ScanError { line, column, message: message.to_owned() }
+    }
+
+    pub fn parse(token: &Token, message: &str) -> Self {
+        Error::ParseError { token: token.to_owned(), message: message.to_owned() }
+    }
+
+    // Where in the source the error is, as `(line, column)`.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match self {
+            Error::ScanError { line, column, .. } => Some((*line, *column)),
+            Error::ParseError { token, .. } | Error::RuntimeError { token, .. } => Some((token.line, token.column)),
+            _ => None,
+        }
+    }
+
+    pub fn runtime(token: &Token, message: &str) -> Self {
+        Error::RuntimeError { token: token.to_owned(), message: message.to_owned() }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ScanError { line, column, message } => {
+                write!(f, "[line {}, col {}] Error: {}", line, column, message)
+            },
+            Error::ParseError { token, message } | Error::RuntimeError { token, message } => {
+                let loc = match token.type_ {
+                    TokenType::Eof => " at end".to_owned(),
+                    _ => format!(" at '{}'", token.lexeme),
+                };
+                write!(f, "[line {}, col {}] Error{}: {}", token.line, token.column, loc, message)
+            },
+            // These only escape if the parser let a stray statement through.
+            Error::Return(_) | Error::TailCall(_) => write!(f, "Can't return from top-level code."),
+            Error::Break => write!(f, "Can't use 'break' outside of a loop or switch."),
+            Error::Continue => write!(f, "Can't use 'continue' outside of a loop."),
+        }
+    }
 }
 
+impl std::error::Error for Error {}
@@ -1,9 +1,40 @@
 use crate::token;
+use crate::stmt::{Param, Stmt};
 
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// `Variable`, `Assign`, `Increment`, `Super` and `This` expressions carry a unique `id`, which the resolver uses as the key of the
+// interpreter's side table of scope depths. The counter is global so that ids stay unique across
+// separately parsed sources, e.g. successive REPL lines whose functions outlive their line.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub fn next_id() -> ExprId {
+    ExprId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+// The id of an expression. Ids aren't part of the structure of the tree, so they are left out when
+// comparing them: the same source parsed twice, or an AST loaded back from the cache (see `cache`),
+// gives an equal tree, though with new ids.
+#[derive(Debug, Clone, Copy)]
+pub struct ExprId(pub usize);
+
+impl PartialEq for ExprId {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub enum Expr {
+    // An array literal, `[a, b, c]`.
+    Array {
+        elements: Vec<Expr>,
+    },
     // Assignment is an expression since it returns a value, so that expressions like `a = b = 2`
     // are possible.
     Assign {
+        id: ExprId,
         name: token::Token,
         value: Box<Expr>,
     },
@@ -12,59 +43,203 @@ pub enum Expr {
         operator: token::Token,
         right: Box<Expr>,
     },
+    Call {
+        callee: Box<Expr>,
+        paren: token::Token,  // the closing `)`, for error reporting
+        arguments: Vec<Expr>,
+        keywords: Vec<(token::Token, Expr)>,  // `name: value` arguments, after the positional ones
+    },
+    // Property access, `object.name`.
+    Get {
+        object: Box<Expr>,
+        name: token::Token,
+    },
     Grouping {
         expression: Box<Expr>,
     },
+    // `++name`, `--name`, `name++` or `name--`. `prefix` expressions give the new value, and postfix
+    // ones the old value.
+    Increment {
+        id: ExprId,
+        name: token::Token,
+        operator: token::Token,
+        prefix: bool,
+    },
+    // `object[index]`.
+    Index {
+        object: Box<Expr>,
+        bracket: token::Token,  // the closing `]`, for error reporting
+        index: Box<Expr>,
+    },
+    // An anonymous function, `fun (params) { body }`.
+    Lambda {
+        params: Rc<Vec<Param>>,
+        body: Rc<Vec<Stmt>>,
+    },
     Literal {
         value: token::Literal,
     },
+    // A map literal, `{ key: value, ... }`.
+    Map {
+        brace: token::Token,  // the closing `}`, for error reporting
+        entries: Vec<(Expr, Expr)>,
+    },
     Logical {
         left: Box<Expr>,
         operator: token::Token,
         right: Box<Expr>,
     },
+    // Property assignment, `object.name = value`.
+    Set {
+        object: Box<Expr>,
+        name: token::Token,
+        value: Box<Expr>,
+    },
+    // `object[index] = value`.
+    SetIndex {
+        object: Box<Expr>,
+        bracket: token::Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+    // `...expression` in an argument list or array literal, which spreads the elements of an array
+    // into it.
+    Spread {
+        ellipsis: token::Token,
+        expression: Box<Expr>,
+    },
+    // `super.method`, looking `method` up starting from the superclass.
+    Super {
+        id: ExprId,
+        keyword: token::Token,
+        method: token::Token,
+    },
+    Ternary {
+        condition: Box<Expr>,
+        question: token::Token,
+        then_expr: Box<Expr>,
+        else_expr: Box<Expr>,
+    },
+    This {
+        id: ExprId,
+        keyword: token::Token,
+    },
     Unary {
         operator: token::Token,
         right: Box<Expr>,
     },
     Variable {
+        id: ExprId,
         name: token::Token,
     },
 }
 
+// The line `expr` starts on: that of its first token, as far as the tree keeps it. A literal has no
+// token, so it has none.
+pub fn line(expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Array { elements } => elements.first().and_then(line),
+        Expr::Binary { left, operator, .. } | Expr::Logical { left, operator, .. } => line(left).or(Some(operator.line)),
+        Expr::Call { callee, paren, .. } => line(callee).or(Some(paren.line)),
+        Expr::Get { object, name } | Expr::Set { object, name, .. } => line(object).or(Some(name.line)),
+        Expr::Grouping { expression } => line(expression),
+        Expr::Index { object, bracket, .. } | Expr::SetIndex { object, bracket, .. } => line(object).or(Some(bracket.line)),
+        Expr::Lambda { params, .. } => params.first().map(|param| param.name.line),
+        Expr::Literal { .. } => None,
+        Expr::Map { brace, entries } => entries.first().and_then(|(key, _)| line(key)).or(Some(brace.line)),
+        Expr::Ternary { condition, question, .. } => line(condition).or(Some(question.line)),
+        Expr::Assign { name, .. } | Expr::Variable { name, .. } => Some(name.line),
+        Expr::Increment { name, operator, prefix, .. } => Some(if *prefix { operator.line } else { name.line }),
+        Expr::Spread { ellipsis: token, .. } | Expr::Super { keyword: token, .. } | Expr::This { keyword: token, .. } |
+        Expr::Unary { operator: token, .. } => Some(token.line),
+    }
+}
+
 pub trait ExprVisitor<T, E> {
     fn accept_expr(&mut self, expr: &Expr) -> Result<T, E> {
         match expr {
-            Expr::Assign { name, value } => {
-                self.visit_assign_expr(name, value)
+            Expr::Array { elements } => {
+                self.visit_array_expr(elements)
+            },
+            Expr::Assign { id, name, value } => {
+                self.visit_assign_expr(id.0, name, value)
             },
             Expr::Binary { left, operator, right } => {
                 self.visit_binary_expr(left, operator, right)
             },
+            Expr::Call { callee, paren, arguments, keywords } => {
+                self.visit_call_expr(callee, paren, arguments, keywords)
+            },
+            Expr::Get { object, name } => {
+                self.visit_get_expr(object, name)
+            },
             Expr::Grouping { expression } => {
                 self.visit_grouping_expr(expression)
             },
+            Expr::Increment { id, name, operator, prefix } => {
+                self.visit_increment_expr(id.0, name, operator, *prefix)
+            },
+            Expr::Index { object, bracket, index } => {
+                self.visit_index_expr(object, bracket, index)
+            },
+            Expr::Lambda { params, body } => {
+                self.visit_lambda_expr(params, body)
+            },
             Expr::Literal { value } => {
                 self.visit_literal_expr(value)
             },
+            Expr::Map { brace, entries } => {
+                self.visit_map_expr(brace, entries)
+            },
             Expr::Logical { left, operator, right } => {
                 self.visit_logical_expr(left, operator, right)
             },
+            Expr::Set { object, name, value } => {
+                self.visit_set_expr(object, name, value)
+            },
+            Expr::SetIndex { object, bracket, index, value } => {
+                self.visit_set_index_expr(object, bracket, index, value)
+            },
+            Expr::Spread { ellipsis, expression } => {
+                self.visit_spread_expr(ellipsis, expression)
+            },
+            Expr::Super { id, keyword, method } => {
+                self.visit_super_expr(id.0, keyword, method)
+            },
+            Expr::Ternary { condition, question, then_expr, else_expr } => {
+                self.visit_ternary_expr(condition, question, then_expr, else_expr)
+            },
+            Expr::This { id, keyword } => {
+                self.visit_this_expr(id.0, keyword)
+            },
             Expr::Unary { operator, right } => {
                 self.visit_unary_expr(operator, right)
             },
-            Expr::Variable { name } => {
-                self.visit_variable_expr(name)
+            Expr::Variable { id, name } => {
+                self.visit_variable_expr(id.0, name)
             },
         }
     }
 
-    fn visit_assign_expr(&mut self, name: &token::Token, value: &Expr) -> Result<T, E>;
+    fn visit_array_expr(&mut self, elements: &[Expr]) -> Result<T, E>;
+    fn visit_assign_expr(&mut self, id: usize, name: &token::Token, value: &Expr) -> Result<T, E>;
     fn visit_binary_expr(&mut self, left: &Expr, operator: &token::Token, right: &Expr) -> Result<T, E>;
+    fn visit_call_expr(&mut self, callee: &Expr, paren: &token::Token, arguments: &[Expr], keywords: &[(token::Token, Expr)]) -> Result<T, E>;
+    fn visit_get_expr(&mut self, object: &Expr, name: &token::Token) -> Result<T, E>;
     fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<T, E>;
+    fn visit_increment_expr(&mut self, id: usize, name: &token::Token, operator: &token::Token, prefix: bool) -> Result<T, E>;
+    fn visit_index_expr(&mut self, object: &Expr, bracket: &token::Token, index: &Expr) -> Result<T, E>;
+    fn visit_lambda_expr(&mut self, params: &Rc<Vec<Param>>, body: &Rc<Vec<Stmt>>) -> Result<T, E>;
     fn visit_literal_expr(&mut self, value: &token::Literal) -> Result<T, E>;
+    fn visit_map_expr(&mut self, brace: &token::Token, entries: &[(Expr, Expr)]) -> Result<T, E>;
     fn visit_logical_expr(&mut self, left: &Expr, operator: &token::Token, right: &Expr) -> Result<T, E>;
+    fn visit_set_expr(&mut self, object: &Expr, name: &token::Token, value: &Expr) -> Result<T, E>;
+    fn visit_set_index_expr(&mut self, object: &Expr, bracket: &token::Token, index: &Expr, value: &Expr) -> Result<T, E>;
+    fn visit_spread_expr(&mut self, ellipsis: &token::Token, expression: &Expr) -> Result<T, E>;
+    fn visit_super_expr(&mut self, id: usize, keyword: &token::Token, method: &token::Token) -> Result<T, E>;
+    fn visit_ternary_expr(&mut self, condition: &Expr, question: &token::Token, then_expr: &Expr, else_expr: &Expr) -> Result<T, E>;
+    fn visit_this_expr(&mut self, id: usize, keyword: &token::Token) -> Result<T, E>;
     fn visit_unary_expr(&mut self, operator: &token::Token, right: &Expr) -> Result<T, E>;
-    fn visit_variable_expr(&mut self, name: &token::Token) -> Result<T, E>;
+    fn visit_variable_expr(&mut self, id: usize, name: &token::Token) -> Result<T, E>;
 }
 
@@ -0,0 +1,187 @@
+use crate::class::Instance;
+use crate::environment::Environment;
+use crate::error::Error;
+use crate::interpreter::Interpreter;
+use crate::stmt::{Param, Stmt};
+use crate::token::{Token, Value};
+
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::ptr;
+use std::rc::{Rc, Weak};
+
+// A user-defined function, created when a `fun` declaration is executed or a lambda is evaluated.
+// The body is shared with the `Stmt::Function` it came from, so creating the value (and cloning it
+// around as a `Value`) doesn't copy the AST.
+pub struct Function {
+    pub name: Option<Token>,  // `None` for a lambda
+    pub params: Rc<Vec<Param>>,
+    pub body: Rc<Vec<Stmt>>,
+
+    // The scope the function was declared in. Calls run in a new scope enclosed by this one, so
+    // the body sees the variables around its declaration rather than those around the call.
+    pub closure: Closure,
+
+    // A class's `init` method, which always returns the instance being initialized.
+    pub is_initializer: bool,
+
+    // A function whose body has a `yield`, so a call returns what it yields. See
+    // `Interpreter::call_generator()`.
+    pub is_generator: bool,
+}
+
+impl Function {
+    // How many arguments a call can take: parameters with a default value can be left out, and a
+    // rest parameter takes any number of them.
+    pub fn arity(&self) -> RangeInclusive<usize> {
+        let required = self.params.iter().filter(|param| param.default.is_none() && !param.rest).count();
+        match self.params.last() {
+            Some(param) if param.rest => required..=usize::MAX,
+            _ => required..=self.params.len(),
+        }
+    }
+
+    // The method bound to `instance`: the same function, but in a scope where `this` is the
+    // instance.
+    pub fn bind(&self, instance: &Instance) -> Function {
+        let mut environment = Environment::new(Some(self.closure.environment()));
+        environment.define("this".to_owned(), Some(&Value::Instance(instance.clone())));
+        self.with_closure(Closure::Strong(Rc::new(RefCell::new(environment))))
+    }
+
+    // The same function, closing over `closure` instead.
+    pub fn with_closure(&self, closure: Closure) -> Function {
+        Function {
+            name: self.name.clone(),
+            params: Rc::clone(&self.params),
+            body: Rc::clone(&self.body),
+            closure,
+            is_initializer: self.is_initializer,
+            is_generator: self.is_generator,
+        }
+    }
+}
+
+// Two functions are only equal if they come from the same declaration and close over the same
+// scope: two declarations with the same name and body are still different functions, and so are
+// the functions one declaration creates in different calls, or the methods bound to different
+// instances.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.body, &other.body) && ptr::eq(self.closure.as_ptr(), other.closure.as_ptr())
+    }
+}
+
+// The scope a function closes over. This is usually a strong reference, but a function stored in
+// the very scope it closes over, like any function declared in a block, would then keep the scope
+// alive while the scope keeps the function alive: a cycle `Rc` can never free. So the scope stores
+// such a function with a weak reference instead, and makes it strong again when the function is
+// read back out (see `Environment::get()`), which can only happen while the scope is alive.
+pub enum Closure {
+    Strong(Rc<RefCell<Environment>>),
+    Weak(Weak<RefCell<Environment>>),
+}
+
+impl Closure {
+    // The scope itself. A function with a weak closure is never called: it is only ever stored in
+    // the scope, and comes out strong.
+    pub fn environment(&self) -> Rc<RefCell<Environment>> {
+        match self {
+            Closure::Strong(environment) => Rc::clone(environment),
+            Closure::Weak(environment) => environment.upgrade().expect("Function outlived the scope it was stored in"),
+        }
+    }
+
+    // Whether this is a strong reference to `environment`.
+    pub fn is_strong_to(&self, environment: &Environment) -> bool {
+        matches!(self, Closure::Strong(closure) if ptr::eq(closure.as_ptr(), environment))
+    }
+
+    fn as_ptr(&self) -> *const RefCell<Environment> {
+        match self {
+            Closure::Strong(environment) => Rc::as_ptr(environment),
+            Closure::Weak(environment) => environment.as_ptr(),
+        }
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<fn {}>", name.lexeme),
+            None => write!(f, "<lambda>"),
+        }
+    }
+}
+
+// A function built into the interpreter and implemented in Rust, such as `clock`. These are defined
+// in the global scope when the interpreter is created (see `Interpreter::new`).
+// `function` gets the already arity-checked arguments, and the interpreter, to call back into
+// functions passed as arguments (see `Interpreter::call_value()`).
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: RangeInclusive<usize>,  // ending in `usize::MAX` if there is no limit
+
+    pub function: fn(&mut Interpreter, &[Value]) -> Result<Value, CallError>,
+
+    // Arguments passed to `function` ahead of those of the call, which `partial` binds.
+    pub bound: Vec<Value>,
+}
+
+impl NativeFunction {
+    pub fn new(name: &'static str, arity: RangeInclusive<usize>, function: fn(&mut Interpreter, &[Value]) -> Result<Value, CallError>) -> Self {
+        NativeFunction { name, arity, function, bound: Vec::new() }
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+// Why calling a value failed: either a message about the call itself, like a wrong number of
+// arguments or a native's bad argument, which the interpreter reports at the call; or an error
+// from running the callee's body, which is passed on as it is, already carrying its position.
+#[derive(Debug)]
+pub enum CallError {
+    Message(String),
+    Error(Error),
+}
+
+impl From<String> for CallError {
+    fn from(message: String) -> Self {
+        CallError::Message(message)
+    }
+}
+
+impl From<&str> for CallError {
+    fn from(message: &str) -> Self {
+        CallError::Message(message.to_owned())
+    }
+}
+
+impl From<Error> for CallError {
+    fn from(error: Error) -> Self {
+        CallError::Error(error)
+    }
+}
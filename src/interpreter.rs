@@ -1,19 +1,130 @@
 use crate::expr::{self, ExprVisitor};
 use crate::stmt::{self, StmtVisitor};
-use crate::token::{self, TokenType, Value};
+use crate::token::{self, MapKey, TokenType, Value};
 use crate::environment::Environment;
-use crate::error::Error;
+use crate::error::{Error, TailCall};
+use crate::function::{CallError, Closure, Function};
+use crate::class::{Class, Instance};
+use crate::natives;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::resolver::Resolver;
+use crate::warn::Warning;
 
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
 use std::mem;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-pub struct Interpreter<'a> {
-    pub environment: &'a mut Environment,
+// A function called before each statement is run (see `Interpreter::on_statement`).
+pub type StatementHook = Box<dyn FnMut(&Interpreter, usize)>;
+
+pub struct Interpreter {
+    // The outermost scope, and the current (innermost) one.
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+
+    // Side table filled in by the resolver: for each local `Variable`/`Assign` expression id, how
+    // many scopes up from the current one its variable lives. Ids missing from it are globals.
+    locals: HashMap<usize, usize>,
+
+    // In release mode `debug_assert` statements are skipped without evaluating their condition.
+    pub release: bool,
+
+    // Escape non-ASCII characters in `print` output as `\u{...}`, for terminals that can't take
+    // UTF-8.
+    pub ascii_out: bool,
+
+    // Require `if`/`while`/`for` conditions and the operands of `and`/`or` to be booleans, rather
+    // than going by truthiness.
+    pub strict_bool: bool,
+
+    // Let float arithmetic give infinities and NaN, as IEEE 754 does, instead of erring.
+    pub ieee_floats: bool,
+
+    // Make `/` on two integers truncating integer division, like Rust's, so `7 / 2` is `3`. A float
+    // operand still gives float division.
+    pub int_div: bool,
+
+    // Show numbers with `,` as the decimal separator, e.g. `3,5`, in `print`, concatenation and
+    // `str()`. Literals are still written with `.`, and so are numbers shown as code, like the
+    // elements of an array or by `repr()`.
+    pub decimal_comma: bool,
+
+    // Group the digits of the whole part of numbers shown like `decimal_comma` says in threes, with
+    // `,`, or `.` under `decimal_comma`, e.g. `1,234,567.5` or `1.234.567,5`.
+    pub group_thousands: bool,
+
+    // The keyword table imported scripts (and anything run with `run_in()`) are scanned with, in
+    // place of the default English one, e.g. for a dialect. See `Scanner::new()`.
+    pub keywords: Option<HashMap<String, TokenType>>,
+
+    // Define the functions declared in a block (or script) before running any of its statements,
+    // so they can be called from anywhere in it, even before their declaration. See `run_order()`.
+    pub hoist: bool,
+
+    // Disable `eval()`, for hosts running scripts they don't trust to build and run code on the fly.
+    pub sandbox: bool,
+
+    // The most characters a string, or elements an array, made by the script may have, if limited,
+    // for hosts running scripts they don't trust not to use up all the memory. Concatenating,
+    // spreading, `push` and the like fail with "Value too large." instead of going over it.
+    pub max_size: Option<usize>,
+
+    // Have the resolver warn about a local variable that shadows one in an enclosing local scope.
+    pub warn_shadow: bool,
+
+    // Count `assert`/`expect` checks in `test_results`, and carry on after a failed one instead of
+    // raising it, so a script used as a test suite runs to the end and reports every failure.
+    pub test_mode: bool,
+
+    // Called before each statement is run, with the interpreter and the line the statement starts
+    // on, e.g. for a debugger to pause and show `variables()`. See `stmt::line()` for the
+    // statements it isn't called for.
+    pub on_statement: Option<StatementHook>,
+
+    // The checks run so far in test mode.
+    test_results: TestResults,
+
+    // The line of the last call to a native function, for a failed check to say where it was.
+    native_call_line: usize,
+
+    // The values yielded so far by the generator being called, if any.
+    yielded: Option<Vec<Value>>,
+
+    // The warnings found so far, waiting for the caller to take and report them.
+    warnings: Vec<Warning>,
+
+    // Where `print` writes to: stdout, unless given to `with_output()`.
+    output: Box<dyn Write>,
+
+    // Where `lines()` reads from: stdin, unless replaced, e.g. by an embedding host or a test.
+    pub input: Box<dyn BufRead>,
+
+    // The script being run, which `import` paths are relative to. They are relative to the working
+    // directory if this isn't set, e.g. at the prompt.
+    pub current_file: Option<PathBuf>,
+
+    // The (canonical) paths of the scripts whose `import` is being run, outermost first, and of
+    // those already imported, which are not run again.
+    importing: Vec<PathBuf>,
+    imported: HashSet<PathBuf>,
+
+    // The emptied scopes of finished calls, for later calls to reuse instead of allocating new ones.
+    // See `new_scope()`.
+    scope_pool: Vec<Rc<RefCell<Environment>>>,
 }
 
 // Expression evaluation.
 // Note the return enum is `Value`, which is similar to a `Literal`, but specifically represents
 // the values of evaluated expressions.
-impl<'a> ExprVisitor<Value, Error> for Interpreter<'a> {
+impl ExprVisitor<Value, Error> for Interpreter {
     fn visit_literal_expr(&mut self, value: &token::Literal) -> Result<Value, Error> {
         Ok(Value::from(value.to_owned()))
     }
@@ -24,59 +135,194 @@ impl<'a> ExprVisitor<Value, Error> for Interpreter<'a> {
         // The implementation in the book returns a value with `appropriate truthiness`.
         // Note how it `short-circuits`, in that `right` is only evaluated if the `truthiness`
         // can not be deduced from `left_eval` right away.
+        // The result is always one of the operands themselves, never a coerced `Value::Bool`:
+        // `"a" and "b"` is `"b"`, `nil and x` is `nil` (without evaluating `x`), and `0 or 5` is
+        // `0`, since `0` is truthy. Users rely on this for `x or default` idioms.
         match operator.type_ {
             TokenType::Or => {
-                if self.is_truthy(&left_eval) {
+                if self.is_condition_true(&left_eval, operator)? {
                     // The expression is definitely going to be truthy, so return `left_eval` right
                     // away.
                     Ok(left_eval)
                 } else {
                     // The expression might be truthy, depending on the value of `right`. So return
                     // that.
-                    Ok(self.evaluate(right)?)
+                    self.evaluate_operand(right, operator)
                 }
             },
             TokenType::And => {
-                if self.is_truthy(&left_eval) {
+                if self.is_condition_true(&left_eval, operator)? {
                     // The expression might be truthy, depending on the value of `right`. So return
                     // that.
-                    Ok(self.evaluate(right)?)
+                    self.evaluate_operand(right, operator)
                 } else {
                     // The expression is definitely *not* going to be truthy, so return `left_eval`
                     // right away.
                     Ok(left_eval)
                 }
             },
+            // Unlike the others, `xor` can't short-circuit: it always needs both operands. And
+            // since neither operand alone gives the answer, it returns a `Value::Bool`.
+            TokenType::Xor => {
+                let left_true = self.is_condition_true(&left_eval, operator)?;
+                let right_eval = self.evaluate_operand(right, operator)?;
+                let right_true = self.is_condition_true(&right_eval, operator)?;
+                Ok(Value::Bool(left_true != right_true))
+            },
 
             // Note no other operator type is reachable, since the parser builds logical expressions
-            // if and only if the operator is either `Or` or `And`.
+            // if and only if the operator is `Or`, `And` or `Xor`.
             _ => unreachable!(),
         }
+    }
+
+    fn visit_call_expr(&mut self, callee: &expr::Expr, paren: &token::Token, arguments: &[expr::Expr], keywords: &[(token::Token, expr::Expr)]) -> Result<Value, Error> {
+        let callee_eval = self.evaluate(callee)?;
+
+        // Arguments are evaluated left to right, before the call.
+        let arguments_eval = self.evaluate_elements(arguments)?;
+        if let Value::NativeFn(_) = callee_eval {
+            self.native_call_line = paren.line;
+        }
+        if keywords.is_empty() {
+            return self.call_value(&callee_eval, arguments_eval).map_err(|error| self.call_error(paren, error));
+        }
+        let mut keywords_eval = Vec::new();
+        for (name, argument) in keywords {
+            keywords_eval.push((name, self.evaluate(argument)?));
+        }
+
+        self.call_with_keywords(&callee_eval, arguments_eval, keywords_eval).map_err(|error| self.call_error(paren, error))
+    }
+
+    fn visit_array_expr(&mut self, elements: &[expr::Expr]) -> Result<Value, Error> {
+        let elements_eval = self.evaluate_elements(elements)?;
+        Ok(Value::Array(Rc::new(RefCell::new(elements_eval)), false))
+    }
+
+    // Keys are checked as they are evaluated, so `{nil: f()}` fails before calling `f`.
+    fn visit_map_expr(&mut self, brace: &token::Token, entries: &[(expr::Expr, expr::Expr)]) -> Result<Value, Error> {
+        let mut map = BTreeMap::new();
+        for (key, value) in entries {
+            let key_eval = self.evaluate(key)?;
+            let key_eval = self.map_key(&key_eval, brace)?;
+            map.insert(key_eval, self.evaluate(value)?);
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map)), false))
+    }
 
-        // This will return the actual `Value::Bool` of the expression.
-        // match operator.type_ {
-        //     TokenType::And => {
-        //         match self.is_truthy(&left_eval) {
-        //             true => Ok(Value::Bool(true)),
-        //             false => {
-        //                 let right_eval = self.evaluate(right)?;
-        //                 Ok(Value::Bool(self.is_truthy(&right_eval)))
-        //             },
-        //         }
-        //     },
-        //     TokenType::Or => {
-        //         let right_eval = self.evaluate(right)?;
-        //         Ok(Value::Bool(self.is_truthy(&left_eval) || self.is_truthy(&right_eval)))
-        //     },
+    // Reading a missing key of a map gives `nil`.
+    fn visit_index_expr(&mut self, object: &expr::Expr, bracket: &token::Token, index: &expr::Expr) -> Result<Value, Error> {
+        let object_eval = self.evaluate(object)?;
+        let index_eval = self.evaluate(index)?;
+        match object_eval {
+            Value::Array(array, _) => {
+                let array = array.borrow();
+                let i = self.array_index(&index_eval, array.len(), bracket)?;
+                Ok(array[i].clone())
+            },
+            Value::Map(map, _) => {
+                let key = self.map_key(&index_eval, bracket)?;
+                Ok(map.borrow().get(&key).cloned().unwrap_or(Value::Nil))
+            },
+            _ => Err(self.error(bracket, "Only arrays and maps can be indexed.")),
+        }
+    }
 
-        //     _ => unreachable!(),
-        // }
+    fn visit_set_index_expr(&mut self, object: &expr::Expr, bracket: &token::Token, index: &expr::Expr, value: &expr::Expr) -> Result<Value, Error> {
+        let object_eval = self.evaluate(object)?;
+        let index_eval = self.evaluate(index)?;
+        let value_eval = self.evaluate(value)?;
+        match object_eval {
+            Value::Array(_, true) | Value::Map(_, true) => Err(self.error(bracket, "Cannot modify a frozen value.")),
+            Value::Array(array, _) => {
+                let mut array = array.borrow_mut();
+                let i = self.array_index(&index_eval, array.len(), bracket)?;
+                array[i] = value_eval.clone();
+                Ok(value_eval)
+            },
+            Value::Map(map, _) => {
+                let key = self.map_key(&index_eval, bracket)?;
+                map.borrow_mut().insert(key, value_eval.clone());
+                Ok(value_eval)
+            },
+            _ => Err(self.error(bracket, "Only arrays and maps can be indexed.")),
+        }
+    }
+
+    fn visit_get_expr(&mut self, object: &expr::Expr, name: &token::Token) -> Result<Value, Error> {
+        match self.evaluate(object)? {
+            Value::Instance(instance) => instance.get(name)
+                .ok_or_else(|| self.error(name, &format!("Undefined property '{}'.", name.lexeme))),
+            _ => Err(self.error(name, "Only instances have properties.")),
+        }
+    }
+
+    fn visit_set_expr(&mut self, object: &expr::Expr, name: &token::Token, value: &expr::Expr) -> Result<Value, Error> {
+        let Value::Instance(instance) = self.evaluate(object)? else {
+            return Err(self.error(name, "Only instances have fields."));
+        };
+        let value_eval = self.evaluate(value)?;
+        instance.set(name, value_eval.clone());
+        Ok(value_eval)
+    }
+
+    // The parser only puts a spread in an argument list or array literal, which expand it
+    // themselves (see `evaluate_elements()`).
+    fn visit_spread_expr(&mut self, _ellipsis: &token::Token, _expression: &expr::Expr) -> Result<Value, Error> {
+        unreachable!()
+    }
+
+    // `super` lives in the scope just outside the one `Function::bind` defines `this` in, so `this`
+    // is one scope nearer than `super`.
+    fn visit_super_expr(&mut self, id: usize, keyword: &token::Token, method: &token::Token) -> Result<Value, Error> {
+        let distance = *self.locals.get(&id).expect("the resolver resolves every `super`");
+        let superclass = self.environment.borrow().get_at(distance, keyword)?;
+        let this = token::Token::new(TokenType::This, "this", token::Literal::Nil, keyword.line, keyword.column);
+        let object = self.environment.borrow().get_at(distance - 1, &this)?;
+
+        match (superclass, object) {
+            (Some(Value::Class(superclass)), Some(Value::Instance(instance))) => {
+                let method_function = superclass.find_method(&method.lexeme)
+                    .ok_or_else(|| self.error(method, &format!("Undefined property '{}'.", method.lexeme)))?;
+                Ok(Value::Function(Rc::new(method_function.bind(&instance))))
+            },
+            _ => unreachable!("`super` and `this` are defined when a subclass method is bound"),
+        }
+    }
+
+    // `this` is an ordinary variable, defined in the scope of a bound method.
+    fn visit_this_expr(&mut self, id: usize, keyword: &token::Token) -> Result<Value, Error> {
+        self.look_up_variable(id, keyword)
     }
 
     fn visit_grouping_expr(&mut self, expression: &expr::Expr) -> Result<Value, Error> {
         self.evaluate(expression)
     }
 
+    // Like a function declaration, a lambda closes over the scope it is evaluated in.
+    fn visit_lambda_expr(&mut self, params: &Rc<Vec<stmt::Param>>, body: &Rc<Vec<stmt::Stmt>>) -> Result<Value, Error> {
+        let function = Function {
+            name: None,
+            params: Rc::clone(params),
+            body: Rc::clone(body),
+            closure: Closure::Strong(Rc::clone(&self.environment)),
+            is_initializer: false,
+            is_generator: stmt::yields(body),
+        };
+        Ok(Value::Function(Rc::new(function)))
+    }
+
+    // Only the branch that is taken gets evaluated.
+    fn visit_ternary_expr(&mut self, condition: &expr::Expr, question: &token::Token, then_expr: &expr::Expr, else_expr: &expr::Expr) -> Result<Value, Error> {
+        let condition_eval = self.evaluate(condition)?;
+        if self.is_condition_true(&condition_eval, question)? {
+            self.evaluate(then_expr)
+        } else {
+            self.evaluate(else_expr)
+        }
+    }
+
     fn visit_unary_expr(&mut self, operator: &token::Token, right: &expr::Expr) -> Result<Value, Error> {
         let right_eval: Value = self.evaluate(right)?;
 
@@ -85,15 +331,18 @@ impl<'a> ExprVisitor<Value, Error> for Interpreter<'a> {
                 Ok(Value::Bool(!self.is_truthy(&right_eval)))
             },
             TokenType::Minus => {
-                if let Value::Number(x) = right_eval {
-                    Ok(Value::Number(-x))
-                } else {
-                    Err(self.operand_not_number_error(operator))
+                match right_eval {
+                    Value::Integer(x) => self.integer_result(x.checked_neg(), operator),
+                    Value::Number(x) => Ok(Value::Number(-x)),
+                    _ => Err(self.operand_not_number_error(operator)),
                 }
             },
+            TokenType::Tilde => {
+                Ok(Value::Integer(!self.integer_operand(&right_eval, operator)?))
+            },
 
             // Note no other operator type is reachable, since the parser builds unary expressions
-            // if and only if the operator is either `Bang` or `Minus`.
+            // if and only if the operator is `Bang`, `Minus` or `Tilde`.
             _ => unreachable!(),
         }
     }
@@ -102,77 +351,138 @@ impl<'a> ExprVisitor<Value, Error> for Interpreter<'a> {
         let left_eval: Value = self.evaluate(left)?;
         let right_eval: Value = self.evaluate(right)?;
 
+        // An instance operand can have a method for the operator (see `overloaded_operator()`).
+        if let Some(result) = self.overloaded_operator(operator, &left_eval, &right_eval)? {
+            return Ok(result);
+        }
+
         match operator.type_ {
+            // A comparison with NaN has no ordering, so it is always false.
             TokenType::Greater => {
-                if let (Value::Number(x), Value::Number(y)) = (left_eval, right_eval) {
-                    Ok(Value::Bool(x > y))
-                } else {
-                    Err(self.operand_not_number_error(operator))
-                }
+                let ordering = self.compare(&left_eval, &right_eval, operator)?;
+                Ok(Value::Bool(ordering == Some(Ordering::Greater)))
             },
             TokenType::GreaterEqual => {
-                if let (Value::Number(x), Value::Number(y)) = (left_eval, right_eval) {
-                    Ok(Value::Bool(x >= y))
-                } else {
-                    Err(self.operand_not_number_error(operator))
-                }
+                let ordering = self.compare(&left_eval, &right_eval, operator)?;
+                Ok(Value::Bool(matches!(ordering, Some(Ordering::Greater | Ordering::Equal))))
             },
             TokenType::Less => {
-                if let (Value::Number(x), Value::Number(y)) = (left_eval, right_eval) {
-                    Ok(Value::Bool(x < y))
-                } else {
-                    Err(self.operand_not_number_error(operator))
-                }
+                let ordering = self.compare(&left_eval, &right_eval, operator)?;
+                Ok(Value::Bool(ordering == Some(Ordering::Less)))
             },
             TokenType::LessEqual => {
-                if let (Value::Number(x), Value::Number(y)) = (left_eval, right_eval) {
-                    Ok(Value::Bool(x <= y))
-                } else {
-                    Err(self.operand_not_number_error(operator))
-                }
+                let ordering = self.compare(&left_eval, &right_eval, operator)?;
+                Ok(Value::Bool(matches!(ordering, Some(Ordering::Less | Ordering::Equal))))
+            },
+            // `object is Class` is whether `object` is an instance of `Class` or of a subclass of
+            // it. Anything other than an instance isn't an instance of any class.
+            TokenType::Is => {
+                let Value::Class(class) = &right_eval else {
+                    return Err(self.error(operator, "Right operand of 'is' must be a class."));
+                };
+                Ok(Value::Bool(matches!(&left_eval, Value::Instance(instance) if instance.class.is_subclass_of(class))))
             },
             TokenType::Minus => {
-                if let (Value::Number(x), Value::Number(y)) = (left_eval, right_eval) {
-                    Ok(Value::Number(x - y))
-                } else {
-                    Err(self.operand_not_number_error(operator))
+                match self.number_operands(&left_eval, &right_eval, operator)? {
+                    Operands::Integers(x, y) => self.integer_result(x.checked_sub(y), operator),
+                    Operands::Floats(x, y) => self.float_result(x - y, operator),
                 }
             },
+            // Division always gives a float, so `7 / 2` is `3.5` rather than a truncated `3`, unless
+            // `int_div` is set.
+            // Dividing by zero is an error, unless `ieee_floats` is set, in which case it gives an
+            // infinity, or NaN for `0 / 0`, as IEEE 754 does. As with `%`, an integer division by
+            // zero is always an error.
             TokenType::Slash => {
-                if let (Value::Number(x), Value::Number(y)) = (left_eval, right_eval) {
-                    if y == 0.0 {
-                        Err(self.error(operator, "Divide by zero."))
-                    } else {
-                        Ok(Value::Number(x / y))
-                    }
+                let (x, y) = match self.number_operands(&left_eval, &right_eval, operator)? {
+                    Operands::Integers(_, 0) if self.int_div => return Err(self.error(operator, "Divide by zero.")),
+                    Operands::Integers(x, y) if self.int_div => return self.integer_result(x.checked_div(y), operator),
+                    Operands::Integers(x, y) => (x as f64, y as f64),
+                    Operands::Floats(x, y) => (x, y),
+                };
+                if y == 0.0 && !self.ieee_floats {
+                    Err(self.error(operator, "Divide by zero."))
                 } else {
-                    Err(self.operand_not_number_error(operator))
+                    self.float_result(x / y, operator)
+                }
+            },
+            // An integer raised to a non-negative integer power stays an integer.
+            TokenType::StarStar => {
+                match self.number_operands(&left_eval, &right_eval, operator)? {
+                    Operands::Integers(x, y) if y >= 0 => {
+                        let result = u32::try_from(y).ok().and_then(|y| x.checked_pow(y));
+                        self.integer_result(result, operator)
+                    },
+                    Operands::Integers(x, y) => self.float_result((x as f64).powf(y as f64), operator),
+                    Operands::Floats(x, y) => self.float_result(x.powf(y), operator),
+                }
+            },
+            // Like Rust's `%`, the result takes the sign of the left operand.
+            // Like division, a float modulo by zero gives NaN under `ieee_floats`. An integer one
+            // is always an error, as there is no integer NaN.
+            TokenType::Percent => {
+                match self.number_operands(&left_eval, &right_eval, operator)? {
+                    Operands::Integers(_, 0) => Err(self.error(operator, "Modulo by zero.")),
+                    Operands::Integers(x, y) => self.integer_result(x.checked_rem(y), operator),
+                    Operands::Floats(_, 0.0) if !self.ieee_floats => Err(self.error(operator, "Modulo by zero.")),
+                    Operands::Floats(x, y) => self.float_result(x % y, operator),
                 }
             },
             TokenType::Star => {
-                if let (Value::Number(x), Value::Number(y)) = (left_eval, right_eval) {
-                    Ok(Value::Number(x * y))
-                } else {
-                    Err(self.operand_not_number_error(operator))
+                match self.number_operands(&left_eval, &right_eval, operator)? {
+                    Operands::Integers(x, y) => self.integer_result(x.checked_mul(y), operator),
+                    Operands::Floats(x, y) => self.float_result(x * y, operator),
                 }
             },
             TokenType::Plus => {
-                if let (&Value::Number(x), &Value::Number(y)) = (&left_eval, &right_eval) {
-                    Ok(Value::Number(x + y))
+                if let Ok(operands) = self.number_operands(&left_eval, &right_eval, operator) {
+                    match operands {
+                        Operands::Integers(x, y) => self.integer_result(x.checked_add(y), operator),
+                        Operands::Floats(x, y) => self.float_result(x + y, operator),
+                    }
                 } else {
                     // If the values aren't *both* numbers, return the concatenated string
                     // representations of the values.
-                    let (x, y) = (left_eval, right_eval);
+                    let x = self.stringify(&left_eval, operator)?;
+                    let y = self.stringify(&right_eval, operator)?;
+                    self.check_size(x.chars().count() + y.chars().count())
+                        .map_err(|message| self.error(operator, &message))?;
                     Ok(Value::String_(format!("{}{}", x, y)))
                 }
             },
 
-            // My implementation of != and == simply piggybacks Rust's `PartialEq` trait.
+            // The bitwise operators work on integers. Whole floats are accepted and converted.
+            TokenType::Ampersand => {
+                let (x, y) = self.integer_operands(&left_eval, &right_eval, operator)?;
+                Ok(Value::Integer(x & y))
+            },
+            TokenType::Caret => {
+                let (x, y) = self.integer_operands(&left_eval, &right_eval, operator)?;
+                Ok(Value::Integer(x ^ y))
+            },
+            TokenType::Pipe => {
+                let (x, y) = self.integer_operands(&left_eval, &right_eval, operator)?;
+                Ok(Value::Integer(x | y))
+            },
+            // `>>` is an arithmetic shift, so it keeps the sign of the left operand.
+            TokenType::LessLess | TokenType::GreaterGreater => {
+                let (x, y) = self.integer_operands(&left_eval, &right_eval, operator)?;
+                let shift = match u32::try_from(y) {
+                    Ok(y) if y < i64::BITS => y,
+                    _ => return Err(self.error(operator, "Shift amount must be between 0 and 63.")),
+                };
+                if operator.type_ == TokenType::LessLess {
+                    Ok(Value::Integer(x << shift))
+                } else {
+                    Ok(Value::Integer(x >> shift))
+                }
+            },
+
             TokenType::BangEqual => {
-                Ok(Value::Bool(left_eval != right_eval))
+                Ok(Value::Bool(!self.is_equal(&left_eval, &right_eval)))
             },
             TokenType::EqualEqual => {
-                Ok(Value::Bool(left_eval == right_eval))
+                Ok(Value::Bool(self.is_equal(&left_eval, &right_eval)))
             },
 
             // Note no other operator type is reachable, since the parser builds binary expressions
@@ -181,27 +491,104 @@ impl<'a> ExprVisitor<Value, Error> for Interpreter<'a> {
         }
     }
 
-    // We do not allow uninitialized variables.
-    fn visit_variable_expr(&mut self, name: &token::Token) -> Result<Value, Error> {
-        self.environment.get(name)?
-            .ok_or_else(|| self.error(name, "Variable not initialized."))
+    fn visit_variable_expr(&mut self, id: usize, name: &token::Token) -> Result<Value, Error> {
+        self.look_up_variable(id, name)
     }
 
-    fn visit_assign_expr(&mut self, name: &token::Token, value: &expr::Expr) -> Result<Value, Error> {
+    fn visit_assign_expr(&mut self, id: usize, name: &token::Token, value: &expr::Expr) -> Result<Value, Error> {
         let value_eval = self.evaluate(value)?;
-        self.environment.assign(name, &value_eval)?;
+        self.assign_variable(id, name, &value_eval)?;
         Ok(value_eval)
     }
+
+    // Unlike `x = x + 1`, this only works on numbers: `++` on a string is an error rather than
+    // appending "1".
+    fn visit_increment_expr(&mut self, id: usize, name: &token::Token, operator: &token::Token, prefix: bool) -> Result<Value, Error> {
+        let old = self.look_up_variable(id, name)?;
+        let step = if operator.type_ == TokenType::PlusPlus { 1 } else { -1 };
+        let new = match old {
+            Value::Integer(x) => self.integer_result(x.checked_add(step), operator)?,
+            Value::Number(x) => Value::Number(x + step as f64),
+            _ => return Err(self.operand_not_number_error(operator)),
+        };
+        self.assign_variable(id, name, &new)?;
+        Ok(if prefix { new } else { old })
+    }
 }
 
 // Statement execution.
-impl<'a> StmtVisitor<(), Error> for Interpreter<'a> {
-    // To enclose the new environment in the current environment, we clone the current environment
-    // and put it in the new one. This isn't ideal, but avoids dealing with lifetimes which I don't
-    // want to do.
+impl StmtVisitor<(), Error> for Interpreter {
+    // The new environment holds a reference to the current one as its enclosing scope.
     fn visit_block_stmt(&mut self, statements: &[stmt::Stmt]) -> Result<(), Error> {
-        let mut new_env = Environment::new(Some(self.environment.clone()));
-        self.execute_block(statements, &mut new_env)
+        let new_env = Environment::new(Some(Rc::clone(&self.environment)));
+        self.execute_block(statements, Rc::new(RefCell::new(new_env)))
+    }
+
+    fn visit_break_stmt(&mut self, _keyword: &token::Token) -> Result<(), Error> {
+        Err(Error::Break)
+    }
+
+    // The methods close over the scope the class is declared in, like functions do. For a subclass,
+    // that scope is wrapped in one defining `super` as the superclass.
+    fn visit_class_stmt(&mut self, name: &token::Token, superclass: Option<&expr::Expr>, methods: &[stmt::Stmt]) -> Result<(), Error> {
+        let superclass = match superclass {
+            Some(expr) => match self.evaluate(expr)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    let expr::Expr::Variable { name, .. } = expr else { unreachable!() };
+                    return Err(self.error(name, "Superclass must be a class."));
+                },
+            },
+            None => None,
+        };
+
+        let mut closure = Rc::clone(&self.environment);
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::new(Some(closure));
+            environment.define("super".to_owned(), Some(&Value::Class(Rc::clone(superclass))));
+            closure = Rc::new(RefCell::new(environment));
+        }
+
+        let mut class_methods = HashMap::new();
+        for method in methods {
+            let stmt::Stmt::Function { name: method_name, params, body } = method else {
+                unreachable!("the parser only puts function declarations in a class body");
+            };
+            let function = Function {
+                name: Some(method_name.to_owned()),
+                params: Rc::clone(params),
+                body: Rc::clone(body),
+                closure: Closure::Strong(Rc::clone(&closure)),
+                is_initializer: method_name.lexeme == "init",
+                is_generator: stmt::yields(body),
+            };
+            class_methods.insert(method_name.lexeme.to_owned(), Rc::new(function));
+        }
+
+        let class = Class { name: name.to_owned(), superclass, methods: class_methods };
+        self.environment.borrow_mut().define(name.lexeme.to_owned(), Some(&Value::Class(Rc::new(class))));
+        Ok(())
+    }
+
+    fn visit_continue_stmt(&mut self, _keyword: &token::Token) -> Result<(), Error> {
+        Err(Error::Continue)
+    }
+
+    fn visit_debug_assert_stmt(&mut self, keyword: &token::Token, condition: &expr::Expr) -> Result<(), Error> {
+        if self.release {
+            return Ok(());
+        }
+
+        let condition_eval = self.evaluate(condition)?;
+        if self.is_truthy(&condition_eval) {
+            Ok(())
+        } else {
+            Err(self.error(keyword, "Assertion failed."))
+        }
+    }
+
+    fn visit_empty_stmt(&mut self) -> Result<(), Error> {
+        Ok(())
     }
 
     fn visit_expression_stmt(&mut self, expression: &expr::Expr) -> Result<(), Error> {
@@ -209,9 +596,23 @@ impl<'a> StmtVisitor<(), Error> for Interpreter<'a> {
         Ok(())
     }
 
-    fn visit_if_stmt(&mut self, condition: &expr::Expr, then_branch: &stmt::Stmt, else_branch: Option<&stmt::Stmt>) -> Result<(), Error> {
+    // Executing a declaration just binds a new function value to its name.
+    fn visit_function_stmt(&mut self, name: &token::Token, params: &Rc<Vec<stmt::Param>>, body: &Rc<Vec<stmt::Stmt>>) -> Result<(), Error> {
+        let function = Function {
+            name: Some(name.to_owned()),
+            params: Rc::clone(params),
+            body: Rc::clone(body),
+            closure: Closure::Strong(Rc::clone(&self.environment)),
+            is_initializer: false,
+            is_generator: stmt::yields(body),
+        };
+        self.environment.borrow_mut().define(name.lexeme.to_owned(), Some(&Value::Function(Rc::new(function))));
+        Ok(())
+    }
+
+    fn visit_if_stmt(&mut self, keyword: &token::Token, condition: &expr::Expr, then_branch: &stmt::Stmt, else_branch: Option<&stmt::Stmt>) -> Result<(), Error> {
         let condition_eval = self.evaluate(condition)?;
-        if self.is_truthy(&condition_eval) {
+        if self.is_condition_true(&condition_eval, keyword)? {
             self.execute(then_branch)?;
         } else if let Some(else_stmt) = else_branch {
             self.execute(else_stmt)?;
@@ -219,76 +620,743 @@ impl<'a> StmtVisitor<(), Error> for Interpreter<'a> {
         Ok(())
     }
 
-    fn visit_print_stmt(&mut self, expression: &expr::Expr) -> Result<(), Error> {
-        let value = self.evaluate(expression)?;
-        println!("{}", value);
+    // Run the script at `path` at the top level, so that its declarations become globals.
+    // A script is only imported once, and importing one whose import is still running (including
+    // the script itself) is an error, rather than an endless loop.
+    // With an alias, the script is instead run in a scope of its own, each time, and what it
+    // defines becomes the fields of a namespace bound to the alias: `import "math.lox" as m;` gives
+    // `m.square()`, but no `square`. Its top level is resolved like a block's, so (unless `hoist`
+    // is on) a function in it can only use the functions declared before it.
+    // Errors in the imported script are reported at the `import`.
+    fn visit_import_stmt(&mut self, keyword: &token::Token, path: &str, alias: Option<&token::Token>) -> Result<(), Error> {
+        let directory = self.current_file.as_deref().and_then(Path::parent).unwrap_or(Path::new(""));
+        let file = directory.join(path);
+        let canonical = fs::canonicalize(&file)
+            .map_err(|e| self.error(keyword, &format!("Can't import '{}': {}.", path, e)))?;
+        let importer = self.current_file.as_deref().and_then(|file| fs::canonicalize(file).ok());
+
+        if importer.as_ref() == Some(&canonical) || self.importing.contains(&canonical) {
+            return Err(self.error(keyword, &format!("Import cycle: '{}' is already being imported.", path)));
+        }
+        if alias.is_none() && self.imported.contains(&canonical) {
+            return Ok(());
+        }
+
+        let source = fs::read_to_string(&file)
+            .map_err(|e| self.error(keyword, &format!("Can't import '{}': {}.", path, e)))?;
+        let statements = crate::parse_with_keywords(&source, self.keywords.clone())
+            .map_err(|errors| self.import_error(keyword, path, &errors[0]))?;
+        let scopes = match alias {
+            Some(_) => vec![Vec::new()],
+            None => Vec::new(),
+        };
+        Resolver::with_scopes(self, scopes).resolve(&statements)
+            .map_err(|errors| self.import_error(keyword, path, &errors[0]))?;
+
+        // Imports in the imported script are relative to its own directory.
+        let current_file = self.current_file.replace(file);
+        let pushed = importer.is_some();
+        self.importing.extend(importer);
+
+        let result = match alias {
+            Some(alias) => {
+                let scope = Rc::new(RefCell::new(Environment::new(Some(Rc::clone(&self.globals)))));
+                self.execute_block(&statements, Rc::clone(&scope))
+                    .map(|()| Some((alias, namespace(alias, &scope.borrow()))))
+            },
+            None => {
+                let environment = mem::replace(&mut self.environment, Rc::clone(&self.globals));
+                let result = self.interpret(&statements);
+                self.environment = environment;
+                result.map(|_| None)
+            },
+        };
+
+        if pushed {
+            self.importing.pop();
+        }
+        self.current_file = current_file;
+
+        match result.map_err(|error| self.import_error(keyword, path, &error))? {
+            Some((alias, namespace)) => self.environment.borrow_mut().define(alias.lexeme.clone(), Some(&namespace)),
+            None => {
+                self.imported.insert(canonical);
+            },
+        }
         Ok(())
     }
 
+    // Unlike `println!`, which panics if stdout can't be written to, a failed write is reported as a
+    // `RuntimeError` at the `print`.
+    fn visit_print_stmt(&mut self, keyword: &token::Token, expression: &expr::Expr) -> Result<(), Error> {
+        let value = self.evaluate(expression)?;
+        let mut text = self.stringify(&value, keyword)?;
+        if self.ascii_out {
+            text = text.chars()
+                .map(|c| if c.is_ascii() { c.to_string() } else { c.escape_unicode().to_string() })
+                .collect();
+        }
+        writeln!(self.output, "{}", text)
+            .map_err(|e| self.error(keyword, &format!("Failed to write output: {}.", e)))
+    }
+
+    // Unwind to the enclosing call, which catches `Error::Return` and makes `value` its result.
+    // If `value` is itself a call of a user-defined function, only its callee and arguments are
+    // evaluated here, and the enclosing call makes it (see `call_function()`). Other calls are
+    // made here, since natives like `eval()` run in the scope they are called from.
+    fn visit_return_stmt(&mut self, _keyword: &token::Token, value: Option<&expr::Expr>) -> Result<(), Error> {
+        if let Some(expr::Expr::Call { callee, paren, arguments, .. }) = value.filter(|value| !has_keywords(value)) {
+            let callee = self.evaluate(callee)?;
+            let arguments = self.evaluate_elements(arguments)?;
+            return match callee {
+                Value::Function(callee) => Err(Error::TailCall(Box::new(TailCall { callee, paren: paren.to_owned(), arguments }))),
+                _ => {
+                    if let Value::NativeFn(_) = callee {
+                        self.native_call_line = paren.line;
+                    }
+                    Err(Error::Return(self.call_value(&callee, arguments).map_err(|error| self.call_error(paren, error))?))
+                },
+            };
+        }
+        let value_eval = match value {
+            Some(x) => self.evaluate(x)?,
+            None => Value::Nil,
+        };
+        Err(Error::Return(value_eval))
+    }
+
     fn visit_var_stmt(&mut self, name: &token::Token, initializer: Option<&expr::Expr>) -> Result<(), Error> {
         if let Some(x) = initializer {
             let value = self.evaluate(x)?;
-            self.environment.define(name.lexeme.to_owned(), Some(&value));
+            self.environment.borrow_mut().define(name.lexeme.to_owned(), Some(&value));
         } else {
-            self.environment.define(name.lexeme.to_owned(), None);
+            self.environment.borrow_mut().define(name.lexeme.to_owned(), None);
         }
         Ok(())
     }
 
-    fn visit_while_stmt(&mut self, condition: &expr::Expr, body: &stmt::Stmt) -> Result<(), Error> {
+    // Case values are evaluated in order, only until one matches, and compared like `==`. The
+    // matching case runs in its own scope. A `break` in it leaves the switch (but not a loop
+    // around it).
+    fn visit_switch_stmt(&mut self, _keyword: &token::Token, discriminant: &expr::Expr, cases: &[(expr::Expr, Vec<stmt::Stmt>)], default: Option<&[stmt::Stmt]>) -> Result<(), Error> {
+        let discriminant_eval = self.evaluate(discriminant)?;
+
+        let mut body = default;
+        for (value, statements) in cases {
+            let value_eval = self.evaluate(value)?;
+            if self.is_equal(&discriminant_eval, &value_eval) {
+                body = Some(statements);
+                break;
+            }
+        }
+
+        let Some(body) = body else {
+            return Ok(());
+        };
+        let new_env = Environment::new(Some(Rc::clone(&self.environment)));
+        match self.execute_block(body, Rc::new(RefCell::new(new_env))) {
+            Ok(()) | Err(Error::Break) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // The parser only allows `yield` in a function, which makes it a generator, so there is always
+    // a call collecting the values.
+    fn visit_yield_stmt(&mut self, keyword: &token::Token, value: &expr::Expr) -> Result<(), Error> {
+        let value = self.evaluate(value)?;
+        let count = self.yielded.as_ref().map_or(0, Vec::len);
+        self.check_size(count + 1).map_err(|message| self.error(keyword, &message))?;
+        if let Some(yielded) = &mut self.yielded {
+            yielded.push(value);
+        }
+        Ok(())
+    }
+
+    // `break` and `continue` arrive here as `Error::Break`/`Error::Continue` from the body.
+    fn visit_while_stmt(&mut self, keyword: &token::Token, condition: &expr::Expr, body: &stmt::Stmt, increment: Option<&expr::Expr>) -> Result<(), Error> {
         let mut condition_eval = self.evaluate(condition)?;
-        while self.is_truthy(&condition_eval) {
-            self.execute(body)?;
+        while self.is_condition_true(&condition_eval, keyword)? {
+            match self.execute(body) {
+                Ok(()) | Err(Error::Continue) => (),
+                Err(Error::Break) => break,
+                Err(e) => return Err(e),
+            }
+            if let Some(x) = increment {
+                self.evaluate(x)?;
+            }
             condition_eval = self.evaluate(condition)?;
         };
         Ok(())
     }
 }
 
-impl<'a> Interpreter<'a> {
-    pub fn new(environment: &'a mut Environment) -> Self {
+impl Interpreter {
+    // The built-in functions are defined in `globals`.
+    pub fn new(globals: Rc<RefCell<Environment>>) -> Self {
+        Self::with_output(globals, Box::new(io::stdout()))
+    }
+
+    // Like `new()`, but `print` writes to `output` instead of stdout, e.g. a `SharedOutput` to
+    // capture it.
+    pub fn with_output(globals: Rc<RefCell<Environment>>, output: Box<dyn Write>) -> Self {
+        natives::define_natives(&mut globals.borrow_mut());
         Self {
-            environment,
+            environment: Rc::clone(&globals),
+            globals,
+            locals: HashMap::new(),
+            release: false,
+            ascii_out: false,
+            strict_bool: false,
+            ieee_floats: false,
+            int_div: false,
+            decimal_comma: false,
+            group_thousands: false,
+            keywords: None,
+            hoist: false,
+            sandbox: false,
+            max_size: None,
+            warn_shadow: false,
+            test_mode: false,
+            on_statement: None,
+            test_results: TestResults::default(),
+            native_call_line: 0,
+            yielded: None,
+            warnings: Vec::new(),
+            output,
+            input: Box::new(io::BufReader::new(io::stdin())),
+            current_file: None,
+            importing: Vec::new(),
+            imported: HashSet::new(),
+            scope_pool: Vec::new(),
         }
     }
 
-    // Interface. If something went wrong, return a `RuntimeError` object.
-    pub fn interpret(&mut self, statements: &Vec<stmt::Stmt>) -> Result<(), Error> {
-        for statement in statements {
-            if let Err(Error::RuntimeError { token, message }) = self.execute(statement) {
-                // If something went wrong in statement execution, call `crate::error_token` here.
-                // Also, return `Err` in case the calling function wants to deal with it.
-                crate::error_token(&token, &message);
-                return Err(Error::RuntimeError { token, message });
+    // Interface. Returns the values of the top-level expression statements, in order. If something
+    // went wrong, stop and return the `RuntimeError` object.
+    pub fn interpret(&mut self, statements: &[stmt::Stmt]) -> Result<Vec<Value>, Error> {
+        let mut values = Vec::new();
+        for statement in self.run_order(statements) {
+            if let Some(value) = self.execute_top_level(statement)? {
+                values.push(value);
             }
         }
-        Ok(())
+        Ok(values)
+    }
+
+    // Like `interpret()`, but a runtime error only abandons the top-level statement it happened
+    // in: it is collected, and execution carries on with the next statement. Anything
+    // the failed statement did before the error (output, assignments) stays done, and a `var`
+    // whose initializer failed is left undefined.
+    // The values of top-level expression statements are returned too, in order.
+    // Runtime errors are the only recoverable ones. `Return`, `Break` and `Continue` can't reach
+    // the top level (they are parse errors outside a function or loop), so if one does anyway it
+    // is collected as fatal and nothing after it is run.
+    pub fn interpret_collecting(&mut self, statements: &[stmt::Stmt]) -> (Vec<Value>, Vec<Error>) {
+        let mut values = Vec::new();
+        let mut errors = Vec::new();
+        for statement in self.run_order(statements) {
+            match self.execute_top_level(statement) {
+                Ok(value) => values.extend(value),
+                Err(error @ Error::RuntimeError { .. }) => errors.push(error),
+                Err(error) => {
+                    errors.push(error);
+                    break;
+                },
+            }
+        }
+        (values, errors)
+    }
+
+    // Interface. The warnings found since the last call, in the order they were found.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        mem::take(&mut self.warnings)
+    }
+
+    // Interface. The `assert`/`expect` checks run so far in test mode.
+    pub fn test_results(&self) -> &TestResults {
+        &self.test_results
+    }
+
+    // Called by `assert` and `expect` with the outcome of a check, and the message to fail with
+    // if it didn't pass. In test mode the outcome is counted, and returned as a boolean.
+    // Otherwise a failed check is an error, and one that passed gives `nil`.
+    pub(crate) fn check(&mut self, passed: bool, failure: impl FnOnce() -> String) -> Result<Value, CallError> {
+        match (self.test_mode, passed) {
+            (true, true) => self.test_results.passed += 1,
+            (true, false) => self.test_results.failures.push(format!("[line {}] {}", self.native_call_line, failure())),
+            (false, true) => return Ok(Value::Nil),
+            (false, false) => return Err(failure().into()),
+        }
+        Ok(Value::Bool(passed))
+    }
+
+    // Called by the resolver to record a warning at `token`.
+    pub fn warn(&mut self, token: &token::Token, message: &str) {
+        self.warnings.push(Warning::new(token, message));
+    }
+
+    // Called by the resolver: the variable in the expression `id` lives `depth` scopes up from the
+    // scope the expression is evaluated in.
+    pub fn resolve(&mut self, id: usize, depth: usize) {
+        self.locals.insert(id, depth);
+    }
+
+    // Runs a statement, giving its value if it is an expression statement.
+    fn execute_top_level(&mut self, statement: &stmt::Stmt) -> Result<Option<Value>, Error> {
+        match statement {
+            stmt::Stmt::Expression { expression } => {
+                self.before_statement(statement);
+                self.evaluate(expression).map(Some)
+            },
+            _ => self.execute(statement).map(|_| None),
+        }
+    }
+
+    // Run `source` in the current scope, for `eval()`. Its value is that of its last statement if
+    // that is an expression, or of `source` itself if it is a bare expression, like `1 + 2`, and
+    // otherwise `nil`. Any error in it is reported at the call to `eval()`, as for an `import`.
+    // The code is resolved against the scopes it runs in, so it can use their variables, and the
+    // variables it declares stay in the current scope. Code after the call was resolved before the
+    // call ran, though, so it only sees them at the top level, where they are globals.
+    pub(crate) fn eval(&mut self, source: &str) -> Result<Value, CallError> {
+        if self.sandbox {
+            return Err("Can't use 'eval' in sandbox mode.".into());
+        }
+        let in_eval = |error: &Error| CallError::from(format!("In eval: {}", error));
+        let tokens = Scanner::new(source.to_owned(), self.keywords.clone()).scan_tokens()
+            .map_err(|errors| in_eval(&errors[0]))?;
+        let statements = match Parser::new(tokens.clone()).parse_expression() {
+            Ok(expression) => vec![stmt::Stmt::Expression { expression }],
+            Err(_) => Parser::new(tokens).parse().map_err(|errors| in_eval(&errors[0]))?,
+        };
+
+        let mut scopes = self.environment.borrow().scope_names();
+        scopes.pop();  // the globals, which the resolver doesn't track
+        scopes.reverse();
+        Resolver::with_scopes(self, scopes).resolve(&statements).map_err(|errors| in_eval(&errors[0]))?;
+
+        let mut value = Value::Nil;
+        for statement in self.run_order(&statements) {
+            value = self.execute_top_level(statement).map_err(|error| in_eval(&error))?.unwrap_or(Value::Nil);
+        }
+        Ok(value)
+    }
+
+    // The order to run a block's (or script's) `statements` in: as written, except that under
+    // `hoist` the function declarations come first. The resolver puts their names in scope from the
+    // start of the block to match (see `Resolver::resolve_stmts()`).
+    fn run_order<'s>(&self, statements: &'s [stmt::Stmt]) -> Vec<&'s stmt::Stmt> {
+        let (mut functions, rest): (Vec<_>, Vec<_>) = statements.iter()
+            .partition(|statement| self.hoist && matches!(statement, stmt::Stmt::Function { .. }));
+        functions.extend(rest);
+        functions
     }
 
     // Runs `accept` for statements.
     fn execute(&mut self, statement: &stmt::Stmt) -> Result<(), Error> {
+        self.before_statement(statement);
         self.accept_stmt(statement)
     }
 
+    // Call `on_statement`, if it is set. It is taken out while it runs, so it can be given the
+    // interpreter.
+    fn before_statement(&mut self, statement: &stmt::Stmt) {
+        if let Some(mut on_statement) = self.on_statement.take() {
+            if let Some(line) = stmt::line(statement) {
+                on_statement(self, line);
+            }
+            self.on_statement = Some(on_statement);
+        }
+    }
+
+    // The variables visible where the interpreter is running, innermost first (see
+    // `Environment::variables()`).
+    pub fn variables(&self) -> Vec<(String, Value)> {
+        self.environment.borrow().variables()
+    }
+
     // Executes scoped code.
-    fn execute_block(&mut self, statements: &[stmt::Stmt], new_env: &mut Environment) -> Result<(), Error> {
-        // Swap the current environment and the new one.
-        mem::swap(self.environment, new_env);
-        for statement in statements {
-            self.execute(statement)?;
-        }
-        // Swap back.
-        mem::swap(self.environment, new_env);
-        self.environment.update(new_env);
+    fn execute_block(&mut self, statements: &[stmt::Stmt], new_env: Rc<RefCell<Environment>>) -> Result<(), Error> {
+        // Make the new environment the current one.
+        let previous = mem::replace(&mut self.environment, new_env);
+        let result = match self.hoist {
+            true => self.run_order(statements).into_iter().try_for_each(|statement| self.execute(statement)),
+            false => statements.iter().try_for_each(|statement| self.execute(statement)),
+        };
+        // Restore the previous one, even if a statement failed, so the caller is left in its own
+        // scope.
+        self.environment = previous;
+        result
+    }
+
+    // Call `callee` with `arguments`, for a call expression, or for a native function calling back
+    // into a function it was given, like `find` does with its predicate. There is no token to
+    // report an error at here, so problems with the call itself come back as a message, which the
+    // call expression reports at its `(` (and a native at its own call).
+    pub(crate) fn call_value(&mut self, callee: &Value, arguments: Vec<Value>) -> Result<Value, CallError> {
+        match callee {
+            Value::Function(function) => {
+                check_arity(&function.arity(), &arguments)?;
+                Ok(self.call_function(function, arguments.into_iter().map(Some).collect())?)
+            },
+            Value::NativeFn(native) => {
+                check_arity(&native.arity, &arguments)?;
+                match native.bound.is_empty() {
+                    true => (native.function)(self, &arguments),
+                    false => (native.function)(self, &[native.bound.clone(), arguments].concat()),
+                }
+            },
+            Value::Class(class) => {
+                check_arity(&class.arity(), &arguments)?;
+                Ok(self.call_class(class, arguments.into_iter().map(Some).collect())?)
+            },
+            _ => Err("Can only call functions and classes.".into()),
+        }
+    }
+
+    // Like `call_value()`, for a call with keyword arguments, which go to the parameters of the
+    // same name. Only functions and classes (through `init`) have named parameters.
+    fn call_with_keywords(&mut self, callee: &Value, arguments: Vec<Value>, keywords: Vec<(&token::Token, Value)>) -> Result<Value, CallError> {
+        match callee {
+            Value::Function(function) => {
+                let arguments = self.keyword_slots(&function.params, arguments, keywords)?;
+                Ok(self.call_function(function, arguments)?)
+            },
+            Value::Class(class) => {
+                let params = class.find_method("init").map(|init| Rc::clone(&init.params)).unwrap_or_default();
+                let arguments = self.keyword_slots(&params, arguments, keywords)?;
+                Ok(self.call_class(class, arguments)?)
+            },
+            Value::NativeFn(native) => Err(format!("'{}' doesn't take keyword arguments.", native.name).into()),
+            _ => Err("Can only call functions and classes.".into()),
+        }
+    }
+
+    // Line up the arguments of a call with keyword arguments against `params`: one slot per
+    // parameter, left empty for those that will get their default value. A keyword that isn't a
+    // parameter, or names one that already has an argument, is reported at the keyword.
+    // A rest parameter only takes positional arguments: those left over follow the other slots.
+    fn keyword_slots(&self, params: &[stmt::Param], arguments: Vec<Value>, keywords: Vec<(&token::Token, Value)>) -> Result<Vec<Option<Value>>, CallError> {
+        let rest = params.last().is_some_and(|param| param.rest);
+        let params = if rest { &params[..params.len() - 1] } else { params };
+        if arguments.len() > params.len() && !rest {
+            return Err(format!("Expected at most {} positional arguments but got {}.", params.len(), arguments.len()).into());
+        }
+        let mut slots: Vec<Option<Value>> = arguments.into_iter().map(Some).collect();
+        if slots.len() < params.len() {
+            slots.resize(params.len(), None);
+        }
+        for (name, value) in keywords {
+            let Some(index) = params.iter().position(|param| param.name.lexeme == name.lexeme) else {
+                return Err(self.error(name, &format!("Unknown keyword argument '{}'.", name.lexeme)).into());
+            };
+            if slots[index].is_some() {
+                return Err(self.error(name, &format!("Argument '{}' given more than once.", name.lexeme)).into());
+            }
+            slots[index] = Some(value);
+        }
+        match params.iter().zip(&slots).find(|(param, slot)| slot.is_none() && param.default.is_none()) {
+            Some((param, _)) => Err(format!("Missing argument '{}'.", param.name.lexeme).into()),
+            None => Ok(slots),
+        }
+    }
+
+    // A class can overload `+`, `==` and `<` by defining the methods `add(other)`, `eq(other)` and
+    // `less(other)`. The method of the left operand is called if it is an instance that has one,
+    // and otherwise that of the right operand, so `3 + v` works as well as `v + 3`. The right
+    // operand's `add` is called with the left one, so it had better be commutative. It isn't
+    // called when the left one is a string, though: that is concatenated, with `"v = " + v` using
+    // the `to_string()` of `v`.
+    // The rest are derived from these: `a != b` is `!(a == b)`, `a > b` is `b < a`, `a <= b` is
+    // `!(b < a)`, and `a >= b` is `!(a < b)` (see `overloaded_less()`).
+    // `None` if neither operand has the method, for the operator to work (or fail) as it would on
+    // any other value.
+    fn overloaded_operator(&mut self, operator: &token::Token, left: &Value, right: &Value) -> Result<Option<Value>, Error> {
+        let result = match operator.type_ {
+            TokenType::Plus => {
+                return match self.call_method(left, "add", vec![right.clone()], operator)? {
+                    Some(result) => Ok(Some(result)),
+                    None if matches!(left, Value::String_(_)) => Ok(None),
+                    None => self.call_method(right, "add", vec![left.clone()], operator),
+                };
+            },
+            TokenType::EqualEqual => self.overloaded_eq(left, right, operator)?,
+            TokenType::BangEqual => self.overloaded_eq(left, right, operator)?.map(|equal| !equal),
+            TokenType::Less => self.overloaded_less(left, right, operator)?,
+            TokenType::Greater => self.overloaded_less(right, left, operator)?,
+            TokenType::LessEqual => self.overloaded_less(right, left, operator)?.map(|less| !less),
+            TokenType::GreaterEqual => self.overloaded_less(left, right, operator)?.map(|less| !less),
+            _ => None,
+        };
+        Ok(result.map(Value::Bool))
+    }
+
+    // `x == y` by the `eq` method of `x`, or else of `y`, if either has one.
+    fn overloaded_eq(&mut self, x: &Value, y: &Value, token: &token::Token) -> Result<Option<bool>, Error> {
+        let result = match self.call_method(x, "eq", vec![y.clone()], token)? {
+            Some(result) => Some(result),
+            None => self.call_method(y, "eq", vec![x.clone()], token)?,
+        };
+        Ok(result.map(|result| self.is_truthy(&result)))
+    }
+
+    // `x < y` by the `less` method of `x`, or else of `y`, if either has one. With only `y.less()`
+    // to go by, `x < y` holds if `y` is neither less than nor equal to `x`, so `v > 3` works for an
+    // instance `v` that can be compared with numbers. Equality is by `eq` if either has it.
+    fn overloaded_less(&mut self, x: &Value, y: &Value, token: &token::Token) -> Result<Option<bool>, Error> {
+        if let Some(result) = self.call_method(x, "less", vec![y.clone()], token)? {
+            return Ok(Some(self.is_truthy(&result)));
+        }
+        let Some(y_less) = self.call_method(y, "less", vec![x.clone()], token)? else {
+            return Ok(None);
+        };
+        if self.is_truthy(&y_less) {
+            return Ok(Some(false));
+        }
+        let equal = match self.overloaded_eq(y, x, token)? {
+            Some(equal) => equal,
+            None => self.is_equal(y, x),
+        };
+        Ok(Some(!equal))
+    }
+
+    // The error for a call that failed with `error`, reported at `token` if it is a message about
+    // the call itself.
+    fn call_error(&self, token: &token::Token, error: CallError) -> Error {
+        match error {
+            CallError::Message(message) => self.error(token, &message),
+            CallError::Error(error) => error,
+        }
+    }
+
+    // Call the method `name` of `value` with `arguments`, if `value` is an instance whose class has
+    // it, for operators and `print`, which a class can overload. `token` is where a failed call is
+    // reported.
+    fn call_method(&mut self, value: &Value, name: &str, arguments: Vec<Value>, token: &token::Token) -> Result<Option<Value>, Error> {
+        let Value::Instance(instance) = value else {
+            return Ok(None);
+        };
+        let Some(method) = instance.class.find_method(name) else {
+            return Ok(None);
+        };
+        let method = Value::Function(Rc::new(method.bind(instance)));
+        self.call_value(&method, arguments).map(Some).map_err(|error| self.call_error(token, error))
+    }
+
+    // How `value` is shown by `print` and by `+` when it concatenates: as it displays, except for
+    // an instance of a class with a `to_string()` method, which is shown as what that returns, and
+    // numbers, which are formatted as `decimal_comma` and `group_thousands` say.
+    // Elements of arrays and maps always show as they display.
+    fn stringify(&mut self, value: &Value, token: &token::Token) -> Result<String, Error> {
+        self.display(value).map_err(|error| self.call_error(token, error))
+    }
+
+    // `stringify()`, for natives, which have no token to report a failed `to_string()` at.
+    pub(crate) fn display(&mut self, value: &Value) -> Result<String, CallError> {
+        match value {
+            Value::Instance(instance) => match instance.class.find_method("to_string") {
+                Some(method) => {
+                    let method = Value::Function(Rc::new(method.bind(instance)));
+                    Ok(self.call_value(&method, Vec::new())?.to_string())
+                },
+                None => Ok(value.to_string()),
+            },
+            Value::Integer(_) | Value::Number(_) => Ok(self.format_number(value.to_string())),
+            _ => Ok(value.to_string()),
+        }
+    }
+
+    // `number`, an integer or float as it displays, with the decimal separator and grouping set by
+    // `decimal_comma` and `group_thousands`. Infinities and NaN are left as they are.
+    fn format_number(&self, number: String) -> String {
+        let (decimal, group) = match self.decimal_comma {
+            true => (",", "."),
+            false => (".", ","),
+        };
+        let (sign, digits) = number.strip_prefix('-').map_or(("", number.as_str()), |digits| ("-", digits));
+        let (whole, fraction) = digits.split_once('.').map_or((digits, None), |(whole, fraction)| (whole, Some(fraction)));
+        if !whole.bytes().all(|b| b.is_ascii_digit()) {
+            return number;
+        }
+        let mut formatted = sign.to_owned();
+        for (i, digit) in whole.chars().enumerate() {
+            if self.group_thousands && i > 0 && (whole.len() - i) % 3 == 0 {
+                formatted.push_str(group);
+            }
+            formatted.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            formatted.push_str(decimal);
+            formatted.push_str(fraction);
+        }
+        formatted
+    }
+
+    // Run the body of `function` in a fresh scope with its parameters bound to `arguments`, which
+    // have already been checked against its arity.
+    // A tail call to another function, `return f(...)`, replaces this one: its body is run by
+    // going round the loop again, rather than in a nested call, so that tail recursion doesn't
+    // grow the stack however deep it goes.
+    // An argument of `None` leaves its parameter to its default value.
+    fn call_function(&mut self, function: &Function, mut arguments: Vec<Option<Value>>) -> Result<Value, Error> {
+        let mut tail_callee: Option<Rc<Function>> = None;
+        loop {
+            let function = tail_callee.as_deref().unwrap_or(function);
+            if function.is_generator {
+                return self.call_generator(function, arguments);
+            }
+            let new_env = self.new_scope(function.closure.environment());
+            self.bind_parameters(function, arguments, &new_env)?;
+            // Falling off the end of the body returns `nil`. An initializer always returns `this`,
+            // which the resolver makes sure is the only thing it can `return`.
+            let result = self.execute_block(&function.body, Rc::clone(&new_env));
+            self.recycle_scope(new_env);
+            if function.is_initializer && matches!(result, Ok(()) | Err(Error::Return(_))) {
+                return Ok(function.closure.environment().borrow().get_here("this").unwrap_or(Value::Nil));
+            }
+            let call = match result {
+                Ok(()) => return Ok(Value::Nil),
+                Err(Error::Return(value)) => return Ok(value),
+                Err(Error::TailCall(call)) => *call,
+                Err(e) => return Err(e),
+            };
+            check_arity(&call.callee.arity(), &call.arguments).map_err(|error| self.call_error(&call.paren, error))?;
+            tail_callee = Some(call.callee);
+            arguments = call.arguments.into_iter().map(Some).collect();
+        }
+    }
+
+    // A new scope enclosed by `enclosing`, for a call: a reused one from `scope_pool` if there is
+    // one.
+    fn new_scope(&mut self, enclosing: Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        match self.scope_pool.pop() {
+            Some(scope) => {
+                scope.borrow_mut().reset(Some(enclosing));
+                scope
+            },
+            None => Rc::new(RefCell::new(Environment::new(Some(enclosing)))),
+        }
+    }
+
+    // Put the scope of a finished call back in `scope_pool`, unless something still refers to it.
+    // A closure created in the call that outlives it keeps the scope, which then mustn't be reused.
+    // A function stored in the scope itself only refers to it weakly (see `Environment::store()`),
+    // and is dropped when the scope is emptied, so the scope is only pooled if no weak reference
+    // is left after that either.
+    fn recycle_scope(&mut self, scope: Rc<RefCell<Environment>>) {
+        if Rc::strong_count(&scope) > 1 || self.scope_pool.len() >= SCOPE_POOL_SIZE {
+            return;
+        }
+        scope.borrow_mut().reset(None);
+        if Rc::weak_count(&scope) == 0 {
+            self.scope_pool.push(scope);
+        }
+    }
+
+    // Run the whole body of the generator `function` and return the values it yielded, as an array,
+    // once it finishes or hits a `return;`. The values are all computed up front rather than as
+    // they are used, so a generator that yields forever never returns.
+    fn call_generator(&mut self, function: &Function, arguments: Vec<Option<Value>>) -> Result<Value, Error> {
+        let new_env = Rc::new(RefCell::new(Environment::new(Some(function.closure.environment()))));
+        self.bind_parameters(function, arguments, &new_env)?;
+        let enclosing = self.yielded.replace(Vec::new());
+        let result = self.execute_block(&function.body, new_env);
+        let yielded = mem::replace(&mut self.yielded, enclosing).unwrap_or_default();
+        match result {
+            Ok(()) | Err(Error::Return(_)) => Ok(Value::Array(Rc::new(RefCell::new(yielded)), false)),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Define the parameters of `function` in `environment`, the scope of a call to it. Parameters
+    // left out of the call get their default value, evaluated in that scope so that it can use the
+    // parameters before it. A rest parameter gets an array of the arguments left over.
+    fn bind_parameters(&mut self, function: &Function, arguments: Vec<Option<Value>>, environment: &Rc<RefCell<Environment>>) -> Result<(), Error> {
+        let mut arguments = arguments.into_iter();
+        for param in function.params.iter() {
+            if param.rest {
+                let rest = arguments.by_ref().flatten().collect();
+                environment.borrow_mut().define(param.name.lexeme.to_owned(), Some(&Value::Array(Rc::new(RefCell::new(rest)), false)));
+                break;
+            }
+            let value = match (arguments.next().flatten(), &param.default) {
+                (Some(argument), _) => argument,
+                (None, Some(default)) => {
+                    let previous = mem::replace(&mut self.environment, Rc::clone(environment));
+                    let value = self.evaluate(default);
+                    self.environment = previous;
+                    value?
+                }
+                // The arity check (or `keyword_slots()`) leaves no parameter without a value.
+                (None, None) => Value::Nil,
+            };
+            environment.borrow_mut().define(param.name.lexeme.to_owned(), Some(&value));
+        }
         Ok(())
     }
 
+    // Calling a class creates a new instance, and runs its `init` method (if any) on it with the
+    // arguments.
+    fn call_class(&mut self, class: &Rc<Class>, arguments: Vec<Option<Value>>) -> Result<Value, Error> {
+        let instance = Instance::new(Rc::clone(class));
+        if let Some(init) = class.find_method("init") {
+            self.call_function(&init.bind(&instance), arguments)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+
+    // Look up the variable `name` of the expression `id`, in the scope the resolver found for it.
+    // We do not allow uninitialized variables.
+    fn look_up_variable(&self, id: usize, name: &token::Token) -> Result<Value, Error> {
+        let value = match self.locals.get(&id) {
+            Some(&distance) => self.environment.borrow().get_at(distance, name)?,
+            None => self.globals.borrow().get(name)?,
+        };
+        value.ok_or_else(|| self.error(name, "Variable not initialized."))
+    }
+
+    // Assign to the variable `name` of the expression `id`, in the scope the resolver found for it.
+    fn assign_variable(&self, id: usize, name: &token::Token, value: &Value) -> Result<(), Error> {
+        match self.locals.get(&id) {
+            Some(&distance) => self.environment.borrow_mut().assign_at(distance, name, value),
+            None => self.globals.borrow_mut().assign(name, value),
+        }
+    }
+
     // Runs `accept` for expressions.
     fn evaluate(&mut self, expr: &expr::Expr) -> Result<Value, Error> {
         self.accept_expr(expr)
     }
 
-    fn is_truthy(&self, value: &Value) -> bool {
+    // Evaluate the positional arguments of a call or the elements of an array literal, left to
+    // right, with the elements of each spread array in place of the spread.
+    fn evaluate_elements(&mut self, exprs: &[expr::Expr]) -> Result<Vec<Value>, Error> {
+        let mut values = Vec::new();
+        for expr in exprs {
+            match expr {
+                expr::Expr::Spread { ellipsis, expression } => match self.evaluate(expression)? {
+                    Value::Array(array, _) => {
+                        self.check_size(values.len() + array.borrow().len())
+                            .map_err(|message| self.error(ellipsis, &message))?;
+                        values.extend(array.borrow().iter().cloned());
+                    },
+                    _ => return Err(self.error(ellipsis, "Can only spread an array.")),
+                },
+                _ => values.push(self.evaluate(expr)?),
+            }
+        }
+        Ok(values)
+    }
+
+    // Fail if a string or array of `size` characters or elements would be over `max_size`.
+    pub(crate) fn check_size(&self, size: usize) -> Result<(), String> {
+        match self.max_size {
+            Some(max_size) if size > max_size => Err("Value too large.".to_owned()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn is_truthy(&self, value: &Value) -> bool {
         match *value {
             Value::Nil => false,
             Value::Bool(x) => x,
@@ -296,17 +1364,305 @@ impl<'a> Interpreter<'a> {
         }
     }
 
+    // Whether a condition holds. Usually this is just truthiness, but under `strict_bool` only an
+    // actual `Value::Bool` is accepted; `token` is where the error is reported otherwise.
+    fn is_condition_true(&self, value: &Value, token: &token::Token) -> Result<bool, Error> {
+        match (self.strict_bool, value) {
+            (_, &Value::Bool(x)) => Ok(x),
+            (false, _) => Ok(self.is_truthy(value)),
+            (true, _) => Err(self.error(token, "Condition must be a boolean.")),
+        }
+    }
+
+    // Evaluate the right operand of `and`/`or`, which under `strict_bool` must be a boolean too.
+    fn evaluate_operand(&mut self, operand: &expr::Expr, operator: &token::Token) -> Result<Value, Error> {
+        let value = self.evaluate(operand)?;
+        self.is_condition_true(&value, operator)?;
+        Ok(value)
+    }
+
+    // The position in an array of length `length` that `index` refers to. A negative index counts
+    // back from the end, so `-1` is the last element.
+    fn array_index(&self, index: &Value, length: usize, bracket: &token::Token) -> Result<usize, Error> {
+        let index = match *index {
+            Value::Integer(x) => x,
+            Value::Number(x) if x.fract() == 0.0 => x as i64,
+            _ => return Err(self.error(bracket, "Array index must be an integer.")),
+        };
+        let position = if index < 0 { index + length as i64 } else { index };
+        if position < 0 || position >= length as i64 {
+            return Err(self.error(bracket, &format!("Array index {} out of bounds for length {}.", index, length)));
+        }
+        Ok(position as usize)
+    }
+
+    fn map_key(&self, key: &Value, token: &token::Token) -> Result<MapKey, Error> {
+        MapKey::from_value(key).ok_or_else(|| self.error(token, "Map key must be a string or a number."))
+    }
+
+    // My implementation of != and == simply piggybacks Rust's `PartialEq` trait, except for
+    // numbers when either is a float. Those are compared by value, so `1 == 1.0`, and with a
+    // relative tolerance (see `floats_equal()`), so `0.1 + 0.2 == 0.3`. Integers are still
+    // compared exactly.
+    // Arrays and maps are equal if their elements are, compared the same way, so `[1] == [1.0]`,
+    // with one exception: NaN. On its own, NaN is equal to nothing, as IEEE 754 says, so
+    // `nan == nan` is false. But as an element it is equal to a NaN with the same bits, so
+    // `[nan] == [nan]` is true, as is `a == a` for any array `a`. Otherwise an array holding a NaN
+    // wouldn't even be equal to a copy of itself.
+    pub(crate) fn is_equal(&self, left: &Value, right: &Value) -> bool {
+        self.difference(left, right).is_none()
+    }
+
+    // Where `left` and `right` first differ, compared as by `is_equal()`, or `None` if they are
+    // equal.
+    pub(crate) fn difference(&self, left: &Value, right: &Value) -> Option<Difference> {
+        find_difference(left, right, false, &mut Vec::new())
+    }
+
+    // Both operands of an arithmetic or comparison operator, as two integers if they both are,
+    // and otherwise as two floats. `operator` is where the error is reported if either isn't a
+    // number.
+    fn number_operands(&self, left: &Value, right: &Value, operator: &token::Token) -> Result<Operands, Error> {
+        match (left, right) {
+            (&Value::Integer(x), &Value::Integer(y)) => Ok(Operands::Integers(x, y)),
+            (&Value::Integer(x), &Value::Number(y)) => Ok(Operands::Floats(x as f64, y)),
+            (&Value::Number(x), &Value::Integer(y)) => Ok(Operands::Floats(x, y as f64)),
+            (&Value::Number(x), &Value::Number(y)) => Ok(Operands::Floats(x, y)),
+            _ => Err(self.operand_not_number_error(operator)),
+        }
+    }
+
+    // How the operands of a comparison are ordered (see `compare_values()`). Anything that can't be
+    // compared, including a string and a number, is an error.
+    fn compare(&self, left: &Value, right: &Value, operator: &token::Token) -> Result<Option<Ordering>, Error> {
+        compare_values(left, right).ok_or_else(|| self.error(operator, "Operands must be two numbers, two strings or two characters."))
+    }
+
+    // A bitwise operand as an integer. A float is accepted if it is a whole number in range, so
+    // `6.0 & 3` works but `1.5 << 1` is an error.
+    fn integer_operand(&self, value: &Value, operator: &token::Token) -> Result<i64, Error> {
+        match *value {
+            Value::Integer(x) => Ok(x),
+            Value::Number(x) if x.fract() == 0.0 && x >= i64::MIN as f64 && x < i64::MAX as f64 => Ok(x as i64),
+            _ => Err(self.error(operator, "Operand(s) must be an integer.")),
+        }
+    }
+
+    fn integer_operands(&self, left: &Value, right: &Value, operator: &token::Token) -> Result<(i64, i64), Error> {
+        Ok((self.integer_operand(left, operator)?, self.integer_operand(right, operator)?))
+    }
+
+    // The result of a checked integer operation, which is `None` if it overflowed.
+    fn integer_result(&self, result: Option<i64>, operator: &token::Token) -> Result<Value, Error> {
+        result.map(Value::Integer).ok_or_else(|| self.error(operator, "Integer overflow."))
+    }
+
+    // An error in the script imported from `path`, reported at the `import`.
+    fn import_error(&self, keyword: &token::Token, path: &str, error: &Error) -> Error {
+        self.error(keyword, &format!("In '{}': {}", path, error))
+    }
+
+    // The result of a float operation, which is an error if it overflowed to an infinity or isn't a
+    // number, unless `ieee_floats` is set.
+    fn float_result(&self, result: f64, operator: &token::Token) -> Result<Value, Error> {
+        if self.ieee_floats || result.is_finite() {
+            Ok(Value::Number(result))
+        } else if result.is_nan() {
+            Err(self.error(operator, "Result is not a number."))
+        } else {
+            Err(self.error(operator, "Numeric overflow."))
+        }
+    }
+
     fn operand_not_number_error(&self, token: &token::Token) -> Error {
         self.error(token, "Operand(s) must be a number.")
     }
 
-    // Helper function to return a `RuntimeError` object to be bubbled up.
-    // Reporting to `crate::error_token` is done once the error has been bubbled up to
-    // `interpret()`. Doing it this way will make it easier for `environment` methods to err, since
-    // they do not have to call `crate::error_token` themselves. Instead, `crate::error_token` is
-    // called in one place (see `interpret()`).
+    // Helper function to return a `RuntimeError` object to be bubbled up to `interpret()`, and
+    // from there to the caller, which reports it.
     fn error(&self, token: &token::Token, message: &str) -> Error {
-        Error::RuntimeError { token: token.to_owned(), message: message.to_owned() }
+        Error::runtime(token, message)
+    }
+}
+
+// The most emptied scopes kept for reuse. Calls only need as many as are finished between two
+// calls, so a few go a long way.
+const SCOPE_POOL_SIZE: usize = 64;
+
+// Relative tolerance of float equality, as in Python's `math.isclose()`: about nine significant
+// digits have to match.
+const FLOAT_TOLERANCE: f64 = 1e-9;
+
+// Whether two floats are equal to within `FLOAT_TOLERANCE` of the larger one. There is no absolute
+// tolerance, so a float is only equal to `0` if it is exactly zero. NaN is equal to nothing, and
+// the infinities only to themselves.
+// The comparison operators use it too (see `compare_values()`).
+// Map keys are still matched exactly, so `m[0.1 + 0.2]` and `m[0.3]` are different entries.
+fn floats_equal(x: f64, y: f64) -> bool {
+    x == y || (x - y).abs() <= FLOAT_TOLERANCE * x.abs().max(y.abs())
+}
+
+// How two values are ordered by the comparison operators, and by natives like `max_by`; `None` if
+// they can't be compared at all, and `Some(None)` if they can but are unordered, as NaN is.
+// Two numbers are compared as for arithmetic, except that floats equal by `==` (see
+// `floats_equal()`) are ordered as equal, so that `0.1 + 0.2 <= 0.3` agrees with
+// `0.1 + 0.2 == 0.3`, and `0.1 + 0.2 > 0.3` is false.
+// Two strings are compared lexicographically by Unicode code point (Rust's `str` ordering), so it
+// is case-sensitive, with `"Z" < "a"`, and a prefix comes first, with `"ab" < "abc"`. Two
+// characters are compared by code point too.
+pub(crate) fn compare_values(left: &Value, right: &Value) -> Option<Option<Ordering>> {
+    let (x, y) = match (left, right) {
+        (Value::String_(x), Value::String_(y)) => return Some(Some(x.cmp(y))),
+        (Value::Char(x), Value::Char(y)) => return Some(Some(x.cmp(y))),
+        (&Value::Integer(x), &Value::Integer(y)) => return Some(Some(x.cmp(&y))),
+        (&Value::Integer(x), &Value::Number(y)) => (x as f64, y),
+        (&Value::Number(x), &Value::Integer(y)) => (x, y as f64),
+        (&Value::Number(x), &Value::Number(y)) => (x, y),
+        _ => return None,
+    };
+    match floats_equal(x, y) {
+        true => Some(Some(Ordering::Equal)),
+        false => Some(x.partial_cmp(&y)),
     }
 }
 
+// Whether `expr` is a call with keyword arguments, which `return` makes like any other expression
+// rather than as a tail call.
+fn has_keywords(expr: &expr::Expr) -> bool {
+    matches!(expr, expr::Expr::Call { keywords, .. } if !keywords.is_empty())
+}
+
+// The namespace for an aliased import: an instance of an empty class named after the alias, with
+// a field for each value defined in the imported script's `scope`.
+fn namespace(alias: &token::Token, scope: &Environment) -> Value {
+    let class = Class { name: alias.to_owned(), superclass: None, methods: HashMap::new() };
+    let instance = Instance::new(Rc::new(class));
+    instance.fields.borrow_mut().extend(scope.values_here());
+    Value::Instance(instance)
+}
+
+fn check_arity(arity: &RangeInclusive<usize>, arguments: &[Value]) -> Result<(), CallError> {
+    if arity.contains(&arguments.len()) {
+        return Ok(());
+    }
+    let expected = match (*arity.start(), *arity.end()) {
+        (min, max) if min == max => min.to_string(),
+        (min, usize::MAX) => format!("at least {}", min),
+        (min, max) => format!("{} to {}", min, max),
+    };
+    Err(format!("Expected {} arguments but got {}.", expected, arguments.len()).into())
+}
+
+// An output for `with_output()` that can be read back while the interpreter still owns it, e.g. to
+// capture what a script prints. Clones share the same buffer, so keep one to read from.
+#[derive(Clone, Default)]
+pub struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl SharedOutput {
+    // Everything written so far. Invalid UTF-8 is replaced, but `print` only writes strings.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// The outcome of the `assert`/`expect` checks run in test mode: how many passed, and the messages
+// of those that failed, in order, each starting with the line of the check. It displays as a report of the failures, then a summary line,
+// e.g. `3 passed, 1 failed`.
+#[derive(Default)]
+pub struct TestResults {
+    pub passed: usize,
+    pub failures: Vec<String>,
+}
+
+impl TestResults {
+    pub fn all_passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for TestResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for failure in &self.failures {
+            writeln!(f, "FAIL: {}", failure)?;
+        }
+        write!(f, "{} passed, {} failed", self.passed, self.failures.len())
+    }
+}
+
+// Where two values compared by `Interpreter::difference()` differ. `path` leads to the first
+// difference inside them, as the indices and keys of the nested arrays and maps, e.g. `[1]["k"]`,
+// and `values` are what the two have there, `None` on the side with no element at all. Both are
+// left empty if the values differ as a whole, e.g. `1` and `"1"`.
+#[derive(Default)]
+pub(crate) struct Difference {
+    pub path: String,
+    pub values: Option<(Option<Value>, Option<Value>)>,
+}
+
+// `Interpreter::difference()`. `nested` is whether `left` and `right` are elements of arrays or
+// maps, and so NaN is equal to itself. `comparing` holds the pairs of arrays or maps whose
+// comparison is under way further out. An array can contain itself, e.g. after `push(a, a)`, so a
+// pair can come up again inside its own comparison; it is taken to be equal there, as any
+// difference will be found elsewhere. Otherwise comparing `a` with an array like it would never
+// end.
+fn find_difference(left: &Value, right: &Value, nested: bool, comparing: &mut Vec<(*const (), *const ())>) -> Option<Difference> {
+    let equal = match (left, right) {
+        (&Value::Integer(x), &Value::Number(y)) | (&Value::Number(y), &Value::Integer(x)) => floats_equal(x as f64, y),
+        (&Value::Number(x), &Value::Number(y)) => floats_equal(x, y) || (nested && x.to_bits() == y.to_bits()),
+        (Value::Array(x, _), Value::Array(y, _)) => {
+            let pair = (Rc::as_ptr(x).cast(), Rc::as_ptr(y).cast());
+            if Rc::ptr_eq(x, y) || comparing.contains(&pair) {
+                return None;
+            }
+            comparing.push(pair);
+            let (x, y) = (x.borrow(), y.borrow());
+            let difference = (0..x.len().max(y.len()))
+                .find_map(|i| element_difference(&format!("[{}]", i), x.get(i), y.get(i), comparing));
+            comparing.pop();
+            return difference;
+        },
+        (Value::Map(x, _), Value::Map(y, _)) => {
+            let pair = (Rc::as_ptr(x).cast(), Rc::as_ptr(y).cast());
+            if Rc::ptr_eq(x, y) || comparing.contains(&pair) {
+                return None;
+            }
+            comparing.push(pair);
+            let (x, y) = (x.borrow(), y.borrow());
+            let keys: BTreeSet<&MapKey> = x.keys().chain(y.keys()).collect();
+            let difference = keys.into_iter()
+                .find_map(|key| element_difference(&format!("[{}]", Value::from(key).quoted()), x.get(key), y.get(key), comparing));
+            comparing.pop();
+            return difference;
+        },
+        _ => left == right,
+    };
+    (!equal).then(Difference::default)
+}
+
+// The difference between the elements of two arrays or maps at `index`, where either may be
+// missing.
+fn element_difference(index: &str, left: Option<&Value>, right: Option<&Value>, comparing: &mut Vec<(*const (), *const ())>) -> Option<Difference> {
+    let mut difference = match (left, right) {
+        (Some(left), Some(right)) => find_difference(left, right, true, comparing)?,
+        _ => Difference::default(),
+    };
+    difference.path.insert_str(0, index);
+    difference.values.get_or_insert_with(|| (left.cloned(), right.cloned()));
+    Some(difference)
+}
+
+// The operands of a numeric binary operator, after promotion.
+enum Operands {
+    Integers(i64, i64),
+    Floats(f64, f64),
+}
@@ -0,0 +1,97 @@
+// The interpreter as a library. `run_source()` runs a whole program, and the stages it is made of
+// (scanner, parser, resolver, interpreter) are public too, for callers that want to drive them
+// themselves, like the command-line interface in `main.rs`.
+// Nothing here reports errors: they are all returned, for the caller to print or handle. Warnings
+// are kept on the interpreter instead (see `Interpreter::take_warnings()`).
+pub mod scanner;
+pub mod token;
+pub mod expr;
+pub mod stmt;
+pub mod ast_printer;
+pub mod dot_printer;
+pub mod parser;
+pub mod resolver;
+pub mod interpreter;
+pub mod environment;
+pub mod error;
+pub mod function;
+pub mod class;
+pub mod warn;
+pub mod cache;
+mod natives;
+
+pub use crate::error::Error;
+pub use crate::interpreter::{Interpreter, SharedOutput, TestResults};
+pub use crate::token::Value;
+pub use crate::warn::Warning;
+
+use crate::ast_printer::{AstPrinter, SourcePrinter};
+use crate::environment::Environment;
+use crate::expr::Expr;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::stmt::Stmt;
+use crate::token::TokenType;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Run `source` in a new interpreter. Returns the values of the top-level expression statements,
+// or else every error found while scanning, parsing or resolving, or the runtime error that
+// stopped the program.
+pub fn run_source(source: &str) -> Result<Vec<Value>, Vec<Error>> {
+    let globals = Rc::new(RefCell::new(Environment::new(None)));  // outermost scope.
+    run_in(&mut Interpreter::new(globals), source)
+}
+
+// Like `run_source()`, but in an existing interpreter, which keeps its globals from one call to
+// the next.
+// The source is scanned with the interpreter's `keywords`, if it has some, so a dialect set up on
+// the interpreter applies to everything it runs.
+pub fn run_in(interpreter: &mut Interpreter, source: &str) -> Result<Vec<Value>, Vec<Error>> {
+    let statements = parse_with_keywords(source, interpreter.keywords.clone())?;
+    Resolver::new(interpreter).resolve(&statements)?;
+    interpreter.interpret(&statements).map_err(|error| vec![error])
+}
+
+// Scan and parse `source`, without running it.
+pub fn parse_source(source: &str) -> Result<Vec<Stmt>, Vec<Error>> {
+    parse_with_keywords(source, None)
+}
+
+// Like `parse_source()`, but with `keywords` in place of the default keyword table if given (see
+// `Scanner::new()`).
+pub fn parse_with_keywords(source: &str, keywords: Option<HashMap<String, TokenType>>) -> Result<Vec<Stmt>, Vec<Error>> {
+    let tokens = Scanner::new(source.to_owned(), keywords).scan_tokens()?;
+    Parser::new(tokens).parse()
+}
+
+// The parsed program as S-expressions (see `AstPrinter`), one line per top-level statement, for
+// snapshot tests of the parser. It only depends on the source: nodes are printed in source order,
+// and without ids or addresses.
+pub fn dump_ast(source: &str) -> Result<String, Vec<Error>> {
+    let statements = parse_source(source)?;
+    // Printing can't fail: the `()` error type is never produced.
+    Ok(AstPrinter.print(&statements).unwrap_or_default())
+}
+
+// The parsed program printed back as source (see `SourcePrinter`), with only the parentheses it
+// needs.
+pub fn dump_source(source: &str) -> Result<String, Vec<Error>> {
+    let statements = parse_source(source)?;
+    // Printing can't fail: the `()` error type is never produced.
+    Ok(SourcePrinter::new().print(&statements).unwrap_or_default())
+}
+
+// Scan and parse `source` as a single expression, with no `;` after it.
+pub fn parse_expression(source: &str) -> Result<Expr, Vec<Error>> {
+    parse_expression_with_keywords(source, None)
+}
+
+// Like `parse_expression()`, but with `keywords` in place of the default keyword table if given.
+pub fn parse_expression_with_keywords(source: &str, keywords: Option<HashMap<String, TokenType>>) -> Result<Expr, Vec<Error>> {
+    let tokens = Scanner::new(source.to_owned(), keywords).scan_tokens()?;
+    Parser::new(tokens).parse_expression()
+}
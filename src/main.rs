@@ -1,102 +1,513 @@
-mod scanner;
-mod token;
-mod expr;
-mod stmt;
-// mod ast_printer;
-mod parser;
-mod interpreter;
-mod environment;
-mod error;
-
-use crate::scanner::Scanner;
-use crate::parser::Parser;
-use crate::environment::Environment;
-use crate::error::Error;
+use toy_interpreter::ast_printer::{AstPrinter, SourcePrinter};
+use toy_interpreter::cache;
+use toy_interpreter::dot_printer::DotPrinter;
+use toy_interpreter::environment::Environment;
+use toy_interpreter::resolver::Resolver;
+use toy_interpreter::scanner::Scanner;
+use toy_interpreter::stmt::Stmt;
+use toy_interpreter::token::TokenType;
+use toy_interpreter::{Error, Interpreter, Value};
 
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::env;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Read, Write};
 use std::process;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// Command-line switches, shared by file and prompt runs.
+#[derive(Default)]
+struct Options {
+    // Print the scanned tokens, one per line, instead of parsing and running the program.
+    tokens: bool,
+
+    // Print the parsed program as S-expressions instead of running it.
+    ast: bool,
+
+    // Print the parsed program as a GraphViz DOT digraph instead of running it.
+    parse_tree_dot: bool,
+
+    // Print the parsed program back as source, with only the parentheses it needs, instead of
+    // running it.
+    source: bool,
+
+    // Skip `debug_assert` statements entirely.
+    release: bool,
+
+    // Escape non-ASCII characters in `print` output.
+    ascii_out: bool,
+
+    // Reject non-boolean conditions instead of going by truthiness.
+    strict_bool: bool,
+
+    // Let float arithmetic, including division by zero, give infinities and NaN instead of erring.
+    ieee_floats: bool,
+
+    // Make `/` on two integers truncating integer division.
+    int_div: bool,
+
+    // Show numbers with `,` as the decimal separator.
+    decimal_comma: bool,
+
+    // Group the digits of numbers in threes.
+    group_thousands: bool,
+
+    // Carry on with the next top-level statement after a runtime error.
+    keep_going: bool,
+
+    // Carry on with the next script after one that failed.
+    continue_on_error: bool,
+
+    // Show the prompt even if stdin isn't a terminal, e.g. to drive it from a pipe.
+    interactive: bool,
+
+    // Define the functions declared in a block before running it, so they can be called earlier.
+    hoist: bool,
+
+    // Disable `eval()`.
+    sandbox: bool,
+
+    // Warn about local variables that shadow one in an enclosing local scope.
+    warn_shadow: bool,
+
+    // Count `assert`/`expect` failures instead of stopping at the first, and print a summary of
+    // the checks at the end.
+    test: bool,
+
+    // Load scripts from their `.loxc` cache when it is up to date, and write it when it isn't.
+    cache: bool,
+
+    // How many elements of an array or map echoed at the prompt to show before cutting it short,
+    // from `--echo-limit=N`: `ECHO_LIMIT` if not given, and all of them if 0. `print` always
+    // shows everything.
+    echo_limit: Option<usize>,
+}
+
+const ECHO_LIMIT: usize = 10;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 2 {
-        eprintln!("Usage: cargo run [-- script]");
-        process::exit(64);
-    } else if args.len() == 2 {
-        run_file(&args[1]);
+    let mut options = Options::default();
+    let mut paths: Vec<String> = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--version" => {
+                println!("toy_interpreter {}", env!("CARGO_PKG_VERSION"));
+                return;
+            },
+            "--tokens" => options.tokens = true,
+            "--ast" => options.ast = true,
+            "--dot" | "--parse-tree-dot" => options.parse_tree_dot = true,
+            "--source" => options.source = true,
+            "--release" => options.release = true,
+            "--ascii-out" => options.ascii_out = true,
+            "--strict-bool" => options.strict_bool = true,
+            "--ieee-floats" => options.ieee_floats = true,
+            "--int-div" => options.int_div = true,
+            "--decimal-comma" => options.decimal_comma = true,
+            "--group-thousands" => options.group_thousands = true,
+            "--keep-going" => options.keep_going = true,
+            "--continue-on-error" => options.continue_on_error = true,
+            "--interactive" => options.interactive = true,
+            "--hoist" => options.hoist = true,
+            "--sandbox" => options.sandbox = true,
+            "--warn-shadow" => options.warn_shadow = true,
+            "--cache" => options.cache = true,
+            "--test" => options.test = true,
+            _ if arg.starts_with("--echo-limit=") => match arg["--echo-limit=".len()..].parse() {
+                Ok(limit) => options.echo_limit = Some(limit),
+                Err(_) => usage(),
+            },
+            _ if arg.starts_with("--") => usage(),
+            _ => paths.push(arg),
+        }
+    }
+
+    if !paths.is_empty() {
+        run_files(&paths, &options);
+    } else if options.interactive || io::stdin().is_terminal() {
+        run_prompt(&options);
     } else {
-        run_prompt();
+        // Piped input, e.g. `echo 'print 1;' | toy_interpreter`, is run as a script.
+        run_stdin(&options);
     }
 }
 
-fn run_file(file_path: &str) {
-    let source = fs::read_to_string(file_path).expect("Failed to read file");
-    let mut environment = Environment::new(None);  // outermost scope.
-    match run(&source, &mut environment) {
-        Err(Error::ScanError) | Err(Error::ParseError) => process::exit(65),
-        Err(Error::RuntimeError { token: _, message: _ }) => process::exit(70),
-        Ok(()) => (),
-    };
+fn usage() -> ! {
+    eprintln!("Usage: cargo run [-- [--tokens] [--ast] [--dot] [--source] [--release] [--ascii-out] [--strict-bool] [--ieee-floats] [--int-div] [--decimal-comma] [--group-thousands] [--keep-going] [--continue-on-error] [--interactive] [--hoist] [--sandbox] [--warn-shadow] [--cache] [--test] [--echo-limit=N] [script...] | --version]");
+    process::exit(64);
+}
+
+// Run the scripts in order, in the same interpreter, so that later ones can use the globals defined
+// by earlier ones. A script that fails stops the run, unless `--continue-on-error` is given.
+// Either way, the exit code is that of the first failure, or else of the `--test` checks.
+fn run_files(file_paths: &[String], options: &Options) {
+    let mut interpreter = new_interpreter(options);
+    let mut exit_code = 0;
+    for file_path in file_paths {
+        let source = fs::read_to_string(file_path).expect("Failed to read file");
+        interpreter.current_file = Some(PathBuf::from(file_path));
+        if let Err(error) = run(&source, &mut interpreter, options, false) {
+            if exit_code == 0 {
+                exit_code = exit_code_for(&error);
+            }
+            if !options.continue_on_error {
+                break;
+            }
+        }
+    }
+    let tests_code = report_tests(&interpreter, options);
+    process::exit(if exit_code == 0 { tests_code } else { exit_code });
+}
+
+fn run_stdin(options: &Options) {
+    let mut source = String::new();
+    if let Err(error) = io::stdin().read_to_string(&mut source) {
+        eprintln!("Failed to read input: {}", error);
+        process::exit(74);
+    }
+    let mut interpreter = new_interpreter(options);
+    let result = run(&source, &mut interpreter, options, false);
+    let tests_code = report_tests(&interpreter, options);
+    if let Err(error) = result {
+        process::exit(exit_code_for(&error));
+    }
+    process::exit(tests_code);
+}
+
+// Under `--test`, print the failed checks and how many passed and failed, e.g. `3 passed, 1 failed`.
+// Returns the exit code for the checks: 1 if any failed, so that CI notices, and otherwise 0. An
+// error that stopped the run takes precedence.
+fn report_tests(interpreter: &Interpreter, options: &Options) -> i32 {
+    if !options.test {
+        return 0;
+    }
+    let results = interpreter.test_results();
+    println!("{}", results);
+    match results.all_passed() {
+        true => 0,
+        false => 1,
+    }
 }
 
-fn run_prompt() {
-    let mut environment = Environment::new(None);  // outermost scope.
+// The exit code for a script that failed with `error`.
+fn exit_code_for(error: &Error) -> i32 {
+    match error {
+        Error::ScanError { .. } | Error::ParseError { .. } => 65,
+        Error::RuntimeError { .. } => 70,
+        Error::Return(_) | Error::TailCall(_) => unreachable!("`return` outside a function is a parse error"),
+        Error::Break | Error::Continue => unreachable!("`break`/`continue` outside a loop is a parse error"),
+    }
+}
+
+fn run_prompt(options: &Options) {
+    // The same interpreter (and so the same global scope) is used for every line.
+    let mut interpreter = new_interpreter(options);
+    load_prelude(&mut interpreter, options);
     loop {
         print!("> ");
         io::stdout().flush().expect("Flush failed");  // to flush out "> "
         let mut line = String::new();
-        io::stdin()
-            .read_line(&mut line)
-            .expect("Failed to read line");
-        _ = run(&line, &mut environment);
+        if !read_line(&mut line) {
+            println!();  // so the shell's prompt starts on a new line after Ctrl-D
+            return;
+        }
+        if matches!(line.trim(), ":quit" | "exit") {
+            return;
+        }
+        if let Some(path) = line.trim().strip_prefix(".debug ") {
+            debug(path.trim(), &mut interpreter, options);
+            continue;
+        }
+
+        // Keep reading while the input is unfinished: a line ending in `\` is joined with the next
+        // one, and so is one that leaves a bracket, string or block comment open.
+        loop {
+            if ends_with_backslash(&line, &interpreter) {
+                let len = line.trim_end().len();
+                line.truncate(len - 1);
+                line.push('\n');  // keep the line count right for error reporting
+            } else if !is_unfinished(&line, &interpreter) {
+                break;
+            }
+
+            print!("... ");
+            io::stdout().flush().expect("Flush failed");
+            if !read_line(&mut line) {
+                break;
+            }
+        }
+
+        _ = run(&line, &mut interpreter, options, true);
     }
 }
 
-fn run(source: &str, environment: &mut Environment) -> Result<(), Error> {
-    let mut scanner = Scanner::new(source.to_owned());
-    let tokens: Vec<token::Token> = scanner.scan_tokens()?;
+// Run the script at `path` in the prompt's interpreter, pausing before its first statement, and
+// then wherever it is told to by the commands read while paused:
+//     .step          run to the next statement
+//     .continue      run to the next breakpoint, or to the end
+//     .break LINE    pause before each statement on LINE from now on
+//     .env           print the variables in scope, other than the native functions
+// The statements of scripts it imports are run without pausing. Its definitions stay defined at
+// the prompt afterwards.
+fn debug(path: &str, interpreter: &mut Interpreter, options: &Options) {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("Can't read '{}': {}.", path, error);
+            return;
+        },
+    };
+    let file = PathBuf::from(path);
+    let lines: Vec<String> = source.lines().map(str::to_owned).collect();
+    let mut stepping = true;
+    let mut breakpoints = HashSet::new();
+    interpreter.on_statement = Some(Box::new(move |interpreter, line| {
+        if interpreter.current_file.as_ref() != Some(&file) || !(stepping || breakpoints.contains(&line)) {
+            return;
+        }
+        println!("[line {}] {}", line, lines.get(line - 1).map_or("", |text| text.trim()));
+        loop {
+            print!("debug> ");
+            io::stdout().flush().expect("Flush failed");
+            let mut command = String::new();
+            if !read_line(&mut command) {
+                // Run the rest without pausing, since there is nothing left to say what to do.
+                stepping = false;
+                breakpoints.clear();
+                return;
+            }
+            match command.split_whitespace().collect::<Vec<_>>()[..] {
+                [".step"] => {
+                    stepping = true;
+                    return;
+                },
+                [".continue"] => {
+                    stepping = false;
+                    return;
+                },
+                [".break", line] => match line.parse::<usize>() {
+                    Ok(line) => _ = breakpoints.insert(line),
+                    Err(_) => eprintln!("Expected a line number after .break."),
+                },
+                [".env"] => {
+                    for (name, value) in interpreter.variables() {
+                        if !matches!(value, Value::NativeFn(_)) {
+                            println!("{} = {}", name, value.quoted());
+                        }
+                    }
+                },
+                _ => eprintln!("Commands: .step, .continue, .break LINE, .env"),
+            }
+        }
+    }));
 
-    // Only EOF token.
-    if tokens.len() == 1 {
-        return Ok(());
-    }
+    let current_file = interpreter.current_file.replace(PathBuf::from(path));
+    _ = run(&source, interpreter, options, false);
+    interpreter.current_file = current_file;
+    interpreter.on_statement = None;
+}
 
-    let mut parser = Parser::new(tokens);
-    // let expression: expr::Expr = parser.parse()?;
-    let statements: Vec<stmt::Stmt> = parser.parse()?;
+// Read a line of input onto the end of `line`. Returns `false` at EOF. A read error is reported,
+// and exits (with `EX_IOERR`).
+fn read_line(line: &mut String) -> bool {
+    match io::stdin().read_line(line) {
+        Ok(read) => read > 0,
+        Err(error) => {
+            eprintln!("Failed to read input: {}", error);
+            process::exit(74);
+        },
+    }
+}
 
-    // let printer = ast_printer::AstPrinter;
-    // println!("{}", printer.print(&expression));
+// Whether `source` ends in a `\` asking for the next line to be joined on. It is scanned to tell:
+// the `\` has to be a token of its own (which the scanner rejects), so one at the end of a comment,
+// like `// C:\`, doesn't count.
+fn ends_with_backslash(source: &str, interpreter: &Interpreter) -> bool {
+    let source = source.trim_end();
+    if !source.ends_with('\\') {
+        return false;
+    }
+    let position = (source.lines().count(), source.lines().last().map_or(0, |line| line.chars().count()));
+    match Scanner::new(source.to_owned(), interpreter.keywords.clone()).scan_tokens() {
+        Ok(_) => false,
+        Err(errors) => errors.iter().any(|error| error.position() == Some(position)),
+    }
+}
 
-    let mut interpreter = interpreter::Interpreter::new(environment);
-    // let value: token::Value = interpreter.interpret(&expression)?;
-    interpreter.interpret(&statements)?;
+// Whether `source` stops partway through a statement: it has more opening brackets than closing
+// ones, or ends inside a string or block comment. It is scanned as usual, so brackets in strings
+// and comments don't count.
+fn is_unfinished(source: &str, interpreter: &Interpreter) -> bool {
+    match Scanner::new(source.to_owned(), interpreter.keywords.clone()).scan_tokens() {
+        Ok(tokens) => {
+            let depth: isize = tokens.iter()
+                .map(|token| match token.type_ {
+                    TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => 1,
+                    TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => -1,
+                    _ => 0,
+                })
+                .sum();
+            depth > 0
+        },
+        Err(errors) => errors.iter().any(|error| matches!(
+            error,
+            Error::ScanError { message, .. } if message.starts_with("Unterminated")
+        )),
+    }
+}
 
-    // println!("{}", value);
+// Run the user's prelude script, from `$TOYLOX_PRELUDE` or else `~/.toylox_prelude`, so its
+// definitions are available at the prompt. A missing file is silently skipped. Errors in it are
+// reported as usual, but don't stop the REPL from starting.
+fn load_prelude(interpreter: &mut Interpreter, options: &Options) {
+    let path = match (env::var("TOYLOX_PRELUDE"), env::var("HOME")) {
+        (Ok(path), _) => PathBuf::from(path),
+        (Err(_), Ok(home)) => PathBuf::from(home).join(".toylox_prelude"),
+        _ => return,
+    };
 
-    Ok(())
+    if let Ok(source) = fs::read_to_string(path) {
+        _ = run(&source, interpreter, options, false);
+    }
 }
 
-fn error_line(line: usize, message: &str) {
-    report(line, "", message);
+fn new_interpreter(options: &Options) -> Interpreter {
+    let globals = Rc::new(RefCell::new(Environment::new(None)));  // outermost scope.
+    let mut interpreter = Interpreter::new(globals);
+    interpreter.release = options.release;
+    interpreter.ascii_out = options.ascii_out;
+    interpreter.strict_bool = options.strict_bool;
+    interpreter.ieee_floats = options.ieee_floats;
+    interpreter.int_div = options.int_div;
+    interpreter.decimal_comma = options.decimal_comma;
+    interpreter.group_thousands = options.group_thousands;
+    interpreter.hoist = options.hoist;
+    interpreter.sandbox = options.sandbox;
+    interpreter.warn_shadow = options.warn_shadow;
+    interpreter.test_mode = options.test;
+    interpreter
 }
 
-fn error_token(token: &token::Token, message: &str) {
-    if token.type_ == token::TokenType::Eof {
-        report(token.line, " at end", message);
-    } else {
-        report(token.line, &format!(" at '{}'", token.lexeme), message);
+// Run `source`, reporting any warnings, then any errors. Returns the first error, for the exit code:
+// the errors of one run all come from the same stage.
+// Warnings are reported without the source line, since they can come from an imported script.
+// With `echo` (at the prompt), input that is a single expression without a `;` is evaluated and its
+// value printed, e.g. `1 + 2` prints `3`.
+fn run(source: &str, interpreter: &mut Interpreter, options: &Options, echo: bool) -> Result<(), Error> {
+    let result = run_statements(source, interpreter, options, echo);
+    for warning in interpreter.take_warnings() {
+        eprintln!("{}", warning);
     }
+    result.map_err(|errors| {
+        for error in &errors {
+            report(error, source);
+        }
+        errors.into_iter().next().expect("Failed runs have at least one error")
+    })
 }
 
-// fn error_runtime(error: Error) {
-//     if let Error::RuntimeError {message, token} = error {
-//         eprintln!("{}\n[line {}]", message, token.line);
-//     }
-// }
+fn run_statements(source: &str, interpreter: &mut Interpreter, options: &Options, echo: bool) -> Result<(), Vec<Error>> {
+    if options.tokens {
+        for token in Scanner::new(source.to_owned(), interpreter.keywords.clone()).scan_tokens()? {
+            println!("{}", token);
+        }
+        return Ok(());
+    }
 
-fn report(line: usize, loc: &str, message: &str) {
-    eprintln!("[line {line}] Error{loc}: {message}");
+    // If the input isn't a bare expression, it is parsed again as statements, which gives the
+    // errors to report.
+    let expression = match echo {
+        true => toy_interpreter::parse_expression_with_keywords(source, interpreter.keywords.clone()).ok(),
+        false => None,
+    };
+    let echo = expression.is_some();
+    let statements = match (expression, interpreter.current_file.as_deref().and_then(cache::cache_path)) {
+        (Some(expression), _) => vec![Stmt::Expression { expression }],
+        (None, Some(cache_path)) if options.cache && interpreter.keywords.is_none() => parse_cached(source, &cache_path, interpreter)?,
+        (None, _) => toy_interpreter::parse_with_keywords(source, interpreter.keywords.clone())?,
+    };
+
+    if options.ast {
+        // Printing can't fail: the `()` error type is never produced.
+        if let Ok(ast) = AstPrinter.print(&statements) {
+            println!("{}", ast);
+        }
+        return Ok(());
+    }
+    if options.source {
+        if let Ok(source) = SourcePrinter::new().print(&statements) {
+            println!("{}", source);
+        }
+        return Ok(());
+    }
+    if options.parse_tree_dot {
+        println!("{}", DotPrinter::new().print(&statements));
+        return Ok(());
+    }
+
+    // Work out which scope each variable refers to before running anything.
+    Resolver::new(interpreter).resolve(&statements)?;
+
+    let values = match options.keep_going {
+        true => {
+            let (values, errors) = interpreter.interpret_collecting(&statements);
+            if !errors.is_empty() {
+                return Err(errors);
+            }
+            values
+        },
+        false => interpreter.interpret(&statements).map_err(|error| vec![error])?,
+    };
+    if echo {
+        let limit = match options.echo_limit.unwrap_or(ECHO_LIMIT) {
+            0 => usize::MAX,
+            limit => limit,
+        };
+        for value in values {
+            println!("{}", value.quoted_up_to(limit));
+        }
+    }
+    Ok(())
 }
 
+// Parse `source`, or load it from the cache at `cache_path` if that is up to date with it. The
+// cache is written afresh if it isn't.
+// The cache only records the source, not the keyword table it was scanned with, so it is skipped
+// for dialects.
+fn parse_cached(source: &str, cache_path: &Path, interpreter: &Interpreter) -> Result<Vec<Stmt>, Vec<Error>> {
+    if let Some(statements) = cache::load(cache_path, source) {
+        return Ok(statements);
+    }
+    let statements = toy_interpreter::parse_with_keywords(source, interpreter.keywords.clone())?;
+    cache::store(cache_path, source, &statements);
+    Ok(statements)
+}
+
+// Print the error, then the source line it is on with a `^` under the column, e.g.
+//
+//     [line 2, col 12] Error at ')': Expected expression.
+//       |
+//     2 | print (1 + );
+//       |            ^
+//
+// The context is left out if the line is not in the source, e.g. for the EOF token after a
+// trailing newline.
+fn report(error: &Error, source: &str) {
+    eprintln!("{}", error);
+    let Some((line, column)) = error.position() else {
+        return;
+    };
+    if let Some(text) = source.lines().nth(line.saturating_sub(1)) {
+        let gutter = " ".repeat(line.to_string().len());
+        // Keep tabs, so the caret lines up however wide they are shown.
+        let padding: String = text.chars()
+            .take(column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        eprintln!("{gutter} |");
+        eprintln!("{line} | {text}");
+        eprintln!("{gutter} | {padding}^");
+    }
+}
@@ -0,0 +1,462 @@
+use crate::environment::Environment;
+use crate::error::Error;
+use crate::function::{CallError, NativeFunction};
+use crate::interpreter::{self, Difference, Interpreter};
+use crate::token::{MapKey, Value};
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// Define the built-in functions in `environment`, which should be the global scope.
+pub fn define_natives(environment: &mut Environment) {
+    let natives = [
+        NativeFunction::new("assert", 1..=2, assert),
+        NativeFunction::new("benchmark", 2..=2, benchmark),
+        NativeFunction::new("chars", 1..=1, chars),
+        NativeFunction::new("clock", 0..=0, clock),
+        NativeFunction::new("concat", 2..=2, concat),
+        NativeFunction::new("eval", 1..=1, eval),
+        NativeFunction::new("expect", 2..=2, expect),
+        NativeFunction::new("expect_error", 1..=2, expect_error),
+        NativeFunction::new("find", 2..=2, find),
+        NativeFunction::new("freeze", 1..=1, freeze),
+        NativeFunction::new("has", 2..=2, has),
+        NativeFunction::new("keys", 1..=1, keys),
+        NativeFunction::new("len", 1..=1, len),
+        NativeFunction::new("lines", 0..=0, lines),
+        NativeFunction::new("max_by", 2..=2, max_by),
+        NativeFunction::new("min_by", 2..=2, min_by),
+        NativeFunction::new("pad", 2..=3, pad),
+        NativeFunction::new("partial", 1..=usize::MAX, partial),
+        NativeFunction::new("pop", 1..=1, pop),
+        NativeFunction::new("push", 2..=2, push),
+        NativeFunction::new("repr", 1..=1, repr),
+        NativeFunction::new("reverse", 1..=1, reverse),
+        NativeFunction::new("round", 2..=2, round),
+        NativeFunction::new("slice", 3..=3, slice),
+        NativeFunction::new("str", 1..=1, str_),
+        NativeFunction::new("substr", 3..=3, substr),
+        NativeFunction::new("version", 0..=0, version),
+    ];
+    for native in natives {
+        environment.define(native.name.to_owned(), Some(&Value::NativeFn(Rc::new(native))));
+    }
+}
+
+// assert(condition[, message]) -> fail, with `message` if given, unless `condition` is truthy. In
+// test mode a failure is counted rather than raised (see `Interpreter::check()`).
+fn assert(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let message = match arguments.get(1) {
+        Some(Value::String_(message)) => Some(message),
+        Some(_) => return Err("Second argument to 'assert' must be a string.".into()),
+        None => None,
+    };
+    let passed = interpreter.is_truthy(&arguments[0]);
+    interpreter.check(passed, || match message {
+        Some(message) => format!("Assertion failed: {}", message),
+        None => "Assertion failed.".to_owned(),
+    })
+}
+
+// benchmark(function, n) -> call `function` with no arguments `n` times, and return how long it
+// took as a map of `total_ms` and `per_call_ms`, in milliseconds. An error in `function` stops the
+// benchmark and is passed on.
+fn benchmark(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    if !is_callable(&arguments[0]) {
+        return Err("First argument to 'benchmark' must be a function.".into());
+    }
+    let n = match arguments[1] {
+        Value::Integer(n) if n >= 0 => n,
+        _ => return Err("Second argument to 'benchmark' must be a non-negative integer.".into()),
+    };
+    let start = Instant::now();
+    for _ in 0..n {
+        interpreter.call_value(&arguments[0], Vec::new())?;
+    }
+    let total_ms = start.elapsed().as_secs_f64() * 1000.0;
+    let per_call_ms = if n == 0 { 0.0 } else { total_ms / n as f64 };
+    let timings = BTreeMap::from([
+        (MapKey::String_("total_ms".to_owned()), Value::Number(total_ms)),
+        (MapKey::String_("per_call_ms".to_owned()), Value::Number(per_call_ms)),
+    ]);
+    Ok(Value::Map(Rc::new(RefCell::new(timings)), false))
+}
+
+// chars(s) -> a new array of the characters of the string `s`, as `Value::Char`s. A character
+// turns back into a string by concatenation, e.g. `"" + c`.
+fn chars(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let Value::String_(s) = &arguments[0] else {
+        return Err("Argument to 'chars' must be a string.".into());
+    };
+    let chars = s.chars().map(Value::Char).collect();
+    Ok(Value::Array(Rc::new(RefCell::new(chars)), false))
+}
+
+// clock() -> the number of seconds since the Unix epoch, as a float, for timing scripts.
+fn clock(_interpreter: &mut Interpreter, _arguments: &[Value]) -> Result<Value, CallError> {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| e.to_string())?;
+    Ok(Value::Number(elapsed.as_secs_f64()))
+}
+
+// concat(a, b) -> a new array of the elements of the array `a` followed by those of the array `b`.
+fn concat(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let (Value::Array(a, _), Value::Array(b, _)) = (&arguments[0], &arguments[1]) else {
+        return Err("Arguments to 'concat' must be arrays.".into());
+    };
+    interpreter.check_size(a.borrow().len() + b.borrow().len())?;
+    let elements = a.borrow().iter().chain(b.borrow().iter()).cloned().collect();
+    Ok(Value::Array(Rc::new(RefCell::new(elements)), false))
+}
+
+// eval(source) -> run the code `source` where `eval` is called, returning the value of its last
+// expression statement, or of `source` itself if it is a bare expression, like `"1 + 2"`.
+// Variables it declares stay defined. Not allowed in sandbox mode. See `Interpreter::eval()`.
+fn eval(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let Value::String_(source) = &arguments[0] else {
+        return Err("Argument to 'eval' must be a string.".into());
+    };
+    interpreter.eval(source)
+}
+
+// expect(actual, expected) -> fail unless `actual == expected`. Like `assert`, a failure is
+// counted rather than raised in test mode. For arrays and maps the message also says where inside
+// them the first difference is, e.g. `at [1], expected 9 but got 2`.
+fn expect(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let (actual, expected) = (&arguments[0], &arguments[1]);
+    let difference = interpreter.difference(actual, expected);
+    interpreter.check(difference.is_none(), || {
+        let mut message = format!("Expected {} but got {}", expected.quoted(), actual.quoted());
+        if let Some(Difference { path, values: Some((actual, expected)) }) = difference {
+            let quoted = |value: Option<Value>| value.map_or("nothing".to_owned(), |value| value.quoted());
+            message += &format!(": at {}, expected {} but got {}", path, quoted(expected), quoted(actual));
+        }
+        message + "."
+    })
+}
+
+// expect_error(function[, substring]) -> call `function` with no arguments, for testing error paths:
+// it fails if the call doesn't, or if `substring` is given and isn't in the message of the error the
+// call failed with. Otherwise the error is caught, and its message returned.
+fn expect_error(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    if !arity(&arguments[0]).is_some_and(|arity| arity.contains(&0)) {
+        return Err("First argument to 'expect_error' must be a function that takes no arguments.".into());
+    }
+    let substring = match arguments.get(1) {
+        Some(Value::String_(s)) => Some(s),
+        Some(_) => return Err("Second argument to 'expect_error' must be a string.".into()),
+        None => None,
+    };
+    let message = match interpreter.call_value(&arguments[0], Vec::new()) {
+        Ok(_) => return Err("Expected an error, but the call succeeded.".into()),
+        Err(CallError::Message(message)) => message,
+        Err(CallError::Error(Error::RuntimeError { message, .. })) => message,
+        Err(error) => return Err(error),
+    };
+    match substring {
+        Some(substring) if !message.contains(substring.as_str()) =>
+            Err(format!("Expected an error containing '{}', but got '{}'.", substring, message).into()),
+        _ => Ok(Value::String_(message)),
+    }
+}
+
+// find(array, predicate) -> the first element of `array` for which the function `predicate`
+// returns a truthy value, or `nil` if there is none. An error in `predicate` stops the search and is
+// passed on.
+fn find(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let Value::Array(array, _) = &arguments[0] else {
+        return Err("First argument to 'find' must be an array.".into());
+    };
+    if !is_callable(&arguments[1]) {
+        return Err("Second argument to 'find' must be a function.".into());
+    }
+    // Go through a copy, since `predicate` could change the array.
+    let elements = array.borrow().clone();
+    for element in elements {
+        let result = interpreter.call_value(&arguments[1], vec![element.clone()])?;
+        if interpreter.is_truthy(&result) {
+            return Ok(element);
+        }
+    }
+    Ok(Value::Nil)
+}
+
+// freeze(value) -> a view of the array or map `value` that can be read but not changed: indexing
+// into it to assign, `push` or `pop` fails. Changes made to `value` itself still show through it,
+// and arrays and maps inside it are not frozen. Any other value is returned unchanged.
+fn freeze(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    match &arguments[0] {
+        Value::Array(array, _) => Ok(Value::Array(Rc::clone(array), true)),
+        Value::Map(map, _) => Ok(Value::Map(Rc::clone(map), true)),
+        value => Ok(value.clone()),
+    }
+}
+
+// has(map, key) -> whether `map` has an entry for `key`.
+fn has(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let Value::Map(map, _) = &arguments[0] else {
+        return Err("First argument to 'has' must be a map.".into());
+    };
+    let key = MapKey::from_value(&arguments[1]).ok_or("Map key must be a string or a number.")?;
+    Ok(Value::Bool(map.borrow().contains_key(&key)))
+}
+
+// keys(map) -> a new array of the keys of `map`.
+fn keys(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let Value::Map(map, _) = &arguments[0] else {
+        return Err("Argument to 'keys' must be a map.".into());
+    };
+    let keys = map.borrow().keys().map(Value::from).collect();
+    Ok(Value::Array(Rc::new(RefCell::new(keys)), false))
+}
+
+// len(s) -> the number of characters (not bytes) in the string `s`, or of elements in the array or
+// map `s`.
+fn len(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    match &arguments[0] {
+        Value::String_(s) => Ok(Value::Integer(s.chars().count() as i64)),
+        Value::Array(array, _) => Ok(Value::Integer(array.borrow().len() as i64)),
+        Value::Map(map, _) => Ok(Value::Integer(map.borrow().len() as i64)),
+        _ => Err("Argument to 'len' must be a string, an array or a map.".into()),
+    }
+}
+
+// lines() -> an array of the lines left in the interpreter's input (stdin, by default), without
+// their line endings. It reads to the end, so a second call gives an empty array.
+fn lines(interpreter: &mut Interpreter, _arguments: &[Value]) -> Result<Value, CallError> {
+    let mut lines = Vec::new();
+    for line in (&mut interpreter.input).lines() {
+        let line = line.map_err(|e| format!("Failed to read input: {}.", e))?;
+        lines.push(Value::String_(line));
+    }
+    Ok(Value::Array(Rc::new(RefCell::new(lines)), false))
+}
+
+// max_by(array, key) -> the element of `array` for which the function `key` returns the largest
+// value, or `nil` if `array` is empty. Keys are compared like with `>`, so they must be all numbers,
+// all strings or all characters. Of several elements with the largest key, the first is returned.
+fn max_by(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    extreme_by(interpreter, arguments, "max_by", Ordering::Greater)
+}
+
+// min_by(array, key) -> like `max_by`, but the element with the smallest key.
+fn min_by(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    extreme_by(interpreter, arguments, "min_by", Ordering::Less)
+}
+
+// pad(s, width[, align]) -> the string `s` padded with spaces to `width` characters, aligned
+// "left" (the default), "right" or "center", with any odd space on the right. A string that is
+// already wider is left as it is, not truncated.
+fn pad(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let Value::String_(s) = &arguments[0] else {
+        return Err("First argument to 'pad' must be a string.".into());
+    };
+    let width = match arguments[1] {
+        Value::Integer(width) if width >= 0 => width as usize,
+        _ => return Err("Second argument to 'pad' must be a non-negative integer.".into()),
+    };
+    interpreter.check_size(width)?;
+    let padding = width.saturating_sub(s.chars().count());
+    let left = match arguments.get(2) {
+        None => 0,
+        Some(Value::String_(align)) if align == "left" => 0,
+        Some(Value::String_(align)) if align == "right" => padding,
+        Some(Value::String_(align)) if align == "center" => padding / 2,
+        Some(_) => return Err("Third argument to 'pad' must be \"left\", \"right\" or \"center\".".into()),
+    };
+    Ok(Value::String_(format!("{}{}{}", " ".repeat(left), s, " ".repeat(padding - left))))
+}
+
+// partial(function, arguments...) -> a function that calls `function` with `arguments` followed
+// by those it is called with, so `partial(add, 1)(2)` is `add(1, 2)`. It takes that many fewer
+// arguments than `function` does.
+fn partial(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let Some(arity) = arity(&arguments[0]) else {
+        return Err("First argument to 'partial' must be a function.".into());
+    };
+    let bound = arguments.len() - 1;
+    if bound > *arity.end() {
+        return Err(format!("Can't bind {} arguments to a function that takes at most {}.", bound, arity.end()).into());
+    }
+    let end = match *arity.end() {
+        usize::MAX => usize::MAX,
+        end => end - bound,
+    };
+    let mut applied = NativeFunction::new("partial", arity.start().saturating_sub(bound)..=end, apply);
+    applied.bound = arguments.to_vec();
+    Ok(Value::NativeFn(Rc::new(applied)))
+}
+
+// The function `partial` returns, with the function and the arguments bound to it first.
+fn apply(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    interpreter.call_value(&arguments[0], arguments[1..].to_vec())
+}
+
+// pop(array) -> remove and return the last element of `array`.
+fn pop(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let Value::Array(array, frozen) = &arguments[0] else {
+        return Err("Argument to 'pop' must be an array.".into());
+    };
+    if *frozen {
+        return Err("Cannot modify a frozen value.".into());
+    }
+    array.borrow_mut().pop().ok_or_else(|| "Can't pop from an empty array.".into())
+}
+
+// push(array, value) -> append `value` to `array`, returning `nil`.
+fn push(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let Value::Array(array, frozen) = &arguments[0] else {
+        return Err("First argument to 'push' must be an array.".into());
+    };
+    if *frozen {
+        return Err("Cannot modify a frozen value.".into());
+    }
+    interpreter.check_size(array.borrow().len() + 1)?;
+    array.borrow_mut().push(arguments[1].clone());
+    Ok(Value::Nil)
+}
+
+// str(value) -> `value` as `print` shows it, e.g. with numbers formatted as the interpreter's
+// `decimal_comma` and `group_thousands` say, and an instance as its `to_string()` method returns.
+// See `Interpreter::display()`.
+fn str_(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    interpreter.display(&arguments[0]).map(Value::String_)
+}
+
+// repr(value) -> `value` as it would be written in code, e.g. with strings quoted and escaped. See
+// `Value::quoted()`.
+fn repr(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    Ok(Value::String_(arguments[0].quoted()))
+}
+
+// reverse(array) -> a new array of the elements of `array` in reverse order.
+fn reverse(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let Value::Array(array, _) = &arguments[0] else {
+        return Err("Argument to 'reverse' must be an array.".into());
+    };
+    let elements = array.borrow().iter().rev().cloned().collect();
+    Ok(Value::Array(Rc::new(RefCell::new(elements)), false))
+}
+
+// round(x, digits) -> the number `x` rounded to `digits` decimal places, with halves rounded away
+// from zero. `digits` can be negative, so `round(1250, -2)` is `1300`. Rounding an integer to
+// zero or more places leaves it an integer; anything else gives a float.
+fn round(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let x = match arguments[0] {
+        Value::Integer(x) => x as f64,
+        Value::Number(x) => x,
+        _ => return Err("First argument to 'round' must be a number.".into()),
+    };
+    let Value::Integer(digits) = arguments[1] else {
+        return Err("Second argument to 'round' must be an integer.".into());
+    };
+    if let (Value::Integer(_), 0..) = (&arguments[0], digits) {
+        return Ok(arguments[0].clone());
+    }
+    // Scaling by a power of ten and dividing back (rather than multiplying by its inverse) gives
+    // the float nearest the rounded decimal, so `round(3.14159, 2)` is exactly `3.14`.
+    let scale = 10f64.powi(digits.unsigned_abs().min(308) as i32);
+    let rounded = match digits >= 0 {
+        true => (x * scale).round() / scale,
+        false => (x / scale).round() * scale,
+    };
+    // A float that big already has no digits after the point to round away.
+    Ok(Value::Number(if rounded.is_finite() { rounded } else { x }))
+}
+
+// slice(array, start, end) -> a new array of the elements of `array` from index `start` up to but
+// not including `end`. As in indexing, a negative index counts back from the end, so
+// `slice(a, -2, len(a))` is the last two elements. Unlike indexing, the indices are then clamped to
+// the array, so an out-of-range slice just comes out shorter (or empty) rather than being an error.
+// `substr` takes its indices the same way.
+fn slice(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let Value::Array(array, _) = &arguments[0] else {
+        return Err("First argument to 'slice' must be an array.".into());
+    };
+    let array = array.borrow();
+    let length = array.len() as i64;
+    let start = bound(arguments, 1, length, "slice")?;
+    let end = bound(arguments, 2, length, "slice")?.max(start);
+    let elements = array[start as usize..end as usize].to_vec();
+    Ok(Value::Array(Rc::new(RefCell::new(elements)), false))
+}
+
+// substr(s, start, end) -> the characters of `s` from index `start` up to but not including `end`.
+// Indices count characters, and are taken like those of `slice`: negative ones count back from the
+// end, and out-of-range ones are clamped to the string.
+fn substr(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, CallError> {
+    let Value::String_(s) = &arguments[0] else {
+        return Err("First argument to 'substr' must be a string.".into());
+    };
+    let length = s.chars().count() as i64;
+    let start = bound(arguments, 1, length, "substr")?;
+    let end = bound(arguments, 2, length, "substr")?.max(start);
+    let slice: String = s.chars().skip(start as usize).take((end - start) as usize).collect();
+    Ok(Value::String_(slice))
+}
+
+// version() -> the interpreter's version, as printed by `--version`, e.g. "0.1.0".
+fn version(_interpreter: &mut Interpreter, _arguments: &[Value]) -> Result<Value, CallError> {
+    Ok(Value::String_(env!("CARGO_PKG_VERSION").to_owned()))
+}
+
+// `max_by` and `min_by`, called `name`: the first element whose key is ordered `wanted` against
+// the keys of all the others.
+fn extreme_by(interpreter: &mut Interpreter, arguments: &[Value], name: &str, wanted: Ordering) -> Result<Value, CallError> {
+    let Value::Array(array, _) = &arguments[0] else {
+        return Err(format!("First argument to '{}' must be an array.", name).into());
+    };
+    if !is_callable(&arguments[1]) {
+        return Err(format!("Second argument to '{}' must be a function.", name).into());
+    }
+    let elements = array.borrow().clone();
+    let mut best: Option<(Value, Value)> = None;
+    for element in elements {
+        let key = interpreter.call_value(&arguments[1], vec![element.clone()])?;
+        let better = match &best {
+            None => true,
+            Some((_, best_key)) => interpreter::compare_values(&key, best_key)
+                .ok_or_else(|| format!("Keys compared by '{}' must be all numbers, all strings or all characters.", name))?
+                == Some(wanted),
+        };
+        if better {
+            best = Some((element, key));
+        }
+    }
+    Ok(best.map_or(Value::Nil, |(element, _)| element))
+}
+
+// Whether `value` can be called, for natives that take a function.
+fn is_callable(value: &Value) -> bool {
+    arity(value).is_some()
+}
+
+// How many arguments `value` can be called with, if it can be called at all.
+fn arity(value: &Value) -> Option<RangeInclusive<usize>> {
+    match value {
+        Value::Function(function) => Some(function.arity()),
+        Value::NativeFn(native) => Some(native.arity.clone()),
+        Value::Class(class) => Some(class.arity()),
+        _ => None,
+    }
+}
+
+// The bound of a range given by argument `i` of the native `name`, for a sequence of `length`
+// elements: it must be a whole number, is counted back from the end if negative, and is clamped to
+// `0..=length`.
+fn bound(arguments: &[Value], i: usize, length: i64, name: &str) -> Result<i64, CallError> {
+    let index = match arguments[i] {
+        Value::Integer(x) => x,
+        Value::Number(x) if x.fract() == 0.0 => x as i64,
+        _ => return Err(format!("{} argument to '{}' must be an integer.", ORDINALS[i], name).into()),
+    };
+    let position = if index < 0 { index.saturating_add(length) } else { index };
+    Ok(position.clamp(0, length))
+}
+
+// For naming arguments in error messages.
+const ORDINALS: [&str; 3] = ["First", "Second", "Third"];
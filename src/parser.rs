@@ -1,11 +1,25 @@
 use crate::token::{Token, TokenType, Literal};
-use crate::expr::Expr;
-use crate::stmt::Stmt;
+use crate::expr::{self, Expr};
+use crate::stmt::{Param, Stmt};
 use crate::error::Error;
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Limit on the number of parameters a function can have (and so arguments a call can pass).
+const MAX_ARGUMENTS: usize = 255;
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,  // point to the *next* token to be parsed
+    function_depth: usize,  // how many function bodies we are inside, to reject a top-level `return`
+    loop_depth: usize,  // how many loops we are inside (in this function), to reject a stray `break`
+    switch_depth: usize,  // likewise for `switch`es, which `break` can also leave
+
+    // Every error found, to be returned once parsing is done. This includes errors that don't
+    // send the parser into panic mode, which is why they aren't simply the `Err`s seen by
+    // `declaration_wrapper()`.
+    errors: RefCell<Vec<Error>>,
 }
 
 
@@ -14,19 +28,41 @@ impl Parser {
         Self {
             tokens,
             current: 0,
+            function_depth: 0,
+            loop_depth: 0,
+            switch_depth: 0,
+            errors: RefCell::new(Vec::new()),
         }
     }
 
     // Interface.
     // program -> declaration* EOF
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, Error> {
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<Error>> {
         let mut statements = Vec::new();
         while !self.is_at_end() {
             if let Some(x) = self.declaration_wrapper() {
                 statements.push(x);
             }
         }
-        Ok(statements)
+        let errors = self.errors.take();
+        match errors.is_empty() {
+            true => Ok(statements),
+            false => Err(errors),
+        }
+    }
+
+    // Interface for the prompt, which echoes the value of a bare expression: parse all of the input
+    // as a single expression, with no `;` after it.
+    pub fn parse_expression(&mut self) -> Result<Expr, Vec<Error>> {
+        let result = self.expression().and_then(|expr| match self.is_at_end() {
+            true => Ok(expr),
+            false => Err(self.error(self.peek(), "Expected end of expression.")),
+        });
+        let errors = self.errors.take();
+        match result {
+            Ok(expr) if errors.is_empty() => Ok(expr),
+            _ => Err(errors),
+        }
     }
 
     // Convert `Result<Stmt, Error>` to `Option<Stmt>`, and call `synchronize()` if something went
@@ -42,15 +78,99 @@ impl Parser {
 
     // Statements.
 
-    // declaration -> var_declaration | statement
+    // declaration -> class_declaration | fun_declaration | var_declaration | statement
+    // A `fun` followed by `(` is a lambda rather than a declaration, and so starts an expression
+    // statement.
     fn declaration(&mut self) -> Result<Stmt, Error> {
-        if self.match_next(&[TokenType::Var]) {
+        if self.match_next(&[TokenType::Class]) {
+            self.class_declaration()
+        } else if self.check(&TokenType::Fun) && !self.check_next(&TokenType::LeftParen) {
+            self.advance();
+            self.function("function")
+        } else if self.match_next(&[TokenType::Var]) {
             self.var_declaration()
         } else {
             self.statement()
         }
     }
 
+    // class_declaration -> "class" identifier ( "<" identifier )? "{" function* "}"
+    fn class_declaration(&mut self) -> Result<Stmt, Error> {
+        let name = self.match_err(&TokenType::Identifier, "Expected class name.")?;
+
+        let mut superclass = None;
+        if self.match_next(&[TokenType::Less]) {
+            let name = self.match_err(&TokenType::Identifier, "Expected superclass name.")?;
+            superclass = Some(Expr::Variable { id: expr::next_id(), name });
+        }
+
+        self.match_err(&TokenType::LeftBrace, "Expected `{` before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+
+        self.match_err(&TokenType::RightBrace, "Expected `}` after class body.")?;
+        Ok(Stmt::Class { name, superclass, methods })
+    }
+
+    // fun_declaration -> "fun" function
+    // function -> identifier "(" function_body
+    // `kind` is used in error messages.
+    fn function(&mut self, kind: &str) -> Result<Stmt, Error> {
+        let name = self.match_err(&TokenType::Identifier, &format!("Expected {} name.", kind))?;
+        self.match_err(&TokenType::LeftParen, &format!("Expected `(` after {} name.", kind))?;
+        let (params, body) = self.function_body(kind)?;
+        Ok(Stmt::Function { name, params: Rc::new(params), body: Rc::new(body) })
+    }
+
+    // The rest of a function declaration or lambda, after the `(`.
+    // function_body -> parameters? ")" block
+    // parameters -> parameter ( "," parameter )*
+    // parameter -> identifier ( "=" expression )? | "..." identifier
+    // Parameters with a default value have to come after those without one, so that the arguments
+    // of a call always go to the leading parameters. A rest parameter, `...` and a name, takes the
+    // arguments left over, so it has to be the last one.
+    fn function_body(&mut self, kind: &str) -> Result<(Vec<Param>, Vec<Stmt>), Error> {
+        let mut params: Vec<Param> = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    self.error(self.peek(), &format!("Can't have more than {} parameters.", MAX_ARGUMENTS));
+                }
+                if params.last().is_some_and(|param| param.rest) {
+                    self.error(self.peek(), "The rest parameter has to be the last one.");
+                }
+                let rest = self.match_next(&[TokenType::DotDotDot]);
+                let name = self.match_err(&TokenType::Identifier, "Expected parameter name.")?;
+                let default = match !rest && self.match_next(&[TokenType::Equal]) {
+                    true => Some(self.expression()?),
+                    false => None,
+                };
+                if default.is_none() && !rest && params.last().is_some_and(|param| param.default.is_some()) {
+                    self.error(&name, "A parameter without a default value can't follow one with a default.");
+                }
+                params.push(Param { name, default, rest });
+                if !self.match_next(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.match_err(&TokenType::RightParen, "Expected `)` after parameters.")?;
+
+        self.match_err(&TokenType::LeftBrace, &format!("Expected `{{` before {} body.", kind))?;
+        // A loop around the declaration doesn't count inside the body: `break` can't leave a function.
+        let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let enclosing_switch_depth = std::mem::replace(&mut self.switch_depth, 0);
+        self.function_depth += 1;
+        let body = self.block();
+        self.function_depth -= 1;
+        self.loop_depth = enclosing_loop_depth;
+        self.switch_depth = enclosing_switch_depth;
+        Ok((params, body?))
+    }
+
     // var_declaration -> "var" identifier ( "=" expression )? ";"
     fn var_declaration(&mut self) -> Result<Stmt, Error> {
         let name = self.match_err(&TokenType::Identifier, "Expected variable name.")?;
@@ -60,41 +180,100 @@ impl Parser {
             false => None,
         };
 
-        self.match_err(&TokenType::Semicolon, "Expected ';' after variable declaration.")?;
+        self.match_semicolon("Expected ';' after variable declaration.")?;
         Ok(Stmt::Var { name, initializer })
     }
 
-    // statement -> for_statement
+    // statement -> break_statement
+    //              | continue_statement
+    //              | debug_assert_statement
+    //              | for_statement
     //              | if_statement
     //              | print_statement
+    //              | return_statement
+    //              | switch_statement
     //              | while_statement
+    //              | yield_statement
     //              | block
+    //              | ";"
     //              | expression_statement
     fn statement(&mut self) -> Result<Stmt, Error> {
-        if self.match_next(&[TokenType::For]) {
+        if self.match_next(&[TokenType::Break, TokenType::Continue]) {
+            self.loop_control_statement()
+
+        } else if self.match_next(&[TokenType::DebugAssert]) {
+            self.debug_assert_statement()
+
+        } else if self.match_next(&[TokenType::For]) {
             self.for_statement()
 
         } else if self.match_next(&[TokenType::If]) {
             self.if_statement()
 
+        } else if self.match_next(&[TokenType::Import]) {
+            self.import_statement()
+
         } else if self.match_next(&[TokenType::Print]) {
             self.print_statement()
 
+        } else if self.match_next(&[TokenType::Return]) {
+            self.return_statement()
+
+        } else if self.match_next(&[TokenType::Switch]) {
+            self.switch_statement()
+
         } else if self.match_next(&[TokenType::While]) {
             self.while_statement()
 
+        } else if self.match_next(&[TokenType::Yield]) {
+            self.yield_statement()
+
         } else if self.match_next(&[TokenType::LeftBrace]) {
             Ok(Stmt::Block { statements: self.block()? })
 
+        } else if self.match_next(&[TokenType::Semicolon]) {
+            Ok(Stmt::Empty)
+
         } else {
             self.expression_statement()
         }
     }
 
+    // break_statement -> "break" ";"
+    // continue_statement -> "continue" ";"
+    // `break` leaves the innermost loop or `switch`; `continue` only applies to loops.
+    fn loop_control_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous().to_owned();
+        if keyword.type_ == TokenType::Break && self.loop_depth == 0 && self.switch_depth == 0 {
+            return Err(self.error(&keyword, "Can't use 'break' outside of a loop or switch."));
+        }
+        if keyword.type_ == TokenType::Continue && self.loop_depth == 0 {
+            return Err(self.error(&keyword, "Can't use 'continue' outside of a loop."));
+        }
+
+        self.match_semicolon(&format!("Expected `;` after `{}`.", keyword.lexeme))?;
+        if keyword.type_ == TokenType::Break {
+            Ok(Stmt::Break { keyword })
+        } else {
+            Ok(Stmt::Continue { keyword })
+        }
+    }
+
+    // debug_assert_statement -> "debug_assert" "(" expression ")" ";"
+    fn debug_assert_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous().to_owned();
+        self.match_err(&TokenType::LeftParen, "Expected `(` after `debug_assert`.")?;
+        let condition = self.expression()?;
+        self.match_err(&TokenType::RightParen, "Expected ')' after condition.")?;
+        self.match_semicolon("Expected `;` after `debug_assert`.")?;
+        Ok(Stmt::DebugAssert { keyword, condition })
+    }
+
     // `Desugar` the `for` statement into a `while` loop.
     // for_statement -> "for" "(" ( var_declaration | expression_statement | ";" ) expression? ";"
     // expression? ";" ")" statement
     fn for_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous().to_owned();
         self.match_err(&TokenType::LeftParen, "Expect `(` after `for`.")?;
 
         let initializer: Option<Stmt>;
@@ -129,17 +308,11 @@ impl Parser {
         //          `increment`
         //      }
         //  }
+        // The increment is executed after every iteration, including ones cut short by `continue`,
+        // so it is given to the `while` separately rather than appended to the body.
 
-        let mut body = self.statement()?;
-        if let Some(inc) = increment {
-            // As the increment will be executed after every iteration, we add it to the body of
-            // the loop.
-            body = Stmt::Block {
-                statements: vec![body, Stmt::Expression { expression: inc }]
-            };
-        }
-
-        body = Stmt::While { condition, body: Box::new(body) };
+        let body = self.loop_body()?;
+        let mut body = Stmt::While { keyword, condition, body: Box::new(body), increment };
 
         if let Some(init) = initializer {
             body = Stmt::Block {
@@ -151,6 +324,7 @@ impl Parser {
     }
 
     fn if_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous().to_owned();
         self.match_err(&TokenType::LeftParen, "Expected `(` after `if`.")?;
         let condition = self.expression()?;
         self.match_err(&TokenType::RightParen, "Expected ')' after condition.")?;
@@ -166,28 +340,132 @@ impl Parser {
             false => None,
         };
 
-        Ok(Stmt::If { condition,
+        Ok(Stmt::If { keyword, condition,
             then_branch: Box::new(then_branch),
             else_branch: else_branch.map(Box::new)
         })
     }
 
+    // import_statement -> "import" string ( "as" IDENTIFIER )? ";"
+    // `as` is only special here, so it can still be used as a name anywhere else.
+    fn import_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous().to_owned();
+        let path = self.match_err(&TokenType::String_, "Expected file path string after `import`.")?;
+        let alias = match self.check(&TokenType::Identifier) && self.peek().lexeme == "as" {
+            true => {
+                self.advance();
+                Some(self.match_err(&TokenType::Identifier, "Expected namespace name after `as`.")?)
+            },
+            false => None,
+        };
+        self.match_semicolon("Expected `;` after import path.")?;
+        Ok(Stmt::Import { keyword, path: path.literal.to_string(), alias })
+    }
+
     // print_statement -> "print" expression ";"
     fn print_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous().to_owned();
         let value = self.expression()?;
-        self.match_err(&TokenType::Semicolon, "Expected `;` after value.")?;
-        Ok(Stmt::Print { expression: value })
+        self.match_semicolon("Expected `;` after value.")?;
+        Ok(Stmt::Print { keyword, expression: value })
+    }
+
+    // return_statement -> "return" expression? ";"
+    fn return_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous().to_owned();
+        if self.function_depth == 0 {
+            return Err(self.error(&keyword, "Can't return from top-level code."));
+        }
+
+        let value = match self.check(&TokenType::Semicolon) {
+            true => None,
+            false => Some(self.expression()?),
+        };
+        self.match_semicolon("Expected `;` after return value.")?;
+        Ok(Stmt::Return { keyword, value })
     }
 
     // while_statement -> "while" "(" expression ")" statement
     fn while_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous().to_owned();
         self.match_err(&TokenType::LeftParen, "Expected `(` after `while`.")?;
         let condition = self.expression()?;
         self.match_err(&TokenType::RightParen, "Expected ')' after condition.")?;
 
-        let body = self.statement()?;
+        let body = self.loop_body()?;
 
-        Ok(Stmt::While { condition, body: Box::new(body) })
+        Ok(Stmt::While { keyword, condition, body: Box::new(body), increment: None })
+    }
+
+    // yield_statement -> "yield" expression ";"
+    fn yield_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous().to_owned();
+        if self.function_depth == 0 {
+            return Err(self.error(&keyword, "Can't yield from top-level code."));
+        }
+
+        let value = self.expression()?;
+        self.match_semicolon("Expected `;` after yield value.")?;
+        Ok(Stmt::Yield { keyword, value })
+    }
+
+    // switch_statement -> "switch" "(" expression ")" "{" switch_case* ( "default" ":" declaration* )? "}"
+    // switch_case -> "case" expression ":" declaration*
+    // Each case's statements run up to the next `case`/`default`; the `default` must come last.
+    fn switch_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous().to_owned();
+        self.match_err(&TokenType::LeftParen, "Expected `(` after `switch`.")?;
+        let discriminant = self.expression()?;
+        self.match_err(&TokenType::RightParen, "Expected `)` after switch value.")?;
+        self.match_err(&TokenType::LeftBrace, "Expected `{` before switch body.")?;
+
+        self.switch_depth += 1;
+        let body = self.switch_body();
+        self.switch_depth -= 1;
+        let (cases, default) = body?;
+
+        self.match_err(&TokenType::RightBrace, "Expected `}` after switch body.")?;
+        Ok(Stmt::Switch { keyword, discriminant, cases, default })
+    }
+
+    // The cases of a `switch`, up to its closing `}`.
+    #[allow(clippy::type_complexity)]
+    fn switch_body(&mut self) -> Result<(Vec<(Expr, Vec<Stmt>)>, Option<Vec<Stmt>>), Error> {
+        let mut cases = Vec::new();
+        while self.match_next(&[TokenType::Case]) {
+            let value = self.expression()?;
+            self.match_err(&TokenType::Colon, "Expected `:` after case value.")?;
+            cases.push((value, self.case_body()?));
+        }
+
+        let mut default = None;
+        if self.match_next(&[TokenType::Default]) {
+            self.match_err(&TokenType::Colon, "Expected `:` after `default`.")?;
+            default = Some(self.case_body()?);
+        }
+
+        if !self.check(&TokenType::RightBrace) {
+            return Err(self.error(self.peek(), "Expected `case`, or `}` after `default` case."));
+        }
+        Ok((cases, default))
+    }
+
+    // The statements of one case.
+    fn case_body(&mut self) -> Result<Vec<Stmt>, Error> {
+        let mut statements = Vec::new();
+        while !self.check(&TokenType::Case) && !self.check(&TokenType::Default)
+            && !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            statements.push(self.declaration()?);
+        }
+        Ok(statements)
+    }
+
+    // Parse the body of a loop, in which `break` and `continue` are allowed.
+    fn loop_body(&mut self) -> Result<Stmt, Error> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
     }
 
     // block -> "{" declaration* "}"
@@ -206,7 +484,7 @@ impl Parser {
     // These are for expressions with side effects such as function calls.
     fn expression_statement(&mut self) -> Result<Stmt, Error> {
         let expr = self.expression()?;
-        self.match_err(&TokenType::Semicolon, "Expected `;` after expression.")?;
+        self.match_semicolon("Expected `;` after expression.")?;
         Ok(Stmt::Expression { expression: expr })
     }
 
@@ -218,10 +496,11 @@ impl Parser {
         self.assignment()
     }
 
-    // assignment -> (identifier "=" assignment) | logic_or
+    // assignment -> ( ( call "." )? identifier "=" assignment ) | ( call "[" expression "]" "=" assignment )
+    //               | ternary
     fn assignment(&mut self) -> Result<Expr, Error> {
         // We let `self.equality()` collect the identifier.
-        let expr = self.logic_or()?;
+        let expr = self.ternary()?;
 
         if self.match_next(&[TokenType::Equal]) {
             let equals = self.previous().to_owned();
@@ -230,8 +509,12 @@ impl Parser {
             // Test if what is collected can be used as a variable.
             // Doing it this way allows identifiers like `Point(x+2, 0.0).y` since it itself is an
             // expression.
-            if let Expr::Variable { name } = expr {
-                return Ok(Expr::Assign { name, value: Box::new(value) });
+            if let Expr::Variable { name, .. } = expr {
+                return Ok(Expr::Assign { id: expr::next_id(), name, value: Box::new(value) });
+            } else if let Expr::Get { object, name } = expr {
+                return Ok(Expr::Set { object, name, value: Box::new(value) });
+            } else if let Expr::Index { object, bracket, index } = expr {
+                return Ok(Expr::SetIndex { object, bracket, index, value: Box::new(value) });
             } else {
                 // Note we don't bubble up error because we don't need to go into panic mode and
                 // synchronize. We accept their mistake by reporting the error and move on.
@@ -242,11 +525,33 @@ impl Parser {
         Ok(expr)
     }
 
-    // logic_or -> logic_and ("or" logic_and)*
+    // The `else` branch recurses into `ternary` itself, making it right-associative:
+    // `a ? b : c ? d : e` is `a ? b : (c ? d : e)`.
+    // ternary -> logic_or ( "?" expression ":" ternary )?
+    fn ternary(&mut self) -> Result<Expr, Error> {
+        let expr = self.logic_or()?;
+
+        if self.match_next(&[TokenType::Question]) {
+            let question = self.previous().to_owned();
+            let then_expr = self.expression()?;
+            self.match_err(&TokenType::Colon, "Expected `:` after then branch of conditional expression.")?;
+            let else_expr = self.ternary()?;
+            return Ok(Expr::Ternary {
+                condition: Box::new(expr),
+                question,
+                then_expr: Box::new(then_expr),
+                else_expr: Box::new(else_expr),
+            });
+        }
+
+        Ok(expr)
+    }
+
+    // logic_or -> logic_and ( ( "or" | "xor" ) logic_and )*
     fn logic_or(&mut self) -> Result<Expr, Error> {
         let mut expr = self.logic_and()?;
 
-        while self.match_next(&[TokenType::Or]) {
+        while self.match_next(&[TokenType::Or, TokenType::Xor]) {
             let operator = self.previous().to_owned();
             let right = self.logic_and()?;
             expr = Expr::Logical {
@@ -276,13 +581,13 @@ impl Parser {
         Ok(expr)
     }
 
-    // equality -> comparison ( ( "!=" | "==" ) comparison )*
+    // equality -> bit_or ( ( "!=" | "==" ) bit_or )*
     fn equality(&mut self) -> Result<Expr, Error> {
-        let mut expr = self.comparison()?;
+        let mut expr = self.bit_or()?;
 
         while self.match_next(&[TokenType::BangEqual, TokenType::EqualEqual]) {
             let operator = self.previous().to_owned();
-            let right = self.comparison()?;
+            let right = self.bit_or()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
                 operator,
@@ -293,11 +598,81 @@ impl Parser {
         Ok(expr)
     }
 
-    // comparison -> term ( ( ">" | ">=" | "<" | "<=" ) term )*
+    // The bitwise operators bind looser than comparison but tighter than equality, so
+    // `x & 1 == 1` is `(x & 1) == 1` rather than C's `x & (1 == 1)`.
+    // bit_or -> bit_xor ( "|" bit_xor )*
+    fn bit_or(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.bit_xor()?;
+
+        while self.match_next(&[TokenType::Pipe]) {
+            let operator = self.previous().to_owned();
+            let right = self.bit_xor()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            }
+        }
+
+        Ok(expr)
+    }
+
+    // bit_xor -> bit_and ( "^" bit_and )*
+    fn bit_xor(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.bit_and()?;
+
+        while self.match_next(&[TokenType::Caret]) {
+            let operator = self.previous().to_owned();
+            let right = self.bit_and()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            }
+        }
+
+        Ok(expr)
+    }
+
+    // bit_and -> comparison ( "&" comparison )*
+    fn bit_and(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.comparison()?;
+
+        while self.match_next(&[TokenType::Ampersand]) {
+            let operator = self.previous().to_owned();
+            let right = self.comparison()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            }
+        }
+
+        Ok(expr)
+    }
+
+    // comparison -> shift ( ( ">" | ">=" | "<" | "<=" | "is" ) shift )*
     fn comparison(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.shift()?;
+
+        while self.match_next(&[TokenType::Greater, TokenType::GreaterEqual, TokenType::Less, TokenType::LessEqual, TokenType::Is]) {
+            let operator = self.previous().to_owned();
+            let right = self.shift()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            }
+        }
+
+        Ok(expr)
+    }
+
+    // shift -> term ( ( "<<" | ">>" ) term )*
+    fn shift(&mut self) -> Result<Expr, Error> {
         let mut expr = self.term()?;
 
-        while self.match_next(&[TokenType::Greater, TokenType::GreaterEqual, TokenType::Less, TokenType::LessEqual]) {
+        while self.match_next(&[TokenType::LessLess, TokenType::GreaterGreater]) {
             let operator = self.previous().to_owned();
             let right = self.term()?;
             expr = Expr::Binary {
@@ -327,13 +702,13 @@ impl Parser {
         Ok(expr)
     }
 
-    // factor -> unary ( ( "/" | "*" ) unary )*
+    // factor -> exponent ( ( "/" | "*" | "%" ) exponent )*
     fn factor(&mut self) -> Result<Expr, Error> {
-        let mut expr = self.unary()?;
+        let mut expr = self.exponent()?;
 
-        while self.match_next(&[TokenType::Slash, TokenType::Star]) {
+        while self.match_next(&[TokenType::Slash, TokenType::Star, TokenType::Percent]) {
             let operator = self.previous().to_owned();
-            let right = self.unary()?;
+            let right = self.exponent()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
                 operator,
@@ -344,21 +719,142 @@ impl Parser {
         Ok(expr)
     }
 
-    // unary -> ( ( "!" | "-" ) unary ) | primary
+    // `**` is right-associative, so the right operand recurses into `exponent` itself rather than
+    // looping like the other binary operators: `2 ** 3 ** 2` is `2 ** (3 ** 2)`.
+    // It sits below `unary`, so a unary minus binds tighter: `-2 ** 2` is `(-2) ** 2`.
+    // exponent -> unary ( "**" exponent )?
+    fn exponent(&mut self) -> Result<Expr, Error> {
+        let expr = self.unary()?;
+
+        if self.match_next(&[TokenType::StarStar]) {
+            let operator = self.previous().to_owned();
+            let right = self.exponent()?;
+            return Ok(Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
+        }
+
+        Ok(expr)
+    }
+
+    // unary -> ( ( "!" | "-" | "~" ) unary ) | ( ( "++" | "--" ) unary ) | postfix
     fn unary(&mut self) -> Result<Expr, Error> {
-        if self.match_next(&[TokenType::Bang, TokenType::Minus]) {
+        if self.match_next(&[TokenType::Bang, TokenType::Minus, TokenType::Tilde]) {
             let operator = self.previous().to_owned();
             let right = self.unary()?;
             Ok(Expr::Unary {
                 operator,
                 right: Box::new(right),
             })
+        } else if self.match_next(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous().to_owned();
+            let target = self.unary()?;
+            Ok(self.increment(target, operator, true))
+        } else {
+            self.postfix()
+        }
+    }
+
+    // postfix -> call ( "++" | "--" )?
+    fn postfix(&mut self) -> Result<Expr, Error> {
+        let expr = self.call()?;
+
+        if self.match_next(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous().to_owned();
+            return Ok(self.increment(expr, operator, false));
+        }
+
+        Ok(expr)
+    }
+
+    // Build an increment or decrement of `target`, which must be a variable. Like an invalid
+    // assignment target, anything else is reported without going into panic mode.
+    fn increment(&mut self, target: Expr, operator: Token, prefix: bool) -> Expr {
+        if let Expr::Variable { name, .. } = target {
+            Expr::Increment { id: expr::next_id(), name, operator, prefix }
         } else {
-            self.primary()
+            self.error(&operator, "Invalid increment target.");
+            target
         }
     }
 
-    // primary -> literal | "(" expression ")"
+    // A call is a primary expression followed by any number of argument lists, property accesses
+    // and indexes, so `f(1)(2)`, `a.b(1).c` and `grid[1][2]` work.
+    // call -> primary ( "(" arguments? ")" | "." identifier | "[" expression "]" )*
+    // arguments -> expression ( "," expression )*
+    fn call(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_next(&[TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_next(&[TokenType::Dot]) {
+                let name = self.match_err(&TokenType::Identifier, "Expected property name after `.`.")?;
+                expr = Expr::Get { object: Box::new(expr), name };
+            } else if self.match_next(&[TokenType::LeftBracket]) {
+                let index = self.expression()?;
+                let bracket = self.match_err(&TokenType::RightBracket, "Expected `]` after index.")?;
+                expr = Expr::Index { object: Box::new(expr), bracket, index: Box::new(index) };
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    // Parse the arguments of a call to `callee`. The `(` has been consumed.
+    // arguments -> argument ( "," argument )*
+    // argument -> identifier ":" expression | element
+    // Keyword arguments, `name: value`, come after the positional ones.
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, Error> {
+        let mut arguments = Vec::new();
+        let mut keywords = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if arguments.len() + keywords.len() >= MAX_ARGUMENTS {
+                    // Report, but don't go into panic mode; the parser is still in a sane state.
+                    self.error(self.peek(), &format!("Can't have more than {} arguments.", MAX_ARGUMENTS));
+                }
+                if self.check(&TokenType::Identifier) && self.check_next(&TokenType::Colon) {
+                    let name = self.advance().to_owned();
+                    self.advance();
+                    keywords.push((name, self.expression()?));
+                } else {
+                    let start = self.peek().to_owned();
+                    arguments.push(self.element()?);
+                    if !keywords.is_empty() {
+                        self.error(&start, "Positional arguments can't follow keyword arguments.");
+                    }
+                }
+                if !self.match_next(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.match_err(&TokenType::RightParen, "Expected `)` after arguments.")?;
+        Ok(Expr::Call { callee: Box::new(callee), paren, arguments, keywords })
+    }
+
+    // A positional argument or an array element, which `...` spreads if it is an array.
+    // element -> "..."? expression
+    fn element(&mut self) -> Result<Expr, Error> {
+        if self.match_next(&[TokenType::DotDotDot]) {
+            let ellipsis = self.previous().to_owned();
+            let expression = self.expression()?;
+            return Ok(Expr::Spread { ellipsis, expression: Box::new(expression) });
+        }
+        self.expression()
+    }
+
+    // primary -> literal | "(" expression ")" | identifier | "this" | "super" "." identifier
+    //            | "fun" "(" function_body | "[" ( element ( "," element )* )? "]"
+    //            | "{" ( expression ":" expression ( "," expression ":" expression )* )? "}"
+    // A `{` can only start a map here, in an expression; at the start of a statement it starts a
+    // block instead.
     fn primary(&mut self) -> Result<Expr, Error> {
         if self.match_next(&[TokenType::False]) {
             Ok(Expr::Literal { value: Literal::Bool(false) })
@@ -378,7 +874,50 @@ impl Parser {
             Ok(Expr::Grouping { expression: Box::new(expr) })
 
         } else if self.match_next(&[TokenType::Identifier]) {
-            Ok(Expr::Variable { name: self.previous().to_owned() })
+            Ok(Expr::Variable { id: expr::next_id(), name: self.previous().to_owned() })
+
+        } else if self.match_next(&[TokenType::LeftBracket]) {
+            let mut elements = Vec::new();
+            if !self.check(&TokenType::RightBracket) {
+                loop {
+                    elements.push(self.element()?);
+                    if !self.match_next(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+            self.match_err(&TokenType::RightBracket, "Expected `]` after array elements.")?;
+            Ok(Expr::Array { elements })
+
+        } else if self.match_next(&[TokenType::LeftBrace]) {
+            let mut entries = Vec::new();
+            if !self.check(&TokenType::RightBrace) {
+                loop {
+                    let key = self.expression()?;
+                    self.match_err(&TokenType::Colon, "Expected `:` after map key.")?;
+                    let value = self.expression()?;
+                    entries.push((key, value));
+                    if !self.match_next(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+            let brace = self.match_err(&TokenType::RightBrace, "Expected `}` after map entries.")?;
+            Ok(Expr::Map { brace, entries })
+
+        } else if self.match_next(&[TokenType::Super]) {
+            let keyword = self.previous().to_owned();
+            self.match_err(&TokenType::Dot, "Expected `.` after `super`.")?;
+            let method = self.match_err(&TokenType::Identifier, "Expected superclass method name.")?;
+            Ok(Expr::Super { id: expr::next_id(), keyword, method })
+
+        } else if self.match_next(&[TokenType::This]) {
+            Ok(Expr::This { id: expr::next_id(), keyword: self.previous().to_owned() })
+
+        } else if self.match_next(&[TokenType::Fun]) {
+            self.match_err(&TokenType::LeftParen, "Expected `(` after `fun`.")?;
+            let (params, body) = self.function_body("lambda")?;
+            Ok(Expr::Lambda { params: Rc::new(params), body: Rc::new(body) })
 
         } else {
             Err(self.error(self.peek(), "Expected expression."))
@@ -405,6 +944,44 @@ impl Parser {
         }
     }
 
+    // Like `match_err` for the `;` ending a statement, but if the next token clearly starts a new
+    // statement (a statement keyword, or an identifier on a later line), report the missing `;`
+    // and carry on as if it were there. Bubbling up the error instead would make `synchronize()`
+    // throw away the following, perfectly valid, statement.
+    fn match_semicolon(&mut self, message: &str) -> Result<(), Error> {
+        if self.match_next(&[TokenType::Semicolon]) {
+            return Ok(());
+        }
+
+        let next = self.peek();
+        let on_new_line = next.line > self.previous().line;
+        if self.at_statement_start() || (next.type_ == TokenType::Identifier && on_new_line) {
+            self.error(next, message);
+            Ok(())
+        } else {
+            Err(self.error(next, message))
+        }
+    }
+
+    // Return whether the next token is a keyword that begins a statement.
+    fn at_statement_start(&self) -> bool {
+        matches!(self.peek().type_,
+            TokenType::Break |
+            TokenType::Class |
+            TokenType::Continue |
+            TokenType::DebugAssert |
+            TokenType::Fun |
+            TokenType::Var |
+            TokenType::For |
+            TokenType::If |
+            TokenType::Import |
+            TokenType::While |
+            TokenType::Print |
+            TokenType::Return |
+            TokenType::Switch |
+            TokenType::Yield)
+    }
+
     // Return whether next token is `token_type`.
     fn check(&self, token_type: &TokenType) -> bool {
         if self.is_at_end() {
@@ -414,6 +991,14 @@ impl Parser {
         }
     }
 
+    // Like `check()`, but for the token after the next one.
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => token.type_ == *token_type,
+            None => false,
+        }
+    }
+
     // Return current token.
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() { self.current += 1; }
@@ -421,7 +1006,7 @@ impl Parser {
     }
 
     fn is_at_end(&self) -> bool {
-        return self.peek().type_ == TokenType::Eof
+        self.peek().type_ == TokenType::Eof
     }
 
     fn peek(&self) -> &Token {
@@ -433,11 +1018,12 @@ impl Parser {
             .expect("Could not get previous token: at beginning of file")
     }
 
-    // Report error to main function to be printed.
+    // Record the error to be returned from `parse()`.
     // Also, return `Error::ParseError` variant to be bubbled up.
     fn error(&self, token: &Token, message: &str) -> Error {
-        crate::error_token(token, message);
-        Error::ParseError
+        let error = Error::parse(token, message);
+        self.errors.borrow_mut().push(error.clone());
+        error
     }
 
     fn synchronize(&mut self) {
@@ -448,17 +1034,8 @@ impl Parser {
                 return;
             }
 
-            match self.peek().type_ {
-                TokenType::Class |
-                TokenType::Fun |
-                TokenType::Var |
-                TokenType::For |
-                TokenType::If |
-                TokenType::While |
-                TokenType::Print |
-                TokenType::Return => { return; },
-
-                _ => (),
+            if self.at_statement_start() {
+                return;
             }
 
             self.advance();
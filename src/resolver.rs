@@ -0,0 +1,422 @@
+use crate::expr::{Expr, ExprVisitor};
+use crate::stmt::{self, Param, Stmt, StmtVisitor};
+use crate::interpreter::Interpreter;
+use crate::token::{Literal, Token};
+use crate::error::Error;
+
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+
+// A static pass run between parsing and interpreting. It walks the AST once, keeping track of the
+// block scopes that would exist at runtime, and tells the interpreter how many scopes up each local
+// variable lives. Variables not found in any block scope are assumed to be global.
+// This makes a variable always refer to the declaration in scope where it is *written*, even if a
+// closure is called after a shadowing declaration has been added to an enclosing block.
+pub struct Resolver<'a> {
+    interpreter: &'a mut Interpreter,
+
+    // Innermost scope last. The value is whether the variable's initializer has been resolved.
+    // The global scope is not tracked.
+    scopes: Vec<HashMap<String, bool>>,
+
+    // What kind of function and class body we are in, if any, to check `return` and `this`.
+    current_function: FunctionType,
+    current_class: ClassType,
+    // Whether that function is a generator, which can't return a value: its result is what it
+    // yields.
+    in_generator: bool,
+
+    // Errors are collected as they are found and resolution carries on, so that all of them are
+    // returned at once.
+    errors: Vec<Error>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+impl ExprVisitor<(), Error> for Resolver<'_> {
+    fn visit_array_expr(&mut self, elements: &[Expr]) -> Result<(), Error> {
+        elements.iter().try_for_each(|element| self.resolve_expr(element))
+    }
+    fn visit_assign_expr(&mut self, id: usize, name: &Token, value: &Expr) -> Result<(), Error> {
+        self.resolve_expr(value)?;
+        self.resolve_local(id, name);
+        Ok(())
+    }
+    fn visit_binary_expr(&mut self, left: &Expr, _operator: &Token, right: &Expr) -> Result<(), Error> {
+        self.resolve_expr(left)?;
+        self.resolve_expr(right)
+    }
+    fn visit_call_expr(&mut self, callee: &Expr, _paren: &Token, arguments: &[Expr], keywords: &[(Token, Expr)]) -> Result<(), Error> {
+        self.resolve_expr(callee)?;
+        arguments.iter().try_for_each(|argument| self.resolve_expr(argument))?;
+        keywords.iter().try_for_each(|(_, argument)| self.resolve_expr(argument))
+    }
+    // Properties are looked up dynamically, so only the object needs resolving.
+    fn visit_get_expr(&mut self, object: &Expr, _name: &Token) -> Result<(), Error> {
+        self.resolve_expr(object)
+    }
+    fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<(), Error> {
+        self.resolve_expr(expression)
+    }
+    fn visit_increment_expr(&mut self, id: usize, name: &Token, _operator: &Token, _prefix: bool) -> Result<(), Error> {
+        self.resolve_local(id, name);
+        Ok(())
+    }
+    fn visit_index_expr(&mut self, object: &Expr, _bracket: &Token, index: &Expr) -> Result<(), Error> {
+        self.resolve_expr(object)?;
+        self.resolve_expr(index)
+    }
+    fn visit_lambda_expr(&mut self, params: &Rc<Vec<Param>>, body: &Rc<Vec<Stmt>>) -> Result<(), Error> {
+        self.resolve_function(params, body, FunctionType::Function)
+    }
+    fn visit_literal_expr(&mut self, _value: &Literal) -> Result<(), Error> {
+        Ok(())
+    }
+    fn visit_map_expr(&mut self, _brace: &Token, entries: &[(Expr, Expr)]) -> Result<(), Error> {
+        for (key, value) in entries {
+            self.resolve_expr(key)?;
+            self.resolve_expr(value)?;
+        }
+        Ok(())
+    }
+    fn visit_logical_expr(&mut self, left: &Expr, _operator: &Token, right: &Expr) -> Result<(), Error> {
+        self.resolve_expr(left)?;
+        self.resolve_expr(right)
+    }
+    fn visit_set_expr(&mut self, object: &Expr, _name: &Token, value: &Expr) -> Result<(), Error> {
+        self.resolve_expr(value)?;
+        self.resolve_expr(object)
+    }
+    fn visit_set_index_expr(&mut self, object: &Expr, _bracket: &Token, index: &Expr, value: &Expr) -> Result<(), Error> {
+        self.resolve_expr(object)?;
+        self.resolve_expr(index)?;
+        self.resolve_expr(value)
+    }
+    fn visit_spread_expr(&mut self, _ellipsis: &Token, expression: &Expr) -> Result<(), Error> {
+        self.resolve_expr(expression)
+    }
+    fn visit_super_expr(&mut self, id: usize, keyword: &Token, _method: &Token) -> Result<(), Error> {
+        match self.current_class {
+            ClassType::None => self.error(keyword, "Can't use 'super' outside of a class."),
+            ClassType::Class => self.error(keyword, "Can't use 'super' in a class with no superclass."),
+            ClassType::Subclass => (),
+        }
+        self.resolve_local(id, keyword);
+        Ok(())
+    }
+    fn visit_ternary_expr(&mut self, condition: &Expr, _question: &Token, then_expr: &Expr, else_expr: &Expr) -> Result<(), Error> {
+        self.resolve_expr(condition)?;
+        self.resolve_expr(then_expr)?;
+        self.resolve_expr(else_expr)
+    }
+    // `this` resolves to the scope wrapping the methods (see `visit_class_stmt`).
+    fn visit_this_expr(&mut self, id: usize, keyword: &Token) -> Result<(), Error> {
+        if self.current_class == ClassType::None {
+            self.error(keyword, "Can't use 'this' outside of a class.");
+        }
+        self.resolve_local(id, keyword);
+        Ok(())
+    }
+    fn visit_unary_expr(&mut self, _operator: &Token, right: &Expr) -> Result<(), Error> {
+        self.resolve_expr(right)
+    }
+    fn visit_variable_expr(&mut self, id: usize, name: &Token) -> Result<(), Error> {
+        // Declared in the innermost scope, but its initializer is still being resolved.
+        if self.scopes.last().and_then(|scope| scope.get(&name.lexeme)) == Some(&false) {
+            self.error(name, "Can't read local variable in its own initializer.");
+        }
+        self.resolve_local(id, name);
+        Ok(())
+    }
+}
+
+impl StmtVisitor<(), Error> for Resolver<'_> {
+    fn visit_block_stmt(&mut self, statements: &[Stmt]) -> Result<(), Error> {
+        self.begin_scope();
+        let result = self.resolve_stmts(statements);
+        self.end_scope();
+        result
+    }
+    fn visit_break_stmt(&mut self, _keyword: &Token) -> Result<(), Error> {
+        Ok(())
+    }
+    // The methods are resolved in a scope of their own defining `this`, matching the scope
+    // `Function::bind` creates at runtime. For a subclass there is another scope around that one,
+    // defining `super`.
+    fn visit_class_stmt(&mut self, name: &Token, superclass: Option<&Expr>, methods: &[Stmt]) -> Result<(), Error> {
+        let enclosing_class = std::mem::replace(&mut self.current_class, ClassType::Class);
+        self.declare(name);
+        self.define(name);
+
+        if let Some(superclass) = superclass {
+            if let Expr::Variable { name: superclass_name, .. } = superclass {
+                if superclass_name.lexeme == name.lexeme {
+                    self.error(superclass_name, "A class can't inherit from itself.");
+                }
+            }
+            self.current_class = ClassType::Subclass;
+            self.resolve_expr(superclass)?;
+            self.begin_scope();
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.insert("super".to_owned(), true);
+            }
+        }
+
+        self.begin_scope();
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert("this".to_owned(), true);
+        }
+        let mut result = Ok(());
+        for method in methods {
+            if let Stmt::Function { name, params, body } = method {
+                let kind = if name.lexeme == "init" { FunctionType::Initializer } else { FunctionType::Method };
+                result = result.and(self.resolve_function(params, body, kind));
+            }
+        }
+        self.end_scope();
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+        self.current_class = enclosing_class;
+        result
+    }
+    fn visit_continue_stmt(&mut self, _keyword: &Token) -> Result<(), Error> {
+        Ok(())
+    }
+    fn visit_debug_assert_stmt(&mut self, _keyword: &Token, condition: &Expr) -> Result<(), Error> {
+        self.resolve_expr(condition)
+    }
+    fn visit_empty_stmt(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn visit_expression_stmt(&mut self, expression: &Expr) -> Result<(), Error> {
+        self.resolve_expr(expression)
+    }
+    // The name is defined before the body is resolved, so the function can call itself.
+    fn visit_function_stmt(&mut self, name: &Token, params: &Rc<Vec<Param>>, body: &Rc<Vec<Stmt>>) -> Result<(), Error> {
+        // Hoisted functions were declared at the start of the block, by `resolve_stmts()`.
+        if !self.interpreter.hoist {
+            self.declare(name);
+            self.define(name);
+        }
+        self.resolve_function(params, body, FunctionType::Function)
+    }
+    // Both branches are resolved: we don't know which one will run.
+    fn visit_if_stmt(&mut self, _keyword: &Token, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) -> Result<(), Error> {
+        self.resolve_expr(condition)?;
+        self.resolve_stmt(then_branch)?;
+        if let Some(else_branch) = else_branch {
+            self.resolve_stmt(else_branch)?;
+        }
+        Ok(())
+    }
+    // The imported script is resolved on its own when it is run. An alias is declared like a
+    // variable.
+    fn visit_import_stmt(&mut self, _keyword: &Token, _path: &str, alias: Option<&Token>) -> Result<(), Error> {
+        if let Some(alias) = alias {
+            self.warn_if_shadowing(alias);
+            self.declare(alias);
+            self.define(alias);
+        }
+        Ok(())
+    }
+    fn visit_print_stmt(&mut self, _keyword: &Token, expression: &Expr) -> Result<(), Error> {
+        self.resolve_expr(expression)
+    }
+    fn visit_while_stmt(&mut self, _keyword: &Token, condition: &Expr, body: &Stmt, increment: Option<&Expr>) -> Result<(), Error> {
+        self.resolve_expr(condition)?;
+        self.resolve_stmt(body)?;
+        if let Some(increment) = increment {
+            self.resolve_expr(increment)?;
+        }
+        Ok(())
+    }
+    // An initializer always returns `this`, so it may only use a bare `return;`.
+    fn visit_return_stmt(&mut self, keyword: &Token, value: Option<&Expr>) -> Result<(), Error> {
+        if let Some(value) = value {
+            if self.current_function == FunctionType::Initializer {
+                self.error(keyword, "Can't return a value from an initializer.");
+            } else if self.in_generator {
+                self.error(keyword, "Can't return a value from a generator.");
+            }
+            self.resolve_expr(value)?;
+        }
+        Ok(())
+    }
+    // Declaring and defining are split so that the initializer is resolved while the new name is
+    // declared but not yet usable.
+    fn visit_var_stmt(&mut self, name: &Token, initializer: Option<&Expr>) -> Result<(), Error> {
+        self.warn_if_shadowing(name);
+        self.declare(name);
+        if let Some(initializer) = initializer {
+            self.resolve_expr(initializer)?;
+        }
+        self.define(name);
+        Ok(())
+    }
+    // Each case is its own scope, as at runtime.
+    fn visit_switch_stmt(&mut self, _keyword: &Token, discriminant: &Expr, cases: &[(Expr, Vec<Stmt>)], default: Option<&[Stmt]>) -> Result<(), Error> {
+        self.resolve_expr(discriminant)?;
+        for (value, statements) in cases {
+            self.resolve_expr(value)?;
+            self.visit_block_stmt(statements)?;
+        }
+        if let Some(statements) = default {
+            self.visit_block_stmt(statements)?;
+        }
+        Ok(())
+    }
+    // The parser has already made sure this is in a function. An initializer can't be a generator,
+    // since it always returns `this`.
+    fn visit_yield_stmt(&mut self, keyword: &Token, value: &Expr) -> Result<(), Error> {
+        if self.current_function == FunctionType::Initializer {
+            self.error(keyword, "Can't yield from an initializer.");
+        }
+        self.resolve_expr(value)
+    }
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Self {
+            interpreter,
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            in_generator: false,
+            errors: Vec::new(),
+        }
+    }
+
+    // Like `new()`, but for code run inside local scopes that already exist, like `eval()`'s, which
+    // define the names in `scopes`, outermost first.
+    pub fn with_scopes(interpreter: &'a mut Interpreter, scopes: Vec<Vec<String>>) -> Self {
+        let mut resolver = Self::new(interpreter);
+        resolver.scopes = scopes.into_iter()
+            .map(|names| names.into_iter().map(|name| (name, true)).collect())
+            .collect();
+        resolver
+    }
+
+    // Interface. Returns `Err` with all errors found, if any, in which case the program shouldn't be
+    // run.
+    pub fn resolve(&mut self, statements: &[Stmt]) -> Result<(), Vec<Error>> {
+        if let Err(error) = self.resolve_stmts(statements) {
+            self.errors.push(error);
+        }
+        match self.errors.is_empty() {
+            true => Ok(()),
+            false => Err(mem::take(&mut self.errors)),
+        }
+    }
+
+    // Under `hoist`, the functions declared in a block are in scope from its start, as they are
+    // defined before anything else in it is run (see `Interpreter::run_order()`).
+    fn resolve_stmts(&mut self, statements: &[Stmt]) -> Result<(), Error> {
+        if self.interpreter.hoist {
+            for statement in statements {
+                if let Stmt::Function { name, .. } = statement {
+                    self.declare(name);
+                    self.define(name);
+                }
+            }
+        }
+        statements.iter().try_for_each(|statement| self.resolve_stmt(statement))
+    }
+
+    fn resolve_stmt(&mut self, statement: &Stmt) -> Result<(), Error> {
+        self.accept_stmt(statement)
+    }
+
+    fn resolve_expr(&mut self, expression: &Expr) -> Result<(), Error> {
+        self.accept_expr(expression)
+    }
+
+    // The parameters and body share one scope, as in `Interpreter::call_function`. A parameter's
+    // default value is resolved in it too, before the parameter itself is declared, so it can use
+    // the parameters before it.
+    fn resolve_function(&mut self, params: &[Param], body: &[Stmt], kind: FunctionType) -> Result<(), Error> {
+        let enclosing_function = std::mem::replace(&mut self.current_function, kind);
+        let enclosing_generator = mem::replace(&mut self.in_generator, stmt::yields(body));
+        self.begin_scope();
+        let mut result = Ok(());
+        for param in params {
+            if let Some(default) = &param.default {
+                result = result.and(self.resolve_expr(default));
+            }
+            self.declare(&param.name);
+            self.define(&param.name);
+        }
+        let result = result.and(self.resolve_stmts(body));
+        self.end_scope();
+        self.current_function = enclosing_function;
+        self.in_generator = enclosing_generator;
+        result
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    // Redeclaring a name in the same local scope is almost certainly a mistake, so it is an error.
+    // It is allowed at the global scope (e.g. re-running a line at the prompt), which isn't tracked.
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.insert(name.lexeme.clone(), false).is_some() {
+            self.error(name, "Already a variable with this name in this scope.");
+        }
+    }
+
+    // Under `warn_shadow`, warn about a local variable with the same name as one in an enclosing
+    // local scope. Shadowing a global is fine, as is redeclaring one, so that code at the prompt can
+    // be re-run.
+    fn warn_if_shadowing(&mut self, name: &Token) {
+        let Some((_, enclosing)) = self.scopes.split_last() else {
+            return;
+        };
+        if self.interpreter.warn_shadow && enclosing.iter().any(|scope| scope.contains_key(&name.lexeme)) {
+            self.interpreter.warn(name, &format!("Variable '{}' shadows an outer binding.", name.lexeme));
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    // Find the innermost scope declaring `name` and record how far up it is. If there is none, the
+    // variable is left for the interpreter to look up in the globals.
+    fn resolve_local(&mut self, id: usize, name: &Token) {
+        if let Some(depth) = self.scopes.iter().rev().position(|scope| scope.contains_key(&name.lexeme)) {
+            self.interpreter.resolve(id, depth);
+        }
+    }
+
+    // Record an error at `token`. Unlike in the parser, there is nothing to synchronize: the
+    // resolver just carries on.
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors.push(Error::parse(token, message));
+    }
+}
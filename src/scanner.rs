@@ -2,6 +2,7 @@ use crate::token::{Token, TokenType, Literal};
 use crate::error::Error;
 
 use std::collections::HashMap;
+use std::mem;
 
 use lazy_static::lazy_static;
 
@@ -9,55 +10,150 @@ lazy_static! {
     static ref KEYWORDS: HashMap<String, TokenType> = {
         let mut m = HashMap::new();
         m.insert("and".to_owned(), TokenType::And);
+        m.insert("break".to_owned(), TokenType::Break);
+        m.insert("case".to_owned(), TokenType::Case);
         m.insert("class".to_owned(), TokenType::Class);
+        m.insert("continue".to_owned(), TokenType::Continue);
+        m.insert("debug_assert".to_owned(), TokenType::DebugAssert);
+        m.insert("default".to_owned(), TokenType::Default);
         m.insert("else".to_owned(), TokenType::Else);
         m.insert("false".to_owned(), TokenType::False);
         m.insert("for".to_owned(), TokenType::For);
         m.insert("fun".to_owned(), TokenType::Fun);
         m.insert("if".to_owned(), TokenType::If);
+        m.insert("import".to_owned(), TokenType::Import);
+        m.insert("is".to_owned(), TokenType::Is);
         m.insert("nil".to_owned(), TokenType::Nil);
         m.insert("or".to_owned(), TokenType::Or);
         m.insert("print".to_owned(), TokenType::Print);
         m.insert("return".to_owned(), TokenType::Return);
         m.insert("super".to_owned(), TokenType::Super);
+        m.insert("switch".to_owned(), TokenType::Switch);
         m.insert("this".to_owned(), TokenType::This);
         m.insert("true".to_owned(), TokenType::True);
         m.insert("var".to_owned(), TokenType::Var);
         m.insert("while".to_owned(), TokenType::While);
+        m.insert("xor".to_owned(), TokenType::Xor);
+        m.insert("yield".to_owned(), TokenType::Yield);
         m
     };
 }
 
 pub struct Scanner {
-    source: String,
+    // The source is split into `char`s once up front, so the pointers below can index it directly
+    // instead of walking the string with `chars().nth()` on every access.
+    source: Vec<char>,
     tokens: Vec<Token>,
+    // Optional override of the default (English) keyword table, e.g. for dialects.
+    keywords: Option<HashMap<String, TokenType>>,
     start: usize,  // point to the start of the current token
     current: usize,  // point to the *next* character to be scanned
     line: usize,
-    had_error: bool,
+    line_start: usize,  // point to the first character of the current line
+    start_column: usize,  // column of the start of the current token
+    errors: Vec<Error>,
+}
+
+// A copy of the default keyword table, e.g. to start a dialect's table from.
+pub fn default_keywords() -> HashMap<String, TokenType> {
+    KEYWORDS.clone()
 }
 
 impl Scanner {
-    pub fn new(source: String) -> Self {
+    pub fn new(source: String, keywords: Option<HashMap<String, TokenType>>) -> Self {
         Self {
-            source,
+            source: source.chars().collect(),
             tokens: Vec::new(),
+            keywords,
             start: 0,
             current: 0,
             line: 1,
-            had_error: false,
+            line_start: 0,
+            start_column: 1,
+            errors: Vec::new(),
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Error> {
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<Error>> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_column = self.column();
             self.scan_token();
         }
-        self.tokens.push(Token::new(TokenType::Eof, "", Literal::Nil, self.line));
-        match self.had_error {
-            true => Err(Error::ScanError),
-            false => Ok(self.tokens.to_owned()),
+        let column = self.column();
+        self.tokens.push(Token::new(TokenType::Eof, "", Literal::Nil, self.line, column));
+        self.result()
+    }
+
+    // Replace the `deleted` characters at `offset` in the source scanned last with `inserted`, and
+    // return the tokens of the edited source, e.g. for an editor rescanning as the user types.
+    // Offsets count characters, not bytes.
+    // Only the tokens around the edit are scanned again: scanning restarts at the last token that
+    // starts before the edit, which the edit could extend, and stops at the first token after the
+    // edit that starts where an old one did. From there on the old tokens are reused, moved to
+    // their new lines and columns. The result is the same as scanning the edited source from
+    // scratch, as long as the last scan succeeded.
+    pub fn rescan_range(&mut self, offset: usize, deleted: usize, inserted: &str) -> Result<Vec<Token>, Vec<Error>> {
+        let old_line_starts = line_starts(&self.source);
+        let mut old_tokens = mem::take(&mut self.tokens);
+        old_tokens.pop();  // `Eof`, which moves to the new end
+        let starts: Vec<usize> = old_tokens.iter()
+            .map(|token| old_line_starts[token.line - 1] + token.column - 1)
+            .collect();
+
+        let before = starts.partition_point(|&start| start < offset);
+        let kept = before.saturating_sub(1);
+        match before {
+            0 => {
+                self.current = 0;
+                self.line = 1;
+                self.line_start = 0;
+            },
+            _ => {
+                self.current = starts[kept];
+                self.line = old_tokens[kept].line;
+                self.line_start = starts[kept] + 1 - old_tokens[kept].column;
+            },
+        }
+        self.tokens = old_tokens[..kept].to_vec();
+
+        let inserted: Vec<char> = inserted.chars().collect();
+        let edit_end = offset + inserted.len();
+        self.source.splice(offset..offset + deleted, inserted.iter().copied());
+
+        // Scan until an old token starts at the same place in the edited source. Positions inside a
+        // token or comment are never checked, as scanning goes from one token to the next.
+        let mut reused = None;
+        while !self.is_at_end() {
+            if self.current >= edit_end {
+                let old_start = self.current + deleted - inserted.len();
+                if let Ok(i) = starts[kept..].binary_search(&old_start) {
+                    reused = Some(kept + i);
+                    break;
+                }
+            }
+            self.start = self.current;
+            self.start_column = self.column();
+            self.scan_token();
+        }
+
+        let new_line_starts = line_starts(&self.source);
+        if let Some(reused) = reused {
+            for (token, start) in old_tokens.into_iter().zip(starts).skip(reused) {
+                let (line, column) = position(&new_line_starts, start + inserted.len() - deleted);
+                self.tokens.push(Token { line, column, ..token });
+            }
+        }
+        let (line, column) = position(&new_line_starts, self.source.len());
+        self.tokens.push(Token::new(TokenType::Eof, "", Literal::Nil, line, column));
+        self.result()
+    }
+
+    // The tokens, or the errors if there were any.
+    fn result(&mut self) -> Result<Vec<Token>, Vec<Error>> {
+        match self.errors.is_empty() {
+            true => Ok(self.tokens.to_owned()),
+            false => Err(mem::take(&mut self.errors)),
         }
     }
 
@@ -69,12 +165,27 @@ impl Scanner {
             ')' => self.add_token(TokenType::RightParen),
             '{' => self.add_token(TokenType::LeftBrace),
             '}' => self.add_token(TokenType::RightBrace),
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
             ',' => self.add_token(TokenType::Comma),
-            '.' => self.add_token(TokenType::Dot),
-            '-' => self.add_token(TokenType::Minus),
-            '+' => self.add_token(TokenType::Plus),
+            ':' => self.add_token(TokenType::Colon),
+            '?' => self.add_token(TokenType::Question),
+            '.' => {
+                let t = if self.peek() == '.' && self.peek_next() == '.' {
+                    self.advance();
+                    self.advance();
+                    TokenType::DotDotDot
+                } else {
+                    TokenType::Dot
+                };
+                self.add_token(t);
+            },
+            '%' => self.add_token(TokenType::Percent),
             ';' => self.add_token(TokenType::Semicolon),
-            '*' => self.add_token(TokenType::Star),
+            '&' => self.add_token(TokenType::Ampersand),
+            '^' => self.add_token(TokenType::Caret),
+            '|' => self.add_token(TokenType::Pipe),
+            '~' => self.add_token(TokenType::Tilde),
 
             // 2-character tokens
             '!' => {
@@ -86,11 +197,35 @@ impl Scanner {
                 self.add_token(t);
             },
             '<' => {
-                let t = if self.match_next('=') { TokenType::LessEqual } else { TokenType::Less };
+                let t = if self.match_next('=') {
+                    TokenType::LessEqual
+                } else if self.match_next('<') {
+                    TokenType::LessLess
+                } else {
+                    TokenType::Less
+                };
+                self.add_token(t);
+            },
+            '-' => {
+                let t = if self.match_next('-') { TokenType::MinusMinus } else { TokenType::Minus };
+                self.add_token(t);
+            },
+            '+' => {
+                let t = if self.match_next('+') { TokenType::PlusPlus } else { TokenType::Plus };
+                self.add_token(t);
+            },
+            '*' => {
+                let t = if self.match_next('*') { TokenType::StarStar } else { TokenType::Star };
                 self.add_token(t);
             },
             '>' => {
-                let t = if self.match_next('=') { TokenType::GreaterEqual } else { TokenType::Greater };
+                let t = if self.match_next('=') {
+                    TokenType::GreaterEqual
+                } else if self.match_next('>') {
+                    TokenType::GreaterGreater
+                } else {
+                    TokenType::Greater
+                };
                 self.add_token(t);
             },
             '/' => {
@@ -101,15 +236,7 @@ impl Scanner {
                         self.advance();
                     }
                 } else if self.match_next('*') {
-                    // `/* ... */` style comments
-                    while !(self.peek() == '*' && self.peek_next() == '/') && !self.is_at_end() {
-                        if self.peek() == '\n' { self.line += 1; }
-                        self.advance();
-                    }
-
-                    // consume `*` then `/`
-                    self.advance();
-                    self.advance();
+                    self.block_comment();
                 } else {
                     self.add_token(TokenType::Slash);
                 }
@@ -117,20 +244,36 @@ impl Scanner {
 
             // ignore
             ' ' | '\r' | '\t' => (),
-            '\n' => self.line += 1,
+            '\n' => self.newline(),
 
             // literals and identifier
             '"' => self.string(),
             '0'..='9' => self.number(),
-            'a'..='z' | 'A'..='Z' | '_' => self.identifier(),
+            c if is_identifier_start(c) => self.identifier(),
 
             _ => self.error("Unexpected character"),
         };
     }
 
+    // Report an error at the last consumed character.
     fn error(&mut self, message: &str) {
-        crate::error_line(self.line, message);
-        self.had_error = true;
+        self.error_at(self.line, self.column() - 1, message);
+    }
+
+    // Record an error, to be returned once scanning is done.
+    fn error_at(&mut self, line: usize, column: usize, message: &str) {
+        self.errors.push(Error::scan(line, column, message));
+    }
+
+    // 1-based column of the next character to be scanned.
+    fn column(&self) -> usize {
+        self.current - self.line_start + 1
+    }
+
+    // Called after consuming a `\n`.
+    fn newline(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
     }
 
     fn is_at_end(&self) -> bool {
@@ -141,7 +284,7 @@ impl Scanner {
     // Return the current character and increment current pointer.
     fn advance(&mut self) -> char {
         if !self.is_at_end() { self.current += 1; }
-        self.source.chars().nth(self.current - 1).unwrap()
+        self.source[self.current - 1]
     }
 
     // Return whether or not next character is `expected`. If so, consume it.
@@ -149,7 +292,7 @@ impl Scanner {
         if self.is_at_end() {
             return false;
         }
-        if self.source.chars().nth(self.current).unwrap() != expected {
+        if self.source[self.current] != expected {
             return false;
         }
         self.current += 1;
@@ -162,7 +305,7 @@ impl Scanner {
         if self.is_at_end() {
             '\0'
         } else {
-            self.source.chars().nth(self.current).unwrap()
+            self.source[self.current]
         }
     }
 
@@ -172,26 +315,76 @@ impl Scanner {
         if self.current + 1 >= self.source.len() {
             '\0'
         } else {
-            self.source.chars().nth(self.current+1).unwrap()
+            self.source[self.current+1]
+        }
+    }
+
+    // Process `/* ... */` style comments, which may nest. The opening `/*` has been consumed.
+    fn block_comment(&mut self) {
+        let start_line = self.line;
+        let mut depth = 1;
+
+        while depth > 0 && !self.is_at_end() {
+            if self.peek() == '/' && self.peek_next() == '*' {
+                self.advance();
+                self.advance();
+                depth += 1;
+            } else if self.peek() == '*' && self.peek_next() == '/' {
+                self.advance();
+                self.advance();
+                depth -= 1;
+            } else if self.advance() == '\n' {
+                self.newline();
+            }
+        }
+
+        if depth > 0 {
+            // Point at where the comment was opened, not at the end of the file.
+            self.error_at(start_line, self.start_column, "Unterminated block comment");
         }
     }
 
     // Process string.
+    // Escape sequences are interpreted here, so the literal holds the actual string and the
+    // interpreter needs no changes.
     fn string(&mut self) {
-        // Keep consuming until `"`.
+        let mut value = String::new();
+
+        // Keep consuming until `"`. An escaped `\"` is part of the string and does not end it.
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            let c = self.advance();
+            if c == '\n' {
+                self.newline();
+            }
+
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+
+            if self.is_at_end() {
+                break;  // reported as unterminated below
+            }
+            match self.advance() {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                '0' => value.push('\0'),
+                '\\' => value.push('\\'),
+                '"' => value.push('"'),
+                '\n' => {
+                    self.error("Unknown escape sequence '\\' at end of line");
+                    self.newline();
+                },
+                other => self.error(&format!("Unknown escape sequence '\\{}'", other)),
             }
-            self.advance();
         }
 
         if self.is_at_end() {
             self.error("Unterminated string");
         } else {
             self.advance();  // closing `"`
-            let s: Literal = Literal::String_(self.source[self.start+1..self.current-1].to_owned());
-            self.add_full_token(TokenType::String_, s);
+            self.add_full_token(TokenType::String_, Literal::String_(value));
         }
     }
 
@@ -202,34 +395,102 @@ impl Scanner {
             self.advance();
         }
 
+        // A literal with a fractional part or an exponent is a float; otherwise it is an integer.
+        let mut is_float = false;
+
         // Consume decimal point only if the character after is a digit.
-        // `123.` will give Number(123), Dot.
+        // `123.` will give Integer(123), Dot.
         if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.advance();  // consume `.`
+            is_float = true;
         }
 
         // Consume the fractional part.
         while self.peek().is_ascii_digit() {
             self.advance();
         }
-        let s: Literal = Literal::Number(self.source[self.start..self.current].parse().unwrap());
+
+        // Consume the exponent part: `e` or `E`, an optional sign, then at least one digit.
+        // Something like `1e` or `1e+` is an error rather than `Number(1)` followed by more tokens.
+        if self.peek() == 'e' || self.peek() == 'E' {
+            self.advance();  // consume `e`
+            is_float = true;
+            if self.peek() == '+' || self.peek() == '-' {
+                self.advance();
+            }
+            if !self.peek().is_ascii_digit() {
+                self.error("Expected digits in number exponent");
+                return;
+            }
+            while self.peek().is_ascii_digit() {
+                self.advance();
+            }
+        }
+
+        // A suffix forces the type: `i` an integer, `f` a float, so `1f` is a float even though it
+        // looks like an integer. Any other letters straight after the number are an error, rather
+        // than a number followed by an identifier.
+        let digits = self.lexeme();
+        let suffix_start = self.current;
+        while is_identifier_continue(self.peek()) {
+            self.advance();
+        }
+        let suffix: String = self.source[suffix_start..self.current].iter().collect();
+        match suffix.as_str() {
+            "" => {},
+            "f" => is_float = true,
+            "i" if is_float => {
+                self.error_at(self.line, self.start_column, "Integer suffix on a number with a fraction or exponent");
+                return;
+            },
+            "i" => {},
+            _ => {
+                self.error_at(self.line, self.start_column, &format!("Invalid number suffix '{}'", suffix));
+                return;
+            },
+        }
+
+        // An integer literal too big for an `i64` is read as a float instead, like any other number
+        // that can't be held exactly, unless it has the `i` suffix. One too big even for an `f64`,
+        // like `1e400`, would be an infinity, which arithmetic can't produce either (see
+        // `Interpreter::float_result()`), so it is an error.
+        let s: Literal = match digits.parse() {
+            Ok(x) if !is_float => Literal::Integer(x),
+            Err(_) if suffix == "i" => {
+                self.error_at(self.line, self.start_column, "Integer literal is too large");
+                return;
+            },
+            _ => match digits.parse::<f64>().unwrap() {
+                x if x.is_infinite() => {
+                    self.error_at(self.line, self.start_column, "Number literal is too large");
+                    return;
+                },
+                x => Literal::Number(x),
+            },
+        };
         self.add_full_token(TokenType::Number, s)
     }
 
     // Process identifier.
     fn identifier(&mut self) {
-        // Allow alphanumeric and `_` in identifier.
-        while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
+        while is_identifier_continue(self.peek()) {
             self.advance();
         }
 
-        let s = &self.source[self.start..self.current];
+        let s = self.lexeme();
 
         // Check if `s` is a keyword. If so, add that; otherwise, add `TokenType::Identifier`.
-        let type_ = KEYWORDS.get(s).unwrap_or(&TokenType::Identifier).to_owned();
+        // Use the user-supplied keyword table if there is one.
+        let keywords = self.keywords.as_ref().unwrap_or(&KEYWORDS);
+        let type_ = keywords.get(&s).unwrap_or(&TokenType::Identifier).to_owned();
         self.add_token(type_);
     }
 
+    // Return the text of the current token.
+    fn lexeme(&self) -> String {
+        self.source[self.start..self.current].iter().collect()
+    }
+
     // Add a non-literal token.
     fn add_token(&mut self, type_: TokenType) {
         self.add_full_token(type_, Literal::Nil);
@@ -237,9 +498,33 @@ impl Scanner {
 
     // Add a token with a literal.
     fn add_full_token(&mut self, type_: TokenType, literal: Literal) {
-        let lexeme = &self.source[self.start..self.current];
-        let token = Token::new(type_, lexeme, literal, self.line);
+        let lexeme = self.lexeme();
+        let token = Token::new(type_, &lexeme, literal, self.line, self.start_column);
         self.tokens.push(token);
     }
 }
 
+// Where each line of `source` starts.
+fn line_starts(source: &[char]) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(source.iter().enumerate().filter(|(_, &c)| c == '\n').map(|(i, _)| i + 1));
+    starts
+}
+
+// The 1-based line and column of the character at `offset`, given where each line starts.
+fn position(line_starts: &[usize], offset: usize) -> (usize, usize) {
+    let line = line_starts.partition_point(|&start| start <= offset);
+    (line, offset - line_starts[line - 1] + 1)
+}
+
+// Identifiers can use letters and digits from any script, e.g. `café` or `число`, not just ASCII.
+// They follow Unicode's XID_Start and XID_Continue properties (as Rust's own identifiers do), plus
+// `_`. So combining marks can continue an identifier, as in a decomposed `café`, but symbols like
+// `½` or emoji can't appear in one.
+fn is_identifier_start(c: char) -> bool {
+    unicode_ident::is_xid_start(c) || c == '_'
+}
+
+fn is_identifier_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
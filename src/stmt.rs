@@ -1,29 +1,127 @@
 use crate::expr::Expr;
 use crate::token::Token;
 
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq)]
 pub enum Stmt {
     Block {
         statements: Vec<Stmt>,
     },
+    Break {
+        keyword: Token,
+    },
+    Class {
+        name: Token,
+        superclass: Option<Expr>,  // an `Expr::Variable`
+        methods: Vec<Stmt>,  // each a `Stmt::Function`
+    },
+    Continue {
+        keyword: Token,
+    },
+    DebugAssert {
+        keyword: Token,
+        condition: Expr,
+    },
+    // A lone `;`, e.g. the body of `while (step()) ;`.
+    Empty,
     Expression {
         expression: Expr,
     },
+    Function {
+        name: Token,
+        params: Rc<Vec<Param>>,
+        // Shared with the `Function` values created from this declaration.
+        body: Rc<Vec<Stmt>>,
+    },
     If {
+        keyword: Token,
         condition: Expr,
         then_branch: Box<Stmt>,
         else_branch: Option<Box<Stmt>>,
     },
+    // Runs another script in the global scope, or with an alias, in a scope of its own whose
+    // definitions become the fields of a namespace. See `Interpreter::visit_import_stmt()`.
+    Import {
+        keyword: Token,
+        path: String,
+        alias: Option<Token>,
+    },
     Print {
+        keyword: Token,
         expression: Expr,
     },
     While {
+        keyword: Token,
         condition: Expr,
         body: Box<Stmt>,
+        // Only set for a desugared `for` loop. It is kept out of `body` so that it still runs
+        // after a `continue`.
+        increment: Option<Expr>,
+    },
+    Return {
+        keyword: Token,
+        value: Option<Expr>,
     },
     Var {
         name: Token,
         initializer: Option<Expr>,
     },
+    // Runs the statements of the first case whose value equals `discriminant`, or else those of
+    // `default`. There is no fallthrough between cases.
+    Switch {
+        keyword: Token,
+        discriminant: Expr,
+        cases: Vec<(Expr, Vec<Stmt>)>,
+        default: Option<Vec<Stmt>>,
+    },
+    // Only allowed in a function, which makes it a generator. See `Interpreter::call_generator()`.
+    Yield {
+        keyword: Token,
+        value: Expr,
+    },
+}
+
+// The line `statement` starts on, for a debugger to stop at. Blocks and `;` have none: the
+// statements in a block are stopped at instead.
+pub fn line(statement: &Stmt) -> Option<usize> {
+    match statement {
+        Stmt::Block { .. } | Stmt::Empty => None,
+        Stmt::Expression { expression } => crate::expr::line(expression),
+        Stmt::Class { name, .. } | Stmt::Function { name, .. } | Stmt::Var { name, .. } => Some(name.line),
+        Stmt::Break { keyword } | Stmt::Continue { keyword } | Stmt::DebugAssert { keyword, .. } |
+        Stmt::If { keyword, .. } | Stmt::Import { keyword, .. } | Stmt::Print { keyword, .. } |
+        Stmt::While { keyword, .. } | Stmt::Return { keyword, .. } | Stmt::Switch { keyword, .. } |
+        Stmt::Yield { keyword, .. } => Some(keyword.line),
+    }
+}
+
+// Whether `statements`, the body of a function, yield and so make it a generator. A `yield` in a
+// nested function or class belongs to that instead.
+pub fn yields(statements: &[Stmt]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Stmt::Yield { .. } => true,
+        Stmt::Block { statements } => yields(statements),
+        Stmt::If { then_branch, else_branch, .. } => {
+            yields(std::slice::from_ref(then_branch)) || else_branch.as_ref().is_some_and(|branch| yields(std::slice::from_ref(branch)))
+        },
+        Stmt::While { body, .. } => yields(std::slice::from_ref(body)),
+        Stmt::Switch { cases, default, .. } => {
+            cases.iter().any(|(_, statements)| yields(statements)) || default.as_ref().is_some_and(|statements| yields(statements))
+        },
+        _ => false,
+    })
+}
+
+// A parameter of a function declaration or lambda, with the expression giving its value when a
+// call leaves it out, if it has one: `greeting` in `fun greet(name, greeting = "Hello")`.
+// A rest parameter, `...rest`, can only be the last one, and collects the positional arguments
+// left over after the others into an array.
+#[derive(Debug, PartialEq)]
+pub struct Param {
+    pub name: Token,
+    pub default: Option<Expr>,
+    pub rest: bool,
 }
 
 pub trait StmtVisitor<T, E> {
@@ -32,29 +130,69 @@ pub trait StmtVisitor<T, E> {
             Stmt::Block { statements } => {
                 self.visit_block_stmt(statements)
             },
+            Stmt::Break { keyword } => {
+                self.visit_break_stmt(keyword)
+            },
+            Stmt::Class { name, superclass, methods } => {
+                self.visit_class_stmt(name, superclass.as_ref(), methods)
+            },
+            Stmt::Continue { keyword } => {
+                self.visit_continue_stmt(keyword)
+            },
+            Stmt::DebugAssert { keyword, condition } => {
+                self.visit_debug_assert_stmt(keyword, condition)
+            },
+            Stmt::Empty => {
+                self.visit_empty_stmt()
+            },
             Stmt::Expression { expression } => {
                 self.visit_expression_stmt(expression)
             },
-            Stmt::If { condition, then_branch, else_branch } => {
-                self.visit_if_stmt(condition, then_branch, else_branch.as_deref())
+            Stmt::Function { name, params, body } => {
+                self.visit_function_stmt(name, params, body)
+            },
+            Stmt::If { keyword, condition, then_branch, else_branch } => {
+                self.visit_if_stmt(keyword, condition, then_branch, else_branch.as_deref())
+            },
+            Stmt::Import { keyword, path, alias } => {
+                self.visit_import_stmt(keyword, path, alias.as_ref())
             },
-            Stmt::Print { expression } => {
-                self.visit_print_stmt(expression)
+            Stmt::Print { keyword, expression } => {
+                self.visit_print_stmt(keyword, expression)
             },
-            Stmt::While { condition, body } => {
-                self.visit_while_stmt(condition, body)
+            Stmt::While { keyword, condition, body, increment } => {
+                self.visit_while_stmt(keyword, condition, body, increment.as_ref())
+            },
+            Stmt::Return { keyword, value } => {
+                self.visit_return_stmt(keyword, value.as_ref())
             },
             Stmt::Var { name, initializer } => {
                 self.visit_var_stmt(name, initializer.as_ref())
             },
+            Stmt::Switch { keyword, discriminant, cases, default } => {
+                self.visit_switch_stmt(keyword, discriminant, cases, default.as_deref())
+            },
+            Stmt::Yield { keyword, value } => {
+                self.visit_yield_stmt(keyword, value)
+            },
         }
     }
 
     fn visit_block_stmt(&mut self, statements: &[Stmt]) -> Result<T, E>;
+    fn visit_break_stmt(&mut self, keyword: &Token) -> Result<T, E>;
+    fn visit_class_stmt(&mut self, name: &Token, superclass: Option<&Expr>, methods: &[Stmt]) -> Result<T, E>;
+    fn visit_continue_stmt(&mut self, keyword: &Token) -> Result<T, E>;
+    fn visit_debug_assert_stmt(&mut self, keyword: &Token, condition: &Expr) -> Result<T, E>;
+    fn visit_empty_stmt(&mut self) -> Result<T, E>;
     fn visit_expression_stmt(&mut self, expression: &Expr) -> Result<T, E>;
-    fn visit_if_stmt(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) -> Result<T, E>;
-    fn visit_print_stmt(&mut self, expression: &Expr) -> Result<T, E>;
-    fn visit_while_stmt(&mut self, condition: &Expr, body: &Stmt) -> Result<T, E>;
+    fn visit_function_stmt(&mut self, name: &Token, params: &Rc<Vec<Param>>, body: &Rc<Vec<Stmt>>) -> Result<T, E>;
+    fn visit_if_stmt(&mut self, keyword: &Token, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) -> Result<T, E>;
+    fn visit_import_stmt(&mut self, keyword: &Token, path: &str, alias: Option<&Token>) -> Result<T, E>;
+    fn visit_print_stmt(&mut self, keyword: &Token, expression: &Expr) -> Result<T, E>;
+    fn visit_while_stmt(&mut self, keyword: &Token, condition: &Expr, body: &Stmt, increment: Option<&Expr>) -> Result<T, E>;
+    fn visit_return_stmt(&mut self, keyword: &Token, value: Option<&Expr>) -> Result<T, E>;
     fn visit_var_stmt(&mut self, name: &Token, initializer: Option<&Expr>) -> Result<T, E>;
+    fn visit_switch_stmt(&mut self, keyword: &Token, discriminant: &Expr, cases: &[(Expr, Vec<Stmt>)], default: Option<&[Stmt]>) -> Result<T, E>;
+    fn visit_yield_stmt(&mut self, keyword: &Token, value: &Expr) -> Result<T, E>;
 }
 
@@ -1,24 +1,39 @@
+use crate::class::{Class, Instance};
+use crate::function::{Function, NativeFunction};
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::convert::From;
+use std::rc::Rc;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenType {
     // Single-character tokens.
-    LeftParen, RightParen, LeftBrace, RightBrace,
-    Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
+    LeftParen, RightParen, LeftBrace, RightBrace, LeftBracket, RightBracket,
+    Colon, Comma, Dot, Percent, Question, Semicolon, Slash,
+    Ampersand, Caret, Pipe, Tilde,
 
     // One or two character tokens.
     Bang, BangEqual,
     Equal, EqualEqual,
-    Greater, GreaterEqual,
-    Less, LessEqual,
+    Greater, GreaterEqual, GreaterGreater,
+    Less, LessEqual, LessLess,
+    Minus, MinusMinus,
+    Plus, PlusPlus,
+    Star, StarStar,
+
+    // `...`, which spreads an array or collects the rest of the arguments.
+    DotDotDot,
 
     // Literals.
     Identifier, String_, Number,
 
     // Keywords.
     And, Class, Else, False, Fun, For, If, Nil, Or,
-    Print, Return, Super, This, True, Var, While,
+    Print, Return, Super, This, True, Var, While, Xor,
+    Break, Continue, DebugAssert, Import,
+    Switch, Case, Default, Is, Yield,
 
     Eof,
 }
@@ -26,6 +41,7 @@ pub enum TokenType {
 // Literal represents `front-end` values that have been manually entered by user.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
+    Integer(i64),
     Number(f64),
     String_(String),
     Bool(bool),
@@ -35,6 +51,7 @@ pub enum Literal {
 impl fmt::Display for Literal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s: String = match self {
+            Literal::Integer(x) => x.to_string(),
             Literal::Number(x) => x.to_string(),
             Literal::String_(x) => x.to_owned(),
             Literal::Bool(x) => x.to_string(),
@@ -45,29 +62,162 @@ impl fmt::Display for Literal {
 }
 
 // Value represents values of evaluated expressions within the interpreter.
+// Numbers are either an `Integer` or a float (`Number`). Arithmetic on two integers stays an
+// integer, except for `/`, which always gives a float. If either operand is a float, the other is
+// converted and the result is a float. See `Interpreter::visit_binary_expr`.
+// Note the derived `PartialEq` tells `Integer(1)` and `Number(1.0)` apart; the `==` operator
+// doesn't (see `Interpreter::is_equal`).
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
+    Integer(i64),
     Number(f64),
     String_(String),
+    // A single character of a string, as given by `chars()`. It is a value of its own, so it is
+    // never equal to the one-character string: `chars("a")[0] == "a"` is false.
+    Char(char),
     Bool(bool),
     Nil,
+    Function(Rc<Function>),
+    NativeFn(Rc<NativeFunction>),
+    Class(Rc<Class>),
+    Instance(Instance),
+    // Like instances, copies of an array or map share its elements. The flag is set on a frozen
+    // view of one (see `freeze()`), which can be read but not changed.
+    Array(Rc<RefCell<Vec<Value>>>, bool),
+    Map(Rc<RefCell<BTreeMap<MapKey, Value>>>, bool),
 }
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let s: String = match self {
+        write!(f, "{}", self.format(false, usize::MAX, &mut Vec::new()))
+    }
+}
+
+impl Value {
+    // How the value is shown inside an array or map, when echoed at the prompt, and by `repr()`:
+    // like `Display`, but with strings quoted, so e.g. `""` can't be mistaken for nothing. Their
+    // special characters are escaped the way the scanner reads them, so the result of a string,
+    // number, `nil` or array or map of these can be pasted back as code.
+    pub fn quoted(&self) -> String {
+        self.format(true, usize::MAX, &mut Vec::new())
+    }
+
+    // `quoted()`, but showing only the first `limit` elements of a longer array or map, then how
+    // many there are in all, e.g. `[1, 2, ... (1000 total)]`. Nested ones are cut short too.
+    pub fn quoted_up_to(&self, limit: usize) -> String {
+        self.format(true, limit, &mut Vec::new())
+    }
+
+    // `Display`, or `quoted()` if `quoted`, with arrays and maps cut short after `limit` elements.
+    // `open` holds the arrays and maps being formatted further out: one that contains itself, e.g.
+    // after `push(a, a)`, is shown as `[...]` or `{...}` where it comes up again, instead of being
+    // formatted forever.
+    fn format(&self, quoted: bool, limit: usize, open: &mut Vec<*const ()>) -> String {
+        match self {
+            Value::Integer(x) => x.to_string(),
+            // Rust's float formatting already gives the shortest string that parses back to the
+            // same `f64`, so e.g. `0.1 + 0.2` prints `0.30000000000000004` and not a rounded `0.3`
+            // that would hide the difference. Whole floats print without a `.0`.
             Value::Number(x) => x.to_string(),
+            Value::String_(x) if quoted => format!("\"{}\"", escape(x)),
             Value::String_(x) => x.to_owned(),
+            // Quoted like in Rust, to tell it from a string. There is no literal to read it back.
+            Value::Char(x) if quoted => format!("'{}'", escape(&x.to_string())),
+            Value::Char(x) => x.to_string(),
             Value::Bool(x) => x.to_string(),
             Value::Nil => "nil".to_owned(),
-        };
-        write!(f, "{}", s)
+            Value::Function(x) => x.to_string(),
+            Value::NativeFn(x) => x.to_string(),
+            Value::Class(x) => x.to_string(),
+            Value::Instance(x) => x.to_string(),
+            // Strings inside an array are quoted, so `["a, b"]` and `["a", "b"]` print differently.
+            Value::Array(x, _) => {
+                let id = Rc::as_ptr(x).cast();
+                if open.contains(&id) {
+                    return "[...]".to_owned();
+                }
+                open.push(id);
+                let mut elements: Vec<String> = x.borrow().iter().take(limit).map(|value| value.format(true, limit, open)).collect();
+                open.pop();
+                if x.borrow().len() > limit {
+                    elements.push(format!("... ({} total)", x.borrow().len()));
+                }
+                format!("[{}]", elements.join(", "))
+            },
+            Value::Map(x, _) => {
+                let id = Rc::as_ptr(x).cast();
+                if open.contains(&id) {
+                    return "{...}".to_owned();
+                }
+                open.push(id);
+                let mut entries: Vec<String> = x.borrow().iter().take(limit)
+                    .map(|(key, value)| format!("{}: {}", Value::from(key).quoted(), value.format(true, limit, open)))
+                    .collect();
+                open.pop();
+                if x.borrow().len() > limit {
+                    entries.push(format!("... ({} total)", x.borrow().len()));
+                }
+                format!("{{{}}}", entries.join(", "))
+            },
+        }
+    }
+}
+
+// `s` with the characters a string literal can't hold as they are replaced by escape sequences.
+pub(crate) fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// A key of a map. Only strings and numbers can be keys.
+// A whole float is stored as the equal integer, so `m[1]` and `m[1.0]` are the same entry (as
+// `1 == 1.0`). Other floats are stored by their bits, since `f64` itself can't be hashed or
+// ordered. Entries are kept ordered by key: integers, then other numbers, then strings.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MapKey {
+    Integer(i64),
+    Number(u64),
+    String_(String),
+}
+
+impl MapKey {
+    // The key for `value`, or `None` if it can't be used as one.
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Integer(x) => Some(Self::Integer(*x)),
+            Value::Number(x) if x.fract() == 0.0 && *x >= i64::MIN as f64 && *x < i64::MAX as f64 => Some(Self::Integer(*x as i64)),
+            Value::Number(x) => Some(Self::Number(x.to_bits())),
+            Value::String_(x) => Some(Self::String_(x.to_owned())),
+            _ => None,
+        }
+    }
+}
+
+impl From<&MapKey> for Value {
+    fn from(key: &MapKey) -> Self {
+        match key {
+            MapKey::Integer(x) => Self::Integer(*x),
+            MapKey::Number(x) => Self::Number(f64::from_bits(*x)),
+            MapKey::String_(x) => Self::String_(x.to_owned()),
+        }
     }
 }
 
 impl From<Literal> for Value {
     fn from(literal: Literal) -> Self {
         match literal {
+            Literal::Integer(x) => Self::Integer(x),
             Literal::Number(x) => Self::Number(x),
             Literal::String_(x) => Self::String_(x),
             Literal::Bool(x) => Self::Bool(x),
@@ -83,25 +233,28 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Literal,
     pub line: usize,
+    pub column: usize,  // of the first character of the lexeme
 }
 
 impl Token {
     pub fn new(type_: TokenType,
                lexeme: &str,
                literal: Literal,
-               line: usize) -> Self {
+               line: usize,
+               column: usize) -> Self {
         Self {
             type_,
             lexeme: lexeme.to_owned(),
             literal,
             line,
+            column,
         }
     }
 }
 
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?} {} {:?} L{}", self.type_, self.lexeme, self.literal, self.line)
+        write!(f, "{:?} {} {:?} L{}:{}", self.type_, self.lexeme, self.literal, self.line, self.column)
     }
 }
 
@@ -0,0 +1,25 @@
+use crate::token::Token;
+
+use std::fmt;
+
+// A problem worth pointing out that doesn't stop the program, like a variable shadowing another.
+// Unlike errors, warnings can't be returned, since the run carries on, so they are collected on the
+// interpreter (see `Interpreter::take_warnings()`) for the caller to report.
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub token: Token,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(token: &Token, message: &str) -> Self {
+        Warning { token: token.to_owned(), message: message.to_owned() }
+    }
+}
+
+// Like errors, e.g. `[line 3, col 9] Warning at 'x': Variable 'x' shadows an outer binding.`
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[line {}, col {}] Warning at '{}': {}", self.token.line, self.token.column, self.token.lexeme, self.message)
+    }
+}
@@ -0,0 +1,204 @@
+// Arrays, and the natives working on them.
+mod common;
+
+use common::{error_of, output_of};
+
+#[test]
+fn nested_arrays_can_be_indexed_and_assigned() {
+    let source = "var grid = [[1, 2], [3, 4]];
+        print grid[1][0];
+        grid[0][1] = 5;
+        print grid;
+        print grid[-1][-1];";
+    assert_eq!(output_of(source), "3\n[[1, 5], [3, 4]]\n4\n");
+}
+
+#[test]
+fn copies_of_an_array_share_its_elements() {
+    assert_eq!(output_of("var a = [1]; var b = a; b[0] = 2; print a;"), "[2]\n");
+    assert_eq!(output_of("var inner = [1]; var outer = [inner]; push(inner, 2); print outer;"), "[[1, 2]]\n");
+}
+
+#[test]
+fn push_pop_and_len() {
+    let source = "var a = [];
+        push(a, 1); push(a, [2, 3]);
+        print len(a);
+        print pop(a);
+        print a;
+        print len(a[0] + \"\");";
+    assert_eq!(output_of(source), "2\n[2, 3]\n[1]\n1\n");
+    assert_eq!(error_of("pop([]);"), "Can't pop from an empty array.");
+    assert_eq!(error_of("push(1, 2);"), "First argument to 'push' must be an array.");
+}
+
+#[test]
+fn indexing_out_of_bounds_is_an_error() {
+    assert_eq!(error_of("[1, 2][2];"), "Array index 2 out of bounds for length 2.");
+    assert_eq!(error_of("[1, 2][-3];"), "Array index -3 out of bounds for length 2.");
+    assert_eq!(error_of("[1][0.5];"), "Array index must be an integer.");
+}
+
+#[test]
+fn arrays_are_equal_if_their_elements_are() {
+    assert_eq!(output_of("print [1, [2, \"x\"]] == [1, [2, \"x\"]];"), "true\n");
+    assert_eq!(output_of("print [1] == [1.0]; print [0.1 + 0.2] == [0.3];"), "true\ntrue\n");
+    assert_eq!(output_of("print [1] == [2]; print [1] == [1, 2]; print [[1]] != [[2]];"), "false\nfalse\ntrue\n");
+    assert_eq!(output_of("print [] == {}; print [1] == 1;"), "false\nfalse\n");
+}
+
+#[test]
+fn maps_are_equal_if_their_entries_are() {
+    assert_eq!(output_of("print {\"a\": [1]} == {\"a\": [1.0]};"), "true\n");
+    assert_eq!(output_of("print {\"a\": 1} == {\"b\": 1};"), "false\n");
+}
+
+#[test]
+fn map_entries_can_be_inserted_overwritten_and_read() {
+    let source = "var m = {\"a\": 1, 2: \"two\"};
+        m[\"b\"] = 3;
+        m[\"a\"] = 10;
+        print m;
+        print m[\"missing\"];
+        print m[2.0];";
+    assert_eq!(output_of(source), "{2: \"two\", \"a\": 10, \"b\": 3}\nnil\ntwo\n");
+}
+
+#[test]
+fn map_keys_must_be_strings_or_numbers() {
+    assert_eq!(error_of("print {nil: 1};"), "Map key must be a string or a number.");
+    assert_eq!(error_of("var m = {}; m[nil] = 1;"), "Map key must be a string or a number.");
+    assert_eq!(error_of("var m = {}; print m[nil];"), "Map key must be a string or a number.");
+    assert_eq!(error_of("has({}, nil);"), "Map key must be a string or a number.");
+}
+
+#[test]
+fn keys_and_has() {
+    let source = "var m = {\"b\": 1, \"a\": 2};
+        print keys(m);
+        print has(m, \"a\");
+        print has(m, \"z\");
+        print keys({});";
+    assert_eq!(output_of(source), "[\"a\", \"b\"]\ntrue\nfalse\n[]\n");
+}
+
+#[test]
+fn an_array_containing_itself_can_be_printed_and_compared() {
+    let source = "var a = [1]; push(a, a);
+        print a;
+        print a == a;
+        var b = [1]; push(b, b);
+        print a == b;
+        var c = [2]; push(c, c);
+        print a == c;";
+    assert_eq!(output_of(source), "[1, [...]]\ntrue\ntrue\nfalse\n");
+    assert_eq!(output_of("var m = {}; m[\"self\"] = m; print m; print m == m;"), "{\"self\": {...}}\ntrue\n");
+}
+
+#[test]
+fn an_array_shared_twice_is_not_a_cycle() {
+    assert_eq!(output_of("var a = [1]; print [a, a];"), "[[1], [1]]\n");
+}
+
+// NaN is unequal to itself on its own, but equal to itself as an element.
+#[test]
+fn nan_elements_are_equal_while_bare_nan_is_not() {
+    let (mut interpreter, output) = common::capturing_interpreter();
+    interpreter.ieee_floats = true;
+    let source = "var inf = 1e308 * 10;
+        var nan = inf - inf;
+        print nan == nan;
+        print nan != nan;
+        print [nan] == [nan];
+        print [nan] != [nan];
+        var a = [1, nan];
+        print a == a;
+        print {\"x\": nan} == {\"x\": nan};
+        print [nan] == [1];";
+    let printed = common::output_of_in(&mut interpreter, &output, source);
+    assert_eq!(printed, "false\ntrue\ntrue\nfalse\ntrue\ntrue\nfalse\n");
+}
+
+#[test]
+fn slice_counts_negative_indices_from_the_end() {
+    assert_eq!(output_of("var a = [1, 2, 3, 4]; print slice(a, -2, len(a)); print slice(a, 0, -1);"), "[3, 4]\n[1, 2, 3]\n");
+    assert_eq!(output_of("print slice([1, 2, 3], -1, -2); print slice([1, 2, 3], -10, -2);"), "[]\n[1]\n");
+}
+
+#[test]
+fn slice_takes_a_clamped_range() {
+    assert_eq!(output_of("var a = [1, 2, 3, 4]; print slice(a, 1, 3); print a;"), "[2, 3]\n[1, 2, 3, 4]\n");
+    assert_eq!(output_of("print slice([1, 2, 3], -5, 2); print slice([1, 2, 3], 2, 10);"), "[1, 2]\n[3]\n");
+    assert_eq!(output_of("print slice([1, 2, 3], 2, 1); print slice([], 0, 1);"), "[]\n[]\n");
+    assert_eq!(error_of("slice(\"abc\", 0, 1);"), "First argument to 'slice' must be an array.");
+    assert_eq!(error_of("slice([1], 0.5, 1);"), "Second argument to 'slice' must be an integer.");
+    assert_eq!(error_of("slice([1], 0, nil);"), "Third argument to 'slice' must be an integer.");
+}
+
+#[test]
+fn concat_returns_a_new_array() {
+    assert_eq!(output_of("var a = [1]; var b = [2, 3]; var c = concat(a, b); push(c, 4); print c; print a; print b;"),
+        "[1, 2, 3, 4]\n[1]\n[2, 3]\n");
+    assert_eq!(output_of("print concat([], []);"), "[]\n");
+    assert_eq!(error_of("concat([1], 2);"), "Arguments to 'concat' must be arrays.");
+}
+
+#[test]
+fn spread_concatenates_arrays_in_a_literal() {
+    assert_eq!(output_of("var a = [1, 2]; var b = [3]; var c = [0, ...a, ...b, 4]; push(a, 9); print c; print [...[]];"),
+        "[0, 1, 2, 3, 4]\n[]\n");
+    assert_eq!(error_of("print [...\"ab\"];"), "Can only spread an array.");
+}
+
+#[test]
+fn reverse_returns_a_new_array() {
+    assert_eq!(output_of("print reverse([1, 2, 3]) == [3, 2, 1];"), "true\n");
+    assert_eq!(output_of("var a = [1, [2]]; var r = reverse(a); print r; print a;"), "[[2], 1]\n[1, [2]]\n");
+    assert_eq!(error_of("reverse(nil);"), "Argument to 'reverse' must be an array.");
+}
+
+#[test]
+fn find_returns_the_first_match_or_nil() {
+    assert_eq!(output_of("print find([1, 4, 6, 9], fun (x) { return x % 2 == 0; });"), "4\n");
+    assert_eq!(output_of("print find([1, 3], fun (x) { return x > 5; }); print find([], len);"), "nil\nnil\n");
+    // The predicate only runs until the first match.
+    assert_eq!(output_of("var calls = 0; find([1, 2, 3], fun (x) { calls = calls + 1; return x == 2; }); print calls;"), "2\n");
+}
+
+#[test]
+fn max_by_and_min_by_compare_computed_keys() {
+    let source = "var words = [\"kiwi\", \"banana\", \"fig\", \"cherry\"];
+        print max_by(words, len);
+        print min_by(words, len);
+        print max_by([3, -7, 5], fun (x) { return x * x; });
+        print max_by([], len);";
+    // Of `banana` and `cherry`, both of length 6, the first wins.
+    assert_eq!(output_of(source), "banana\nfig\n-7\nnil\n");
+    assert_eq!(error_of("max_by([1, \"a\"], fun (x) { return x; });"), "Keys compared by 'max_by' must be all numbers, all strings or all characters.");
+}
+
+#[test]
+fn callback_natives_reject_non_functions_and_pass_on_errors() {
+    assert_eq!(error_of("find([1], 1);"), "Second argument to 'find' must be a function.");
+    assert_eq!(error_of("min_by(\"ab\", len);"), "First argument to 'min_by' must be an array.");
+    assert_eq!(error_of("find([1], fun (a, b) { return true; });"), "Expected 2 arguments but got 1.");
+    assert_eq!(error_of("max_by([1], len);"), "Argument to 'len' must be a string, an array or a map.");
+
+    // An error in the callback keeps its own position, rather than the native's.
+    let errors = toy_interpreter::run_source("find([1], fun (x) {\n  return x / 0;\n});").unwrap_err();
+    assert_eq!(errors[0].to_string(), "[line 2, col 12] Error at '/': Divide by zero.");
+}
+
+#[test]
+fn frozen_arrays_and_maps_can_be_read_but_not_changed() {
+    let source = "var a = [1, 2]; var f = freeze(a);
+        print f[0]; print len(f); print f == a;
+        push(a, 3); print f;
+        var m = freeze({\"k\": 1}); print m[\"k\"]; print keys(m);
+        print freeze(1); print freeze(\"s\");";
+    assert_eq!(output_of(source), "1\n2\ntrue\n[1, 2, 3]\n1\n[\"k\"]\n1\ns\n");
+    assert_eq!(error_of("var f = freeze([1]); push(f, 2);"), "Cannot modify a frozen value.");
+    assert_eq!(error_of("var f = freeze([1]); pop(f);"), "Cannot modify a frozen value.");
+    assert_eq!(error_of("var f = freeze([1]); f[0] = 2;"), "Cannot modify a frozen value.");
+    assert_eq!(error_of("var m = freeze({}); m[\"k\"] = 2;"), "Cannot modify a frozen value.");
+}
@@ -0,0 +1,98 @@
+// Golden tests of the parser, through `dump_ast()`.
+
+fn ast(source: &str) -> String {
+    toy_interpreter::dump_ast(source).unwrap()
+}
+
+#[test]
+fn operator_precedence() {
+    assert_eq!(ast("print 1 + 2 * 3 - 4 / 2;"), "(print (- (+ 1 (* 2 3)) (/ 4 2)))");
+    assert_eq!(ast("print -a == b or c and !d;"), "(print (or (== (- a) b) (and c (! d))))");
+    assert_eq!(ast("a = b = c ? d : e;"), "(; (= a (= b (?: c d e))))");
+    assert_eq!(ast("print (1 + 2) * 3;"), "(print (* (group (+ 1 2)) 3))");
+}
+
+#[test]
+fn for_loops_desugar_to_while() {
+    assert_eq!(ast("for (var i = 0; i < 3; i = i + 1) print i;"),
+        "(block (var i 0) (while (< i 3) (= i (+ i 1)) (print i)))");
+    assert_eq!(ast("for (;;) break;"), "(while true (break))");
+}
+
+#[test]
+fn if_else_nesting() {
+    // A dangling `else` belongs to the nearest `if`.
+    assert_eq!(ast("if (a) if (b) print 1; else print 2;"), "(if a (if b (print 1) (print 2)))");
+    assert_eq!(ast("if (a) { print 1; } else if (b) print 2; else print 3;"),
+        "(if a (block (print 1)) (if b (print 2) (print 3)))");
+}
+
+#[test]
+fn output_is_deterministic() {
+    let source = "var m = {\"z\": 1, \"a\": 2}; fun f(x, y) { return x; } class C < B { m() { this.x = f(1, 2); } }";
+    let expected = "(var m ({} \"z\" 1 \"a\" 2))\n(fun f (x y) (return x))\n(class C B (fun m () (; (.x = this (call f 1 2)))))";
+    for _ in 0..3 {
+        assert_eq!(ast(source), expected);
+    }
+}
+
+#[test]
+fn parse_errors_are_returned() {
+    let errors = toy_interpreter::dump_ast("print (1 + );").unwrap_err();
+    assert_eq!(errors[0].to_string(), "[line 1, col 12] Error at ')': Expected expression.");
+}
+
+#[test]
+fn default_parameters() {
+    assert_eq!(ast("fun greet(name, greeting = \"Hello\") {}"), "(fun greet (name greeting=\"Hello\"))");
+    assert_eq!(ast("var f = fun (x = 1 + 2) { return x; };"), "(var f (fun (x=(+ 1 2)) (return x)))");
+}
+
+#[test]
+fn keyword_arguments() {
+    assert_eq!(ast("greet(\"Sam\", greeting: \"Hi\");"), "(; (call greet \"Sam\" greeting:\"Hi\"))");
+    // A `:` only makes a keyword argument after a name; otherwise it is part of a ternary.
+    assert_eq!(ast("f(a ? b : c);"), "(; (call f (?: a b c)))");
+}
+
+fn source(source: &str) -> String {
+    toy_interpreter::dump_source(source).unwrap()
+}
+
+#[test]
+fn printed_source_keeps_only_the_parentheses_it_needs() {
+    assert_eq!(source("print (1 + 2) * 3;"), "print (1 + 2) * 3;");
+    assert_eq!(source("print 1 + (2 * 3);"), "print 1 + 2 * 3;");
+    for program in ["print (1 + 2) * 3;", "print 1 + 2 * 3;"] {
+        assert_eq!(ast(&source(program)), ast(program));
+    }
+    assert_eq!(source("print ((1)) - (2 - 3) - (4);"), "print 1 - (2 - 3) - 4;");
+    assert_eq!(source("print (2 ** 3) ** 4; print 2 ** (3 ** 4); print (-2) ** 2; print -(2 ** 2);"),
+        "print (2 ** 3) ** 4;\nprint 2 ** 3 ** 4;\nprint -2 ** 2;\nprint -(2 ** 2);");
+    assert_eq!(source("print (a or b) and c; print a or (b and c); print -(-x); print !(a == b);"),
+        "print (a or b) and c;\nprint a or b and c;\nprint - -x;\nprint !(a == b);");
+    assert_eq!(source("x = (a ? b : c) ? (d = e) : (f ? g : h);"), "x = (a ? b : c) ? d = e : f ? g : h;");
+    assert_eq!(source("print (a = b).c; (f)(1)[(2)]; ({\"k\": 1})[\"k\"];"), "print (a = b).c;\nf(1)[2];\n({\"k\": 1}[\"k\"]);");
+}
+
+// Parsing the printed source gives back the same tree, when it has no redundant parentheses.
+#[test]
+fn printed_source_parses_back_to_the_same_tree() {
+    let program = r#"
+        var a = [1, 2.0, "s\n\"", true, nil, ...b];
+        var m = {"k": -1, 2: !false};
+        a[0] = (a[1] + 2) * 3 % -(4 / 2);
+        for (var i = 0; i < 3; i = i + 1) { if (i == 1) continue; else if (i == 2) break; }
+        while (false) ;
+        fun f(x, y = 1, ...rest) { yield x; return; }
+        var g = fun () { return this.x ? f(1, y: 2) : n++ - --n; };
+        class A < B { init() { super.init(); } m() {} }
+        switch (x) { case 1: print 1; default: print 2; }
+        debug_assert(a is A);
+        import "lib.lox";
+        import "lib.lox" as lib;
+    "#;
+    let printed = source(program);
+    assert_eq!(ast(&printed), ast(program));
+    assert_eq!(source(&printed), printed);
+}
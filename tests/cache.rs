@@ -0,0 +1,57 @@
+// The cache of parsed scripts: the AST survives a round trip through its binary form.
+mod common;
+
+use toy_interpreter::cache;
+use toy_interpreter::resolver::Resolver;
+use toy_interpreter::stmt::Stmt;
+
+// Something of every kind of statement and expression.
+const SOURCE: &str = r#"
+var a = [1, 2.5, "s\n", true, nil];
+var m = {"k": -1, 2: !false};
+a[0] = a[1] ** 2 + 3 % 2 - (4 / 2) * 1;
+m["k"] = 1 < 2 and 2 <= 3 or 3 > 4 xor 4 >= 5;
+var i = 0; i++; --i;
+var r = i == 0 ? 1 & 3 | 4 ^ ~5 << 1 >> 1 : 0;
+fun greet(name, greeting = "Hello") { return greeting + ", " + name; }
+var f = fun (x) { return x != nil; };
+fun pair() { yield 1; yield 2; }
+fun rest(first, ...others) { return [first, ...others]; }
+class A { init(x) { this.x = x; } get() { return this.x; } }
+class B < A { get() { return super.get() + 1; } }
+var b = B(1); b.x = 2;
+for (var j = 0; j < 3; j = j + 1) { if (j == 1) continue; else if (j == 2) break; }
+while (false) ;
+switch (b.get()) { case 3: print "three"; default: print "other"; }
+debug_assert(b is A);
+print greet(greeting: "Hi", name: "Sam");
+print [a, m, r, f(1), pair(), rest(...a)];
+"#;
+
+// What `statements` print when resolved and run in a new interpreter.
+fn output_of_statements(statements: &[Stmt]) -> String {
+    let (mut interpreter, output) = common::capturing_interpreter();
+    Resolver::new(&mut interpreter).resolve(statements).unwrap();
+    interpreter.interpret(statements).unwrap();
+    output.contents()
+}
+
+#[test]
+fn the_ast_survives_a_round_trip() {
+    let statements = toy_interpreter::parse_source(SOURCE).unwrap();
+    let bytes = cache::serialize(SOURCE, &statements);
+    let loaded = cache::deserialize(&bytes, SOURCE).unwrap();
+    assert_eq!(loaded, statements);
+    assert_eq!(output_of_statements(&loaded), output_of_statements(&statements));
+    assert_eq!(output_of_statements(&loaded), common::output_of(SOURCE));
+}
+
+#[test]
+fn a_stale_or_corrupt_cache_is_ignored() {
+    let statements = toy_interpreter::parse_source(SOURCE).unwrap();
+    let bytes = cache::serialize(SOURCE, &statements);
+    assert!(cache::deserialize(&bytes, "print 1;").is_none());
+    assert!(cache::deserialize(&bytes[..bytes.len() - 1], SOURCE).is_none());
+    assert!(cache::deserialize(&[bytes.as_slice(), &[0]].concat(), SOURCE).is_none());
+    assert!(cache::deserialize(b"not a cache", SOURCE).is_none());
+}
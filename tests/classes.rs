@@ -0,0 +1,123 @@
+// Classes, instances and the methods that overload operators.
+mod common;
+
+use common::{error_of, output_of};
+
+const VECTOR: &str = "class Vector {
+        init(x, y) { this.x = x; this.y = y; }
+        add(other) { return Vector(this.x + other.x, this.y + other.y); }
+        eq(other) { return this.x == other.x and this.y == other.y; }
+        less(other) { return this.x * this.x + this.y * this.y < other.x * other.x + other.y * other.y; }
+        to_string() { return \"(\" + this.x + \", \" + this.y + \")\"; }
+    }";
+
+fn vector_output_of(source: &str) -> String {
+    output_of(&format!("{}\n{}", VECTOR, source))
+}
+
+#[test]
+fn add_overloads_plus() {
+    assert_eq!(vector_output_of("var v = Vector(1, 2) + Vector(3, 4); print v.x; print v.y;"), "4\n6\n");
+    assert_eq!(vector_output_of("print Vector(1, 2) + Vector(3, 4) + Vector(-4, 0);"), "(0, 6)\n");
+}
+
+#[test]
+fn eq_overloads_equality() {
+    assert_eq!(vector_output_of("print Vector(1, 2) == Vector(1, 2); print Vector(1, 2) != Vector(1, 2);"), "true\nfalse\n");
+    assert_eq!(vector_output_of("print Vector(1, 2) == Vector(2, 1); print Vector(1, 2) != Vector(2, 1);"), "false\ntrue\n");
+}
+
+#[test]
+fn less_overloads_every_comparison() {
+    let source = "var short = Vector(1, 0); var long = Vector(3, 4);
+        print short < long; print short > long; print short <= long; print short >= long;
+        print long <= long; print long >= long;";
+    assert_eq!(vector_output_of(source), "true\nfalse\ntrue\nfalse\ntrue\ntrue\n");
+}
+
+// A vector that also takes a number, as a vector with that number for both components.
+const SCALABLE: &str = "class Vector {
+        init(x, y) { this.x = x; this.y = y; }
+        other(other) { if (other is Vector) return other; return Vector(other, other); }
+        add(other) { var o = this.other(other); return Vector(this.x + o.x, this.y + o.y); }
+        less(other) { var o = this.other(other); return this.x + this.y < o.x + o.y; }
+        to_string() { return \"(\" + this.x + \", \" + this.y + \")\"; }
+    }";
+
+#[test]
+fn the_right_operand_overloads_if_the_left_one_does_not() {
+    let source = format!("{}\n{}", SCALABLE, "var v = Vector(2, 3);
+        print v + 1; print 1 + v;
+        print v < 2; print v > 2; print v <= 2; print v >= 2;
+        print 2 < v; print 2 > v; print 2 <= v; print 2 >= v;
+        print v < 3; print v > 3; print 3 >= v; print \"v = \" + v;");
+    assert_eq!(output_of(&source),
+        "(3, 4)\n(3, 4)\nfalse\ntrue\nfalse\ntrue\ntrue\nfalse\ntrue\nfalse\ntrue\nfalse\ntrue\nv = (2, 3)\n");
+}
+
+#[test]
+fn eq_of_either_operand_decides_equality() {
+    let source = "class Wrapper { init(x) { this.x = x; } eq(other) { return this.x == other; } }
+        var w = Wrapper(1);
+        print w == 1; print 1 == w; print 1 != w; print 2 == w;";
+    assert_eq!(output_of(source), "true\ntrue\nfalse\nfalse\n");
+}
+
+#[test]
+fn to_string_is_used_by_print_and_concatenation() {
+    assert_eq!(vector_output_of("print Vector(1, 2); print \"v = \" + Vector(3, 4);"), "(1, 2)\nv = (3, 4)\n");
+    assert_eq!(vector_output_of("print len(str(Vector(1, 2)));"), "6\n");
+}
+
+#[test]
+fn operators_without_a_method_work_as_before() {
+    assert_eq!(output_of("class A {} var a = A(); print a == a; print a == A(); print a; print \"\" + a;"),
+        "true\nfalse\n<A instance>\n<A instance>\n");
+    assert_eq!(error_of("class A {} A() < A();"), "Operands must be two numbers, two strings or two characters.");
+    assert_eq!(error_of("class A {} A() - A();"), "Operand(s) must be a number.");
+}
+
+#[test]
+fn errors_in_operator_methods_are_passed_on() {
+    assert_eq!(error_of("class A { add(other) { return nil - 1; } } A() + 1;"), "Operand(s) must be a number.");
+    assert_eq!(error_of("class A { add() { return 1; } } A() + 1;"), "Expected 0 arguments but got 1.");
+}
+
+#[test]
+fn is_checks_the_class_and_its_superclasses() {
+    let source = "class Animal {} class Dog < Animal {} class Cat < Animal {}
+        var dog = Dog();
+        print dog is Dog; print dog is Animal; print dog is Cat;
+        print Animal() is Dog; print 1 is Animal; print Dog is Animal;";
+    assert_eq!(output_of(source), "true\ntrue\nfalse\nfalse\nfalse\nfalse\n");
+    assert_eq!(error_of("class A {} A() is A();"), "Right operand of 'is' must be a class.");
+}
+
+#[test]
+fn methods_read_and_write_fields_through_this() {
+    let source = "class Counter {
+            init() { this.count = 0; }
+            bump() { this.count = this.count + 1; return this; }
+            get() { return this.count; }
+        }
+        var c = Counter();
+        c.bump().bump();
+        print c.get(); print c.count;
+        c.count = 10; print c.get();
+        var get = c.get; c.bump(); print get();";
+    assert_eq!(output_of(source), "2\n2\n10\n11\n");
+    assert_eq!(error_of("class A {} print A().missing;"), "Undefined property 'missing'.");
+    assert_eq!(error_of("var x = 1; x.y = 2;"), "Only instances have fields.");
+}
+
+#[test]
+fn an_overridden_method_can_call_the_superclass_one() {
+    let source = "class A { name() { return \"A\"; } describe() { return \"I am \" + this.name(); } }
+        class B < A { name() { return super.name() + \"B\"; } }
+        class C < B { name() { return super.name() + \"C\"; } }
+        print B().name(); print C().name(); print C().describe(); print A().name();";
+    assert_eq!(output_of(source), "AB\nABC\nI am ABC\nA\n");
+    assert_eq!(error_of("class A < A {}"), "A class can't inherit from itself.");
+    assert_eq!(error_of("class A { f() { return super.f(); } }"), "Can't use 'super' in a class with no superclass.");
+    assert_eq!(error_of("fun f() { return super.f(); }"), "Can't use 'super' outside of a class.");
+}
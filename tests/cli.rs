@@ -0,0 +1,296 @@
+// The command-line interface: running the binary on scripts, piped input and the prompt.
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// What the binary writes to stdout and stderr, and its exit code, when run with `args` and fed
+// `stdin`.
+fn run(args: &[&str], stdin: &str) -> (String, String, i32) {
+    run_with_prelude(args, None, stdin)
+}
+
+// Like `run()`, loading the prelude at `prelude`, if given, through `$TOYLOX_PRELUDE`.
+fn run_with_prelude(args: &[&str], prelude: Option<&Path>, stdin: &str) -> (String, String, i32) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_toy_interpreter"));
+    command.args(args)
+        .env_remove("HOME")  // so no prelude is loaded unless given
+        .env_remove("TOYLOX_PRELUDE");
+    if let Some(prelude) = prelude {
+        command.env("TOYLOX_PRELUDE", prelude);
+    }
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to start the interpreter");
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+        output.status.code().unwrap(),
+    )
+}
+
+// What the prompt prints for `lines`, without the prompts themselves.
+fn prompt(lines: &str) -> String {
+    let (stdout, stderr, code) = run(&["--interactive"], lines);
+    assert_eq!((stderr.as_str(), code), ("", 0));
+    stdout.replace("... ", "").replace("> ", "")
+}
+
+#[test]
+fn piped_input_runs_as_a_script() {
+    assert_eq!(run(&[], "var x = 1;\nprint x + 1;\n"), ("2\n".to_owned(), String::new(), 0));
+    // The exit codes are the same as for a script file.
+    let (_, stderr, code) = run(&[], "print 1;\nprint 2");
+    assert!(stderr.starts_with("[line 2, col 8] Error at end: Expected `;` after value."), "{}", stderr);
+    assert_eq!(code, 65);
+    let (stdout, _, code) = run(&[], "print 1;\nundefined;\n");
+    assert_eq!((stdout.as_str(), code), ("1\n", 70));
+}
+
+#[test]
+fn definitions_persist_across_prompt_lines() {
+    let lines = "var x = 1;
+print x;
+var x = \"two\";
+x
+fun f() { return x; }
+x = 3;
+f()
+";
+    assert_eq!(prompt(lines), "1\n\"two\"\n3\n\n");
+}
+
+#[test]
+fn the_prompt_echoes_the_value_of_a_bare_expression() {
+    assert_eq!(prompt("3 * 4\n"), "12\n\n");
+    assert_eq!(prompt("3 * 4;\nprint 3 * 4;\n"), "12\n\n");
+}
+
+#[test]
+fn the_prompt_echoes_strings_quoted_but_print_does_not() {
+    assert_eq!(prompt("\"\"\nprint \"\";\nnil\n\"a\"\n"), "\"\"\n\nnil\n\"a\"\n\n");
+}
+
+#[test]
+fn the_prompt_cuts_long_arrays_short_but_print_does_not() {
+    let lines = "var a = []; for (var i = 0; i < 1000; i = i + 1) push(a, i);\na\nprint a;\n";
+    let (stdout, stderr, code) = run(&["--interactive"], lines);
+    assert_eq!((stderr.as_str(), code), ("", 0));
+    let output: Vec<&str> = stdout.lines().collect();
+    assert_eq!(output[0], "> > [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, ... (1000 total)]");
+    let all: Vec<String> = (0..1000).map(|i| i.to_string()).collect();
+    assert_eq!(output[1], format!("> [{}]", all.join(", ")));
+
+    let (stdout, _, _) = run(&["--interactive", "--echo-limit=2"], "[[1, 2, 3], {\"a\": 1, \"b\": 2, \"c\": 3}]\n[1, 2]\n");
+    assert_eq!(stdout, "> [[1, 2, ... (3 total)], {\"a\": 1, \"b\": 2, ... (3 total)}]\n> [1, 2]\n> \n");
+    let (stdout, _, _) = run(&["--interactive", "--echo-limit=0"], "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10]\n");
+    assert_eq!(stdout, "> [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10]\n> \n");
+}
+
+#[test]
+fn the_version_native_matches_the_version_flag() {
+    let (flag, _, code) = run(&["--version"], "");
+    assert_eq!(code, 0);
+    let (native, _, _) = run(&[], "print version();");
+    assert!(!native.trim().is_empty());
+    assert_eq!(flag, format!("toy_interpreter {}", native));
+}
+
+#[test]
+fn unfinished_blocks_continue_on_the_next_line() {
+    let lines = "var i = 0;
+while (i < 3) {
+  print i;
+  i = i + 1;
+}
+i
+";
+    assert_eq!(prompt(lines), "0\n1\n2\n3\n\n");
+    assert_eq!(prompt("print \"{\";\nprint (1 +\n2);\n"), "{\n3\n\n");
+
+    let (stdout, _, _) = run(&["--interactive"], "while (false) {\n}\n");
+    assert_eq!(stdout, "> ... > \n");
+}
+
+#[test]
+fn end_of_input_or_quit_leaves_the_prompt_cleanly() {
+    assert_eq!(run(&["--interactive"], ""), ("> \n".to_owned(), String::new(), 0));
+    assert_eq!(prompt("print 1;\n"), "1\n\n");
+    assert_eq!(prompt("print 1;\n:quit\nprint 2;\n"), "1\n");
+    assert_eq!(prompt("print 1;\nexit\nprint 2;\n"), "1\n");
+}
+
+#[test]
+fn backslash_continued_lines_form_one_statement() {
+    assert_eq!(prompt("print 1 + \\\n2;\n"), "3\n\n");
+    assert_eq!(prompt("var x = \\\n  \"a\" + \\\n  \"b\";\nx\n"), "\"ab\"\n\n");
+}
+
+#[test]
+fn a_backslash_in_a_comment_does_not_continue_the_line() {
+    let (stdout, _, _) = run(&["--interactive"], "print 1; // path C:\\\nprint 2;\n");
+    assert_eq!(stdout, "> 1\n> 2\n> \n");
+}
+
+#[test]
+fn errors_in_continued_lines_report_the_right_line() {
+    let (_, stderr, _) = run(&["--interactive"], "print 1 + \\\n;\n");
+    assert!(stderr.starts_with("[line 2, col 1] Error at ';': Expected expression."), "{}", stderr);
+}
+
+#[test]
+fn warn_shadow_reports_shadowing_without_failing() {
+    let source = "{ var x = 1; { var x = 2; print x; } }";
+    assert_eq!(run(&[], source), ("2\n".to_owned(), String::new(), 0));
+    assert_eq!(run(&["--warn-shadow"], source),
+        ("2\n".to_owned(), "[line 1, col 20] Warning at 'x': Variable 'x' shadows an outer binding.\n".to_owned(), 0));
+}
+
+#[test]
+fn int_div_makes_integer_division_truncate() {
+    assert_eq!(run(&[], "print 7 / 2;"), ("3.5\n".to_owned(), String::new(), 0));
+    assert_eq!(run(&["--int-div"], "print 7 / 2;"), ("3\n".to_owned(), String::new(), 0));
+}
+
+#[test]
+fn later_scripts_can_use_definitions_from_earlier_ones() {
+    let dir = std::env::temp_dir().join(format!("toy_interpreter_files_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let [first, second, failing] = ["first.lox", "second.lox", "failing.lox"].map(|name| dir.join(name));
+    std::fs::write(&first, "var greeting = \"hi\"; fun greet(name) { return greeting + \" \" + name; }").unwrap();
+    std::fs::write(&second, "print greet(\"there\");").unwrap();
+    std::fs::write(&failing, "print \"before\"; undefined; print \"after\";").unwrap();
+    let [first, second, failing] = [&first, &second, &failing].map(|path| path.to_str().unwrap());
+
+    assert_eq!(run(&[first, second], ""), ("hi there\n".to_owned(), String::new(), 0));
+
+    // A failed script stops the run, unless `--continue-on-error` is given. Either way it sets the
+    // exit code, and the rest of the failed script is not run.
+    let (stdout, _, code) = run(&[first, failing, second], "");
+    assert_eq!((stdout.as_str(), code), ("before\n", 70));
+    let (stdout, _, code) = run(&["--continue-on-error", first, failing, second], "");
+    assert_eq!((stdout.as_str(), code), ("before\nhi there\n", 70));
+    let (stdout, _, code) = run(&["--continue-on-error", "--keep-going", first, failing, second], "");
+    assert_eq!((stdout.as_str(), code), ("before\nafter\nhi there\n", 70));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn import_as_binds_the_definitions_to_a_namespace() {
+    let dir = std::env::temp_dir().join(format!("toy_interpreter_import_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let [math, main] = ["math.lox", "main.lox"].map(|name| dir.join(name));
+    std::fs::write(&math, "var factor = 2;\nfun square(x) { return x * x; }\nfun double(x) { return x * factor; }").unwrap();
+    std::fs::write(&main, "import \"math.lox\" as m;\nprint m.square(3);\nprint m.double(4);\nprint m;\nsquare;").unwrap();
+
+    let (stdout, stderr, code) = run(&[main.to_str().unwrap()], "");
+    assert_eq!((stdout.as_str(), code), ("9\n8\n<m instance>\n", 70));
+    assert!(stderr.contains("Undefined variable 'square'."), "{}", stderr);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn debug_steps_through_a_script_and_shows_its_variables() {
+    let dir = std::env::temp_dir().join(format!("toy_interpreter_debug_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("script.lox");
+    std::fs::write(&script, "var x = 1;\nvar y = x + 1;\nfor (var i = 0; i < 3; i = i + 1)\n    x = x * 2;\nprint x + y;\n").unwrap();
+    let script = script.to_str().unwrap();
+
+    // Step twice, look at the variables, then run to the breakpoint in the loop, which is hit on
+    // each pass, and from there to the end. The script's variables are still there at the prompt
+    // afterwards.
+    let session = format!(".debug {}\n.step\n.step\n.env\n.break 4\n.continue\n.env\n.continue\n.continue\n.continue\nx\n", script);
+    let (stdout, stderr, code) = run(&["--interactive"], &session);
+    assert_eq!((stderr.as_str(), code), ("", 0));
+    assert_eq!(stdout.replace("> ", ""), "\
+[line 1] var x = 1;
+debug[line 2] var y = x + 1;
+debug[line 3] for (var i = 0; i < 3; i = i + 1)
+debugx = 1
+y = 2
+debugdebug[line 4] x = x * 2;
+debugi = 0
+x = 1
+y = 2
+debug[line 4] x = x * 2;
+debug[line 4] x = x * 2;
+debug10
+8
+
+");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_mode_prints_a_summary_of_the_checks() {
+    let source = "assert(1 < 2);\nassert(1 > 2, \"bigger\");\nexpect(len(\"ab\"), 2);\nprint \"done\";\n";
+    let (stdout, stderr, code) = run(&["--test"], source);
+    assert_eq!(stdout, "done\nFAIL: [line 2] Assertion failed: bigger\n2 passed, 1 failed\n");
+    assert_eq!(stderr, "");
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn test_mode_exits_nonzero_only_if_a_check_failed() {
+    assert_eq!(run(&["--test"], "expect(1 + 1, 2);\n"), ("1 passed, 0 failed\n".to_owned(), String::new(), 0));
+    assert_eq!(run(&["--test"], "expect(1 + 1, 3);\n").2, 1);
+    // An error takes precedence, with its own exit code.
+    assert_eq!(run(&["--test"], "expect(1 + 1, 3);\nundefined;\n").2, 70);
+
+    let dir = std::env::temp_dir().join(format!("toy_interpreter_test_mode_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("suite.lox");
+    std::fs::write(&script, "expect([1, 2, 3], [1, 9, 3]);\n").unwrap();
+    let (stdout, _, code) = run(&["--test", script.to_str().unwrap()], "");
+    assert_eq!(stdout, "FAIL: [line 1] Expected [1, 9, 3] but got [1, 2, 3]: at [1], expected 9 but got 2.\n0 passed, 1 failed\n");
+    assert_eq!(code, 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cache_writes_and_reuses_a_loxc_file() {
+    let dir = std::env::temp_dir().join(format!("toy_interpreter_cache_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("script.lox");
+    let cache = dir.join("script.loxc");
+    let script_path = script.to_str().unwrap();
+
+    std::fs::write(&script, "print 1 + 2;").unwrap();
+    assert_eq!(run(&[script_path], ""), ("3\n".to_owned(), String::new(), 0));
+    assert!(!cache.exists());
+    assert_eq!(run(&["--cache", script_path], ""), ("3\n".to_owned(), String::new(), 0));
+    assert!(cache.exists());
+    assert_eq!(run(&["--cache", script_path], ""), ("3\n".to_owned(), String::new(), 0));
+
+    // A changed script is parsed again rather than run from the old cache.
+    std::fs::write(&script, "print 4 + 5;").unwrap();
+    assert_eq!(run(&["--cache", script_path], ""), ("9\n".to_owned(), String::new(), 0));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn the_prelude_is_loaded_before_the_first_prompt() {
+    let dir = std::env::temp_dir().join(format!("toy_interpreter_prelude_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let prelude = dir.join("prelude.lox");
+    std::fs::write(&prelude, "fun helper() { return \"helped\"; }").unwrap();
+    let (stdout, stderr, code) = run_with_prelude(&["--interactive"], Some(&prelude), "helper()\n");
+    assert_eq!((stdout.as_str(), stderr.as_str(), code), ("> \"helped\"\n> \n", "", 0));
+
+    // A prelude with an error is reported, and the prompt still starts, with whatever the prelude
+    // defined before the error.
+    std::fs::write(&prelude, "fun helper() { return 1; }\nundefined;").unwrap();
+    let (stdout, stderr, code) = run_with_prelude(&["--interactive"], Some(&prelude), "helper() + 1\n");
+    assert_eq!((stdout.as_str(), code), ("> 2\n> \n", 0));
+    assert!(stderr.contains("Undefined variable 'undefined'."), "{}", stderr);
+
+    // A missing prelude is skipped.
+    std::fs::remove_file(&prelude).unwrap();
+    assert_eq!(run_with_prelude(&["--interactive"], Some(&prelude), "1\n"), ("> 1\n> \n".to_owned(), String::new(), 0));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,48 @@
+// Helpers shared by the integration tests. Not every test file uses all of them.
+#![allow(dead_code)]
+
+use toy_interpreter::environment::Environment;
+use toy_interpreter::{Interpreter, SharedOutput};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// A fresh interpreter whose `print` output is captured in the returned buffer.
+pub fn capturing_interpreter() -> (Interpreter, SharedOutput) {
+    let output = SharedOutput::default();
+    let globals = Rc::new(RefCell::new(Environment::new(None)));
+    let interpreter = Interpreter::with_output(globals, Box::new(output.clone()));
+    (interpreter, output)
+}
+
+// What `source` prints, run in `interpreter`. Panics if it fails.
+pub fn output_of_in(interpreter: &mut Interpreter, output: &SharedOutput, source: &str) -> String {
+    let start = output.contents().len();
+    if let Err(errors) = toy_interpreter::run_in(interpreter, source) {
+        panic!("{:?} failed: {}", source, errors[0]);
+    }
+    output.contents()[start..].to_owned()
+}
+
+// What `source` prints, run in a new interpreter. Panics if it fails.
+pub fn output_of(source: &str) -> String {
+    let (mut interpreter, output) = capturing_interpreter();
+    output_of_in(&mut interpreter, &output, source)
+}
+
+// The message of the first error `source` fails with, without its position. Panics if it succeeds.
+pub fn error_of(source: &str) -> String {
+    let (mut interpreter, _output) = capturing_interpreter();
+    match toy_interpreter::run_in(&mut interpreter, source) {
+        Ok(_) => panic!("{:?} didn't fail", source),
+        Err(errors) => message(&errors[0]),
+    }
+}
+
+pub fn message(error: &toy_interpreter::Error) -> String {
+    let text = error.to_string();
+    match text.split_once(": ") {
+        Some((_, message)) => message.to_owned(),
+        None => text,
+    }
+}
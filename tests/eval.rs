@@ -0,0 +1,35 @@
+// The `eval()` native, which runs code built at runtime.
+mod common;
+
+use common::{error_of, output_of};
+use toy_interpreter::Value;
+
+#[test]
+fn eval_returns_the_value_of_the_code() {
+    assert_eq!(toy_interpreter::run_source("eval(\"1 + 2\");").unwrap(), vec![Value::Integer(3)]);
+    assert_eq!(output_of("print eval(\"var a = 2; a * 10;\"); print eval(\"print 1;\");"), "20\n1\nnil\n");
+}
+
+#[test]
+fn eval_defines_variables_in_the_caller_scope() {
+    assert_eq!(output_of("eval(\"var x = 5; fun double(n) { return n * 2; }\"); print double(x);"), "10\n");
+    // It can use and assign the caller's locals, too.
+    assert_eq!(output_of("{ var y = 1; eval(\"y = y + 1;\"); print y; print eval(\"var z = y * 10; z;\"); }"), "2\n20\n");
+    assert_eq!(output_of("fun f(n) { return eval(\"n + 1\"); } print f(41);"), "42\n");
+}
+
+#[test]
+fn errors_in_eval_are_runtime_errors_at_the_call() {
+    let errors = toy_interpreter::run_source("var x = 1;\nprint eval(\"1 +\");").unwrap_err();
+    assert_eq!(errors[0].to_string(), "[line 2, col 17] Error at ')': In eval: [line 1, col 4] Error at end: Expected expression.");
+    assert_eq!(error_of("eval(\"nil - 1;\");"), "In eval: [line 1, col 5] Error at '-': Operand(s) must be a number.");
+    assert_eq!(error_of("eval(1);"), "Argument to 'eval' must be a string.");
+}
+
+#[test]
+fn sandbox_mode_disables_eval() {
+    let (mut interpreter, _output) = common::capturing_interpreter();
+    interpreter.sandbox = true;
+    let errors = toy_interpreter::run_in(&mut interpreter, "eval(\"1\");").unwrap_err();
+    assert_eq!(common::message(&errors[0]), "Can't use 'eval' in sandbox mode.");
+}
@@ -0,0 +1,255 @@
+// Function declarations, calls and returns.
+mod common;
+
+use common::{error_of, output_of};
+use toy_interpreter::Value;
+
+use std::rc::Rc;
+
+#[test]
+fn tail_recursion_runs_in_constant_stack_space() {
+    let source = "fun countdown(n) { if (n == 0) return \"done\"; return countdown(n - 1); }
+        print countdown(1000000);";
+    assert_eq!(output_of(source), "done\n");
+}
+
+#[test]
+fn mutual_tail_calls_run_in_constant_stack_space() {
+    let source = "fun is_even(n) { if (n == 0) return true; return is_odd(n - 1); }
+        fun is_odd(n) { if (n == 0) return false; return is_even(n - 1); }
+        print is_even(1000000); print is_odd(777777);";
+    assert_eq!(output_of(source), "true\ntrue\n");
+}
+
+#[test]
+fn tail_calls_keep_their_results_and_errors() {
+    assert_eq!(output_of("fun id(x) { return x; } fun f() { return id(len(\"abc\")); } print f();"), "3\n");
+    assert_eq!(output_of("class A { init(x) { this.x = x; } } fun make() { return A(7); } print make().x;"), "7\n");
+    assert_eq!(error_of("fun f(a) { return f(); } f(1);"), "Expected 1 arguments but got 0.");
+    assert_eq!(error_of("fun f() { return nil(); } f();"), "Can only call functions and classes.");
+}
+
+// What `source` prints with functions hoisted.
+fn hoisted_output_of(source: &str) -> String {
+    let (mut interpreter, output) = common::capturing_interpreter();
+    interpreter.hoist = true;
+    common::output_of_in(&mut interpreter, &output, source)
+}
+
+const IS_EVEN: &str = "fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }";
+const IS_ODD: &str = "fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }";
+
+#[test]
+fn hoisted_functions_can_be_called_before_their_declaration() {
+    for (first, second) in [(IS_EVEN, IS_ODD), (IS_ODD, IS_EVEN)] {
+        let top_level = format!("print isEven(10); print isOdd(7); {} {}", first, second);
+        assert_eq!(hoisted_output_of(&top_level), "true\ntrue\n");
+        let block = format!("{{ print isEven(3); {} {} print isOdd(4); }}", first, second);
+        assert_eq!(hoisted_output_of(&block), "false\nfalse\n");
+    }
+}
+
+#[test]
+fn functions_are_not_hoisted_by_default() {
+    assert_eq!(error_of(&format!("print isEven(2); {} {}", IS_EVEN, IS_ODD)), "Undefined variable 'isEven'.");
+    // In a block, `isEven` resolves `isOdd` as a global, since it isn't declared yet.
+    assert_eq!(error_of(&format!("{{ {} {} print isEven(2); }}", IS_EVEN, IS_ODD)), "Undefined variable 'isOdd'.");
+}
+
+#[test]
+fn hoisting_leaves_other_statements_in_order() {
+    assert_eq!(hoisted_output_of("print f(); var x = 1; fun f() { return \"f\"; } print x;"), "f\n1\n");
+    assert_eq!(error_of("{ fun f() {} fun f() {} }"), "Already a variable with this name in this scope.");
+    let (mut interpreter, _output) = common::capturing_interpreter();
+    interpreter.hoist = true;
+    let errors = toy_interpreter::run_in(&mut interpreter, "{ fun f() {} fun f() {} }").unwrap_err();
+    assert_eq!(common::message(&errors[0]), "Already a variable with this name in this scope.");
+}
+
+// Runs `source`, which must end with the expression `data;` giving an array, and then `release`.
+// Returns whether the array is still alive afterwards, when the script holds no reference to it.
+fn still_alive_after(source: &str, release: &str) -> bool {
+    let (mut interpreter, _output) = common::capturing_interpreter();
+    let values = toy_interpreter::run_in(&mut interpreter, source).unwrap();
+    let Some(Value::Array(array, _)) = values.last() else { panic!("{:?} doesn't end with an array", source) };
+    let array = Rc::downgrade(array);
+    drop(values);
+    toy_interpreter::run_in(&mut interpreter, release).unwrap();
+    array.upgrade().is_some()
+}
+
+#[test]
+fn functions_declared_in_a_scope_do_not_keep_it_alive() {
+    let source = "var data = [1, 2, 3];
+        { var captured = data; fun f() { return captured; } f(); }
+        data;";
+    assert!(!still_alive_after(source, "data = nil;"));
+
+    // Nor does recursion, or a lambda stored in the scope it closes over.
+    let source = "var data = [1];
+        { var captured = data; fun count(n) { if (n == 0) return captured; return count(n - 1); } count(3); }
+        { var captured = data; var g = fun () { return captured; }; }
+        data;";
+    assert!(!still_alive_after(source, "data = nil;"));
+}
+
+#[test]
+fn functions_that_escape_keep_their_scope_alive_until_dropped() {
+    let source = "var data = [1, 2, 3];
+        var escaped;
+        { var captured = data; fun f() { return captured; } escaped = f; }
+        data;";
+    assert!(still_alive_after(source, "data = nil; print len(escaped());"));
+    assert!(!still_alive_after(source, "data = nil; escaped = nil;"));
+
+    let source = "fun make_counter() { var count = 0; fun next() { count = count + 1; return count; } return next; }
+        var counter = make_counter(); counter(); print counter();";
+    assert_eq!(output_of(source), "2\n");
+}
+
+#[test]
+fn the_scopes_of_finished_calls_are_reused_only_if_nothing_kept_them() {
+    // Each counter keeps the scope of its own call, however many calls come in between.
+    let source = "fun make_counter() { var count = 0; fun next() { count = count + 1; return count; } return next; }
+        fun other(x) { var y = x * 2; return y; }
+        var a = make_counter(); other(1); var b = make_counter(); other(2);
+        a(); a(); other(3); b();
+        print a(); print b(); print other(4);";
+    assert_eq!(output_of(source), "3\n2\n8\n");
+
+    // A reused scope is emptied, so it doesn't keep the values of the call that used it alive.
+    let source = "var data = [1, 2, 3];
+        fun f(x) { var y = x; fun g() { return y; } return len(g()); }
+        f(data);
+        data;";
+    assert!(!still_alive_after(source, "data = nil;"));
+}
+
+#[test]
+fn recursive_fib_can_be_benchmarked() {
+    let source = "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+        var result;
+        var timings = benchmark(fun () { result = fib(15); }, 3);
+        print result;
+        print timings[\"per_call_ms\"] > 0;";
+    assert_eq!(output_of(source), "610\ntrue\n");
+}
+
+#[test]
+fn functions_are_equal_to_themselves_however_they_are_read() {
+    assert_eq!(output_of("fun f() {} var g = f; print f == g; { fun h() {} var k = h; print h == k; }"), "true\ntrue\n");
+    assert_eq!(output_of("fun make() { fun inner() {} return inner; } print make() == make();"), "false\n");
+}
+
+#[test]
+fn partial_binds_leading_arguments() {
+    let source = "fun add(a, b) { return a + b; }
+        print partial(add, 1)(2) == add(1, 2);
+        var greet = partial(add, \"hello, \");
+        print greet(\"world\");
+        print partial(add, 1, 2)();
+        print partial(partial(add, 3), 4)();
+        print partial(len)(\"abc\");";
+    assert_eq!(output_of(source), "true\nhello, world\n3\n7\n3\n");
+}
+
+#[test]
+fn partial_adjusts_the_arity() {
+    assert_eq!(error_of("fun add(a, b) { return a + b; } partial(add, 1)(2, 3);"), "Expected 1 arguments but got 2.");
+    assert_eq!(error_of("fun add(a, b) { return a + b; } partial(add, 1, 2, 3);"),
+        "Can't bind 3 arguments to a function that takes at most 2.");
+    assert_eq!(error_of("partial(1, 2);"), "First argument to 'partial' must be a function.");
+    assert_eq!(error_of("partial();"), "Expected at least 1 arguments but got 0.");
+}
+
+#[test]
+fn default_parameters_fill_in_missing_arguments() {
+    let source = "fun greet(name, greeting = \"Hello\") { return greeting + \", \" + name; }
+        print greet(\"Bob\");
+        print greet(\"Bob\", \"Hi\");";
+    assert_eq!(output_of(source), "Hello, Bob\nHi, Bob\n");
+    assert_eq!(output_of("var f = fun (x = 1) { return x; }; print f(); print f(2);"), "1\n2\n");
+    assert_eq!(output_of("class P { init(x = 0, y = x) { this.x = x; this.y = y; } }
+        var p = P(); print p.x + p.y; p = P(3); print p.x + p.y; p = P(3, 4); print p.x + p.y;"), "0\n6\n7\n");
+}
+
+#[test]
+fn default_parameters_are_evaluated_on_each_call_and_can_use_earlier_ones() {
+    let source = "var count = 0; fun next() { count = count + 1; return count; }
+        fun f(a, b = a * 10, c = next()) { print a + b + c; }
+        f(1); f(1); f(1, 2); f(1, 2, 3); print count;";
+    assert_eq!(output_of(source), "12\n13\n6\n6\n3\n");
+}
+
+#[test]
+fn default_parameters_can_only_be_left_out_at_the_end() {
+    assert_eq!(error_of("fun f(a, b = 1) {} f();"), "Expected 1 to 2 arguments but got 0.");
+    assert_eq!(error_of("fun f(a, b = 1) {} f(1, 2, 3);"), "Expected 1 to 2 arguments but got 3.");
+    assert_eq!(error_of("fun f(a = 1, b) {}"), "A parameter without a default value can't follow one with a default.");
+    assert_eq!(error_of("fun f(a = b) { var b; } f();"), "Undefined variable 'b'.");
+}
+
+#[test]
+fn keyword_arguments_go_to_the_parameter_of_that_name() {
+    let source = "fun greet(name, greeting = \"Hello\") { return greeting + \", \" + name; }
+        print greet(greeting: \"Hi\", name: \"Sam\");
+        print greet(name: \"Sam\");
+        print greet(\"Sam\", greeting: \"Hey\");";
+    assert_eq!(output_of(source), "Hi, Sam\nHello, Sam\nHey, Sam\n");
+    // Defaults before a keyword argument are still filled in.
+    assert_eq!(output_of("fun f(a, b = 2, c = 3) { print a + b + c; } f(1, c: 10);"), "13\n");
+    assert_eq!(output_of("class P { init(x, y) { this.x = x; this.y = y; } } var p = P(y: 1, x: 2); print p.x - p.y;"), "1\n");
+    assert_eq!(output_of("fun f(a, b) { return a - b; } fun g() { return f(b: 1, a: 3); } print g();"), "2\n");
+}
+
+#[test]
+fn keyword_arguments_must_match_the_parameters() {
+    assert_eq!(error_of("fun f(a) {} f(b: 1);"), "Unknown keyword argument 'b'.");
+    assert_eq!(error_of("fun f(a) {} f(a: 1, a: 2);"), "Argument 'a' given more than once.");
+    assert_eq!(error_of("fun f(a) {} f(1, a: 2);"), "Argument 'a' given more than once.");
+    assert_eq!(error_of("fun f(a, b) {} f(b: 2);"), "Missing argument 'a'.");
+    assert_eq!(error_of("fun f(a) {} f(1, 2, a: 3);"), "Expected at most 1 positional arguments but got 2.");
+    assert_eq!(error_of("len(x: 1);"), "'len' doesn't take keyword arguments.");
+    assert_eq!(error_of("fun f(a, b) {} f(a: 1, 2);"), "Positional arguments can't follow keyword arguments.");
+}
+
+#[test]
+fn a_generator_returns_the_values_it_yields() {
+    let source = "fun count(n) { for (var i = 1; i <= n; i = i + 1) yield i * 10; }
+        var xs = count(3);
+        for (var i = 0; i < len(xs); i = i + 1) print xs[i];";
+    assert_eq!(output_of(source), "10\n20\n30\n");
+    assert_eq!(output_of("fun g() { yield 1; return; yield 2; } print g();"), "[1]\n");
+    assert_eq!(output_of("fun inner() { yield 2; } fun outer() { yield 1; yield inner(); } print outer();"), "[1, [2]]\n");
+    assert_eq!(output_of("fun g() { yield 1; } fun f() { return g(); } print f();"), "[1]\n");
+}
+
+#[test]
+fn yield_is_only_allowed_where_it_makes_a_generator() {
+    assert_eq!(error_of("yield 1;"), "Can't yield from top-level code.");
+    assert_eq!(error_of("fun g() { yield 1; return 2; }"), "Can't return a value from a generator.");
+    assert_eq!(error_of("class A { init() { yield 1; } }"), "Can't yield from an initializer.");
+    // A nested function doesn't make the one around it a generator.
+    assert_eq!(output_of("fun f() { fun g() { yield 1; } return len(g()); } print f();"), "1\n");
+}
+
+#[test]
+fn spread_expands_an_array_into_arguments() {
+    assert_eq!(output_of("fun add(a, b, c) { return a + b + c; } var xs = [2, 3]; print add(1, ...xs);"), "6\n");
+    assert_eq!(output_of("fun f(a, b = 10) { return a + b; } print f(...[1]); print f(...[1, 2]);"), "11\n3\n");
+    assert_eq!(output_of("fun id(x) { return x; } fun f(xs) { return id(...xs); } print f([7]);"), "7\n");
+    assert_eq!(output_of("print len(...[\"abc\"]);"), "3\n");
+    assert_eq!(error_of("fun f(a) {} f(...[1, 2]);"), "Expected 1 arguments but got 2.");
+    assert_eq!(error_of("fun f(a) {} f(...1);"), "Can only spread an array.");
+}
+
+#[test]
+fn a_rest_parameter_collects_the_extra_arguments() {
+    assert_eq!(output_of("fun f(a, ...rest) { print a; print rest; } f(1); f(1, 2, 3);"), "1\n[]\n1\n[2, 3]\n");
+    assert_eq!(output_of("fun f(...xs) { return len(xs); } print f(...[1, 2], 3);"), "3\n");
+    assert_eq!(output_of("fun f(a, b = 2, ...rest) { print [a, b, rest]; } f(b: 5, a: 1); f(1, 2, 3);"), "[1, 5, []]\n[1, 2, [3]]\n");
+    assert_eq!(output_of("class A { init(...xs) { this.xs = xs; } } print A(1, 2).xs;"), "[1, 2]\n");
+    assert_eq!(error_of("fun f(a, ...rest) {} f();"), "Expected at least 1 arguments but got 0.");
+    assert_eq!(error_of("fun f(...rest) {} f(rest: 1);"), "Unknown keyword argument 'rest'.");
+    assert_eq!(error_of("fun f(...rest, a) {}"), "The rest parameter has to be the last one.");
+}
@@ -0,0 +1,49 @@
+// Dialects: scanning with a keyword table other than the default one.
+mod common;
+
+use toy_interpreter::scanner::{self, Scanner};
+use toy_interpreter::token::TokenType;
+
+use std::collections::HashMap;
+
+// The default table with Spanish `print`, `var` and `if`/`else`.
+fn spanish() -> HashMap<String, TokenType> {
+    let mut keywords = scanner::default_keywords();
+    for (english, spanish) in [("print", "imprimir"), ("var", "variable"), ("if", "si"), ("else", "sino")] {
+        let type_ = keywords.remove(english).unwrap();
+        keywords.insert(spanish.to_owned(), type_);
+    }
+    keywords
+}
+
+#[test]
+fn imprimir_means_print_in_the_dialect() {
+    let (mut interpreter, output) = common::capturing_interpreter();
+    interpreter.keywords = Some(spanish());
+    let source = "variable x = 2; si (x > 1) imprimir \"grande\"; sino imprimir \"pequeño\";";
+    assert_eq!(common::output_of_in(&mut interpreter, &output, source), "grande\n");
+}
+
+#[test]
+fn replaced_keywords_are_plain_identifiers_in_the_dialect() {
+    let (mut interpreter, output) = common::capturing_interpreter();
+    interpreter.keywords = Some(spanish());
+    assert_eq!(common::output_of_in(&mut interpreter, &output, "variable print = 1; imprimir print;"), "1\n");
+}
+
+#[test]
+fn the_default_table_is_english() {
+    assert_eq!(common::output_of("print 1;"), "1\n");
+    let tokens = Scanner::new("imprimir".to_owned(), None).scan_tokens().unwrap();
+    assert_eq!(tokens[0].type_, TokenType::Identifier);
+}
+
+#[test]
+fn expressions_can_be_parsed_in_the_dialect() {
+    assert!(toy_interpreter::parse_expression_with_keywords("x or nil", None).is_ok());
+    let mut keywords = spanish();
+    let type_ = keywords.remove("or").unwrap();
+    keywords.insert("o".to_owned(), type_);
+    assert!(toy_interpreter::parse_expression_with_keywords("x o nil", Some(keywords)).is_ok());
+    assert!(toy_interpreter::parse_expression("x o nil").is_err());
+}
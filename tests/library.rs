@@ -0,0 +1,156 @@
+// The library API, used the way a crate depending on this one would.
+mod common;
+
+use toy_interpreter::environment::Environment;
+use toy_interpreter::scanner::Scanner;
+use toy_interpreter::{Error, Interpreter, SharedOutput, Value};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn new_interpreter() -> Interpreter {
+    Interpreter::new(Rc::new(RefCell::new(Environment::new(None))))
+}
+
+#[test]
+fn run_source_returns_top_level_expression_values() {
+    let values = toy_interpreter::run_source("var x = 2; x * 3; \"a\" + \"b\"; nil;").unwrap();
+    assert_eq!(values, vec![Value::Integer(6), Value::String_("ab".to_owned()), Value::Nil]);
+}
+
+#[test]
+fn run_source_returns_every_scan_error() {
+    let errors = toy_interpreter::run_source("var a = @; var b = #;").unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert!(errors.iter().all(|error| matches!(error, Error::ScanError { .. })));
+}
+
+#[test]
+fn run_source_returns_parse_errors_without_running() {
+    let errors = toy_interpreter::run_source("print 1 +;").unwrap_err();
+    assert!(matches!(&errors[..], [Error::ParseError { message, .. }] if message == "Expected expression."));
+}
+
+#[test]
+fn run_source_returns_resolver_errors() {
+    let errors = toy_interpreter::run_source("{ var a = 1; var a = 2; }").unwrap_err();
+    assert_eq!(errors[0].to_string(), "[line 1, col 18] Error at 'a': Already a variable with this name in this scope.");
+}
+
+#[test]
+fn run_source_stops_at_the_first_runtime_error() {
+    let errors = toy_interpreter::run_source("1 / 0; undefined;").unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].to_string(), "[line 1, col 3] Error at '/': Divide by zero.");
+}
+
+#[test]
+fn run_in_keeps_globals_between_calls() {
+    let mut interpreter = new_interpreter();
+    toy_interpreter::run_in(&mut interpreter, "var count = 1; fun bump() { count = count + 1; }").unwrap();
+    toy_interpreter::run_in(&mut interpreter, "bump(); bump();").unwrap();
+    assert_eq!(toy_interpreter::run_in(&mut interpreter, "count;").unwrap(), vec![Value::Integer(3)]);
+}
+
+#[test]
+fn max_size_stops_strings_and_arrays_growing_past_it() {
+    let mut interpreter = new_interpreter();
+    interpreter.max_size = Some(1000);
+    let mut error_of = |source: &str| {
+        let errors = toy_interpreter::run_in(&mut interpreter, source).unwrap_err();
+        common::message(&errors[0])
+    };
+    // Without the limit, these would run until memory ran out.
+    assert_eq!(error_of("var s = \"x\"; while (true) s = s + s;"), "Value too large.");
+    assert_eq!(error_of("var a = []; while (true) push(a, 1);"), "Value too large.");
+    assert_eq!(error_of("var b = [1]; while (true) b = [...b, ...b];"), "Value too large.");
+    assert_eq!(error_of("var c = [1]; while (true) c = concat(c, c);"), "Value too large.");
+    assert_eq!(error_of("fun count() { while (true) yield 1; } count();"), "Value too large.");
+    assert_eq!(error_of("pad(\"x\", 1001);"), "Value too large.");
+
+    // Up to the limit is fine.
+    let source = "var t = \"\"; for (var i = 0; i < 1000; i = i + 1) t = t + \"x\"; len(t);";
+    assert_eq!(toy_interpreter::run_in(&mut interpreter, source).unwrap(), vec![Value::Integer(1000)]);
+    assert_eq!(toy_interpreter::run_source("len(pad(\"x\", 1001));").unwrap(), vec![Value::Integer(1001)]);
+}
+
+#[test]
+fn on_statement_is_called_before_each_statement_with_its_line() {
+    let mut interpreter = new_interpreter();
+    let stops = Rc::new(RefCell::new(Vec::new()));
+    let recorded = Rc::clone(&stops);
+    interpreter.on_statement = Some(Box::new(move |interpreter, line| {
+        let names: Vec<String> = interpreter.variables().into_iter()
+            .filter(|(_, value)| !matches!(value, Value::NativeFn(_)))
+            .map(|(name, _)| name)
+            .collect();
+        recorded.borrow_mut().push((line, names.join(" ")));
+    }));
+    toy_interpreter::run_in(&mut interpreter, "var a = 1;\nfun f(b) {\n  return b;\n}\n{ f(a); }").unwrap();
+    let stops = stops.borrow();
+    let stops: Vec<(usize, &str)> = stops.iter().map(|(line, names)| (*line, names.as_str())).collect();
+    assert_eq!(stops, [(1, ""), (2, "a"), (5, "a f"), (3, "b a f")]);
+}
+
+#[test]
+fn parse_source_does_not_run_anything() {
+    let statements = toy_interpreter::parse_source("print undefined; var x;").unwrap();
+    assert_eq!(statements.len(), 2);
+}
+
+#[test]
+fn parse_expression_rejects_statements() {
+    assert!(toy_interpreter::parse_expression("1 + 2").is_ok());
+    assert!(toy_interpreter::parse_expression("1 + 2;").is_err());
+    assert!(toy_interpreter::parse_expression("var x = 1;").is_err());
+}
+
+#[test]
+fn with_output_captures_every_print() {
+    let output = SharedOutput::default();
+    let globals = Rc::new(RefCell::new(Environment::new(None)));
+    let mut interpreter = Interpreter::with_output(globals, Box::new(output.clone()));
+    toy_interpreter::run_in(&mut interpreter, "print 1; print \"two\"; print [3];").unwrap();
+    assert_eq!(output.contents(), "1\ntwo\n[3]\n");
+
+    // The buffer keeps collecting as the same interpreter runs more code.
+    toy_interpreter::run_in(&mut interpreter, "print nil;").unwrap();
+    assert_eq!(output.contents(), "1\ntwo\n[3]\nnil\n");
+}
+
+#[test]
+fn print_output_is_not_mixed_between_interpreters() {
+    assert_eq!(common::output_of("print \"a\";"), "a\n");
+    assert_eq!(common::output_of("print \"b\";"), "b\n");
+}
+
+// Rescan `source` after replacing `deleted` characters at `offset` with `inserted`, and check that
+// it gives the same as scanning the edited source from scratch.
+fn assert_rescan_matches(source: &str, offset: usize, deleted: usize, inserted: &str) {
+    let mut scanner = Scanner::new(source.to_owned(), None);
+    scanner.scan_tokens().unwrap();
+    let rescanned = scanner.rescan_range(offset, deleted, inserted).map_err(|errors| errors[0].to_string());
+
+    let mut edited: Vec<char> = source.chars().collect();
+    edited.splice(offset..offset + deleted, inserted.chars());
+    let edited: String = edited.into_iter().collect();
+    let scanned = Scanner::new(edited.clone(), None).scan_tokens().map_err(|errors| errors[0].to_string());
+    assert_eq!(rescanned, scanned, "editing {:?} into {:?}", source, edited);
+}
+
+#[test]
+fn rescan_range_gives_the_same_tokens_as_a_full_rescan() {
+    let source = "var count = 1;\nprint count + 2; // total\nfun f(a) { return a; }\n";
+    assert_rescan_matches(source, 4, 5, "total");  // rename one token
+    assert_rescan_matches(source, 10, 0, "=");  // `=` becomes `==`
+    assert_rescan_matches(source, 14, 0, "\n\n");  // move the later lines down
+    assert_rescan_matches(source, 15, 0, "x ");  // move the rest of a line along
+    assert_rescan_matches(source, 0, 0, "  ");
+    assert_rescan_matches(source, source.len(), 0, "print 3;");
+    assert_rescan_matches(source, 0, source.len(), "1");
+    assert_rescan_matches(source, 8, 1, "");  // join two tokens
+    assert_rescan_matches(source, 15, 0, "/* ");  // an unterminated comment
+    assert_rescan_matches(source, 15, 0, "\"");  // an unterminated string
+    assert_rescan_matches("print \"a b\";", 8, 1, "\n");  // inside a string
+    assert_rescan_matches("café = \"ü\"; x", 12, 1, "yé");  // offsets count characters
+}
@@ -0,0 +1,34 @@
+// The logical operators `and` and `or`.
+mod common;
+
+use common::output_of;
+
+#[test]
+fn and_and_or_return_an_operand() {
+    assert_eq!(output_of("print \"a\" and \"b\"; print nil and 1; print false and 1;"), "b\nnil\nfalse\n");
+    assert_eq!(output_of("print 0 or 5; print nil or \"default\"; print false or nil;"), "0\ndefault\nnil\n");
+    assert_eq!(output_of("var name = nil; print name or \"anonymous\";"), "anonymous\n");
+}
+
+#[test]
+fn and_and_or_skip_the_right_operand_once_the_result_is_known() {
+    let source = "var calls = 0;
+        fun touch(x) { calls = calls + 1; return x; }
+        print nil and touch(1); print calls;
+        print 1 or touch(2); print calls;
+        print 1 and touch(3); print calls;
+        print nil or touch(4); print calls;";
+    assert_eq!(output_of(source), "nil\n0\n1\n0\n3\n1\n4\n2\n");
+}
+
+#[test]
+fn a_skipped_right_operand_has_no_side_effects_or_errors() {
+    let source = "var x = \"unchanged\";
+        true or (x = \"changed\");
+        false and (x = \"changed\");
+        print x;
+        print true or undefined;
+        print false and 1 / 0;
+        print nil or false or \"last\";";
+    assert_eq!(output_of(source), "unchanged\ntrue\nfalse\nlast\n");
+}
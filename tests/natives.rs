@@ -0,0 +1,92 @@
+// Built-in functions that don't belong with a particular kind of value.
+mod common;
+
+use common::{error_of, output_of};
+
+use std::io::Cursor;
+
+#[test]
+fn benchmark_times_the_calls() {
+    let source = "var calls = 0;
+        var timings = benchmark(fun () { calls = calls + 1; }, 1000);
+        print calls;
+        print keys(timings);
+        print timings[\"total_ms\"] > 0;
+        print timings[\"per_call_ms\"] > 0;
+        print timings[\"per_call_ms\"] <= timings[\"total_ms\"];";
+    assert_eq!(output_of(source), "1000\n[\"per_call_ms\", \"total_ms\"]\ntrue\ntrue\ntrue\n");
+    assert_eq!(output_of("var t = benchmark(clock, 0); print t[\"per_call_ms\"];"), "0\n");
+}
+
+#[test]
+fn benchmark_passes_on_errors() {
+    assert_eq!(error_of("benchmark(fun () { return nil - 1; }, 3);"), "Operand(s) must be a number.");
+    assert_eq!(error_of("benchmark(fun (x) {}, 3);"), "Expected 1 arguments but got 0.");
+    assert_eq!(error_of("benchmark(1, 3);"), "First argument to 'benchmark' must be a function.");
+    assert_eq!(error_of("benchmark(clock, -1);"), "Second argument to 'benchmark' must be a non-negative integer.");
+}
+
+#[test]
+fn lines_reads_the_rest_of_the_input() {
+    let (mut interpreter, output) = common::capturing_interpreter();
+    interpreter.input = Box::new(Cursor::new("first\nsecond line\r\nthird"));
+    let source = "var l = lines(); print l; print len(l); print lines();";
+    assert_eq!(common::output_of_in(&mut interpreter, &output, source), "[\"first\", \"second line\", \"third\"]\n3\n[]\n");
+
+    interpreter.input = Box::new(Cursor::new(""));
+    assert_eq!(common::output_of_in(&mut interpreter, &output, "print lines();"), "[]\n");
+}
+
+#[test]
+fn expect_error_passes_when_the_call_fails() {
+    let source = "print expect_error(fun () { return 1 / 0; });
+        print expect_error(fun () { 1 / 0; }, \"zero\");
+        print expect_error(fun () { return len(1, 2); }, \"Expected 1\");
+        print \"still running\";";
+    assert_eq!(output_of(source), "Divide by zero.\nDivide by zero.\nExpected 1 arguments but got 2.\nstill running\n");
+}
+
+#[test]
+fn expect_error_fails_when_the_call_succeeds_or_fails_differently() {
+    assert_eq!(error_of("expect_error(fun () { return 1; });"), "Expected an error, but the call succeeded.");
+    assert_eq!(error_of("expect_error(fun () { nil - 1; }, \"zero\");"),
+        "Expected an error containing 'zero', but got 'Operand(s) must be a number.'.");
+    assert_eq!(error_of("expect_error(fun (x) {});"), "First argument to 'expect_error' must be a function that takes no arguments.");
+    assert_eq!(error_of("expect_error(fun () {}, 1);"), "Second argument to 'expect_error' must be a string.");
+}
+
+#[test]
+fn assert_and_expect_fail_outside_test_mode() {
+    assert_eq!(output_of("print assert(1 < 2); print expect([1, 2], [1, 2.0]);"), "nil\nnil\n");
+    assert_eq!(error_of("assert(1 > 2);"), "Assertion failed.");
+    assert_eq!(error_of("assert(nil, \"nothing\");"), "Assertion failed: nothing");
+    assert_eq!(error_of("expect(1 + 1, 3);"), "Expected 3 but got 2.");
+    assert_eq!(error_of("expect(\"1\", 1);"), "Expected 1 but got \"1\".");
+    assert_eq!(error_of("assert(true, 1);"), "Second argument to 'assert' must be a string.");
+}
+
+#[test]
+fn expect_reports_where_arrays_and_maps_first_differ() {
+    assert_eq!(error_of("expect([1, 2, 3], [1, 9, 3]);"), "Expected [1, 9, 3] but got [1, 2, 3]: at [1], expected 9 but got 2.");
+    assert_eq!(error_of("expect({\"a\": [1, {\"b\": 2}]}, {\"a\": [1, {\"b\": 3}]});"),
+        "Expected {\"a\": [1, {\"b\": 3}]} but got {\"a\": [1, {\"b\": 2}]}: at [\"a\"][1][\"b\"], expected 3 but got 2.");
+    assert_eq!(error_of("expect([1], [1, 2]);"), "Expected [1, 2] but got [1]: at [1], expected 2 but got nothing.");
+    assert_eq!(error_of("expect({1: 1}, {2: 1});"), "Expected {2: 1} but got {1: 1}: at [1], expected nothing but got 1.");
+}
+
+#[test]
+fn test_mode_counts_failures_and_carries_on() {
+    let (mut interpreter, output) = common::capturing_interpreter();
+    interpreter.test_mode = true;
+    let source = "print assert(true);
+        print assert(false, \"first\");
+        expect(2 * 2, 4);
+        expect(2 + 2, 5);
+        print \"still running\";";
+    assert_eq!(common::output_of_in(&mut interpreter, &output, source), "true\nfalse\nstill running\n");
+
+    let results = interpreter.test_results();
+    assert_eq!(results.passed, 2);
+    assert_eq!(results.failures, ["[line 2] Assertion failed: first", "[line 4] Expected 5 but got 4."]);
+    assert_eq!(results.to_string(), "FAIL: [line 2] Assertion failed: first\nFAIL: [line 4] Expected 5 but got 4.\n2 passed, 2 failed");
+}
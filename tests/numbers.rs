@@ -0,0 +1,167 @@
+// Arithmetic, number comparison and number formatting.
+mod common;
+
+use common::output_of;
+use toy_interpreter::Value;
+
+#[test]
+fn float_equality_has_a_relative_tolerance() {
+    assert_eq!(output_of("print 0.1 + 0.2 == 0.3; print 0.1 + 0.2 != 0.3;"), "true\nfalse\n");
+    assert_eq!(output_of("print 1 == 1.0; print 0.3 == 0.3000001;"), "true\nfalse\n");
+    // There is no absolute tolerance: only zero equals zero.
+    assert_eq!(output_of("print 1e-300 == 0;"), "false\n");
+}
+
+#[test]
+fn ordering_agrees_with_float_equality() {
+    let source = "var x = 0.1 + 0.2;
+        print x > 0.3; print x < 0.3; print x >= 0.3; print x <= 0.3;";
+    assert_eq!(output_of(source), "false\nfalse\ntrue\ntrue\n");
+    assert_eq!(output_of("print 0.3 < 0.1 + 0.2; print 0.3 >= 0.1 + 0.2;"), "false\ntrue\n");
+}
+
+#[test]
+fn floats_outside_the_tolerance_are_still_ordered() {
+    assert_eq!(output_of("print 0.3 < 0.3000001; print 0.3000001 > 0.3;"), "true\ntrue\n");
+}
+
+#[test]
+fn integers_compare_exactly() {
+    assert_eq!(output_of("print 9007199254740993 > 9007199254740992;"), "true\n");
+    assert_eq!(output_of("print 9007199254740993 == 9007199254740992;"), "false\n");
+}
+
+// What `source` prints with `--ieee-floats`.
+fn ieee_output_of(source: &str) -> String {
+    let (mut interpreter, output) = common::capturing_interpreter();
+    interpreter.ieee_floats = true;
+    common::output_of_in(&mut interpreter, &output, source)
+}
+
+#[test]
+fn overflowing_float_arithmetic_is_an_error() {
+    assert_eq!(common::error_of("print 1e308 * 10;"), "Numeric overflow.");
+    assert_eq!(common::error_of("print -1e308 - 1e308;"), "Numeric overflow.");
+    assert_eq!(common::error_of("print 10.0 ** 400;"), "Numeric overflow.");
+    assert_eq!(common::error_of("print 1 / 0;"), "Divide by zero.");
+    assert_eq!(common::error_of("print 1.5 % 0;"), "Modulo by zero.");
+}
+
+#[test]
+fn finite_float_arithmetic_is_unaffected() {
+    assert_eq!(output_of("print 1e308 * 1.5 > 1e308; print 1.5 * 4; print 7 / 2; print 2 ** 0.5;"),
+        "true\n6\n3.5\n1.4142135623730951\n");
+    assert_eq!(output_of("print 1e-320 / 1e10;"), "0\n");
+}
+
+#[test]
+fn overflowing_literals_are_a_scan_error() {
+    let errors = toy_interpreter::run_source("print 1;\nprint 1e400;").unwrap_err();
+    assert_eq!(errors[0].to_string(), "[line 2, col 7] Error: Number literal is too large");
+    let digits = "9".repeat(400);
+    assert!(toy_interpreter::run_source(&format!("print {};", digits)).is_err());
+    assert_eq!(output_of("print 1e308 > 1e307; print 99999999999999999999;"), "true\n100000000000000000000\n");
+}
+
+#[test]
+fn ieee_floats_gives_infinities_and_nan() {
+    assert_eq!(ieee_output_of("print 1e308 * 10; print -1e308 * 10;"), "inf\n-inf\n");
+    assert_eq!(ieee_output_of("print 1 / 0; print -1 / 0; print 0 / 0;"), "inf\n-inf\nNaN\n");
+    assert_eq!(ieee_output_of("print 1.5 % 0;"), "NaN\n");
+}
+
+#[test]
+fn integer_modulo_by_zero_is_an_error_even_with_ieee_floats() {
+    let (mut interpreter, _output) = common::capturing_interpreter();
+    interpreter.ieee_floats = true;
+    let errors = toy_interpreter::run_in(&mut interpreter, "1 % 0;").unwrap_err();
+    assert_eq!(common::message(&errors[0]), "Modulo by zero.");
+}
+
+#[test]
+fn floats_print_in_the_shortest_form_that_round_trips() {
+    let cases = [
+        ("0.1 + 0.2", "0.30000000000000004"),
+        ("1 / 3", "0.3333333333333333"),
+        ("0.1", "0.1"),
+        ("2.5 * 2", "5"),
+        ("0.000001", "0.000001"),
+        ("1e23", "100000000000000000000000"),
+    ];
+    for (expression, printed) in cases {
+        let output = output_of(&format!("print {};", expression));
+        assert_eq!(output, format!("{}\n", printed), "printing {}", expression);
+        // It parses back to the very same float.
+        let value = toy_interpreter::run_source(&format!("{};", expression)).unwrap()[0].clone();
+        let Value::Number(x) = value else { panic!("{} isn't a float", expression) };
+        assert_eq!(printed.parse::<f64>().unwrap().to_bits(), x.to_bits());
+    }
+}
+
+#[test]
+fn round_to_a_number_of_digits() {
+    assert_eq!(output_of("print round(3.14159, 2); print round(3.14159, 2) == 3.14;"), "3.14\ntrue\n");
+    assert_eq!(output_of("print round(2.5, 0); print round(-2.5, 0); print round(0.125, 1);"), "3\n-3\n0.1\n");
+    assert_eq!(output_of("print round(1250, -2); print round(1234.5, -1); print round(7, 3);"), "1300\n1230\n7\n");
+    assert_eq!(output_of("print round(1e300, 20) == 1e300; print round(1.5, -400);"), "true\n0\n");
+    assert_eq!(common::error_of("round(\"1\", 2);"), "First argument to 'round' must be a number.");
+    assert_eq!(common::error_of("round(1.5, 0.5);"), "Second argument to 'round' must be an integer.");
+}
+
+// What `source` prints with `--int-div`.
+fn int_div_output_of(source: &str) -> String {
+    let (mut interpreter, output) = common::capturing_interpreter();
+    interpreter.int_div = true;
+    common::output_of_in(&mut interpreter, &output, source)
+}
+
+#[test]
+fn int_div_truncates_integer_division() {
+    assert_eq!(output_of("print 7 / 2; print -7 / 2;"), "3.5\n-3.5\n");
+    assert_eq!(int_div_output_of("print 7 / 2; print -7 / 2; print 6 / 3;"), "3\n-3\n2\n");
+    // A float operand still gives float division.
+    assert_eq!(int_div_output_of("print 7.0 / 2; print 7 / 2.0;"), "3.5\n3.5\n");
+
+    let (mut interpreter, _output) = common::capturing_interpreter();
+    interpreter.int_div = true;
+    interpreter.ieee_floats = true;
+    let errors = toy_interpreter::run_in(&mut interpreter, "1 / 0;").unwrap_err();
+    assert_eq!(common::message(&errors[0]), "Divide by zero.");
+}
+
+// What `source` prints with numbers formatted as `decimal_comma` and `group_thousands` say.
+fn formatted_output_of(source: &str, decimal_comma: bool, group_thousands: bool) -> String {
+    let (mut interpreter, output) = common::capturing_interpreter();
+    interpreter.decimal_comma = decimal_comma;
+    interpreter.group_thousands = group_thousands;
+    common::output_of_in(&mut interpreter, &output, source)
+}
+
+#[test]
+fn numbers_can_show_a_decimal_comma_and_grouped_thousands() {
+    assert_eq!(output_of("print str(3.5); print 1234567.25;"), "3.5\n1234567.25\n");
+    assert_eq!(formatted_output_of("print str(3.5); print 3.5; print \"x\" + 2.5;", true, false), "3,5\n3,5\nx2,5\n");
+    assert_eq!(formatted_output_of("print 1234567.25; print -1234567; print 999; print 1000;", false, true),
+        "1,234,567.25\n-1,234,567\n999\n1,000\n");
+    assert_eq!(formatted_output_of("print 1234567.25; print str(-1000000);", true, true), "1.234.567,25\n-1.000.000\n");
+    // Literals are still read with `.`, and numbers shown as code still use it too.
+    assert_eq!(formatted_output_of("print 0.5 + 1; print [3.5]; print repr(3.5);", true, true), "1,5\n[3.5]\n3.5\n");
+    assert_eq!(formatted_output_of("print str(nil); print str(\"1.5\");", true, true), "nil\n1.5\n");
+}
+
+#[test]
+fn suffixes_choose_the_literal_type() {
+    assert_eq!(toy_interpreter::run_source("1i; 1f; 1.0f; 1e3f; 2.5;").unwrap(),
+        vec![Value::Integer(1), Value::Number(1.0), Value::Number(1.0), Value::Number(1000.0), Value::Number(2.5)]);
+    assert_eq!(int_div_output_of("print 7 / 2; print 7f / 2;"), "3\n3.5\n");
+}
+
+#[test]
+fn invalid_suffixes_are_a_scan_error() {
+    let errors = toy_interpreter::run_source("print 1x;").unwrap_err();
+    assert_eq!(errors[0].to_string(), "[line 1, col 7] Error: Invalid number suffix 'x'");
+    let errors = toy_interpreter::run_source("print 1.5i;").unwrap_err();
+    assert_eq!(common::message(&errors[0]), "Integer suffix on a number with a fraction or exponent");
+    let errors = toy_interpreter::run_source("print 99999999999999999999i;").unwrap_err();
+    assert_eq!(common::message(&errors[0]), "Integer literal is too large");
+}
@@ -0,0 +1,96 @@
+// Variable scoping: blocks, closures and the resolver's checks on them.
+mod common;
+
+use toy_interpreter::Interpreter;
+
+// The warnings the resolver gives for `source`, run in `interpreter`.
+fn warnings_of(interpreter: &mut Interpreter, source: &str) -> Vec<String> {
+    toy_interpreter::run_in(interpreter, source).unwrap();
+    interpreter.take_warnings().iter().map(|warning| warning.to_string()).collect()
+}
+
+#[test]
+fn shadowing_warns_only_with_warn_shadow() {
+    let source = "{ var x = 1; { var x = 2; } }";
+    let (mut interpreter, _output) = common::capturing_interpreter();
+    assert!(warnings_of(&mut interpreter, source).is_empty());
+
+    interpreter.warn_shadow = true;
+    assert_eq!(warnings_of(&mut interpreter, source),
+        vec!["[line 1, col 20] Warning at 'x': Variable 'x' shadows an outer binding."]);
+}
+
+#[test]
+fn shadowing_globals_does_not_warn() {
+    let (mut interpreter, _output) = common::capturing_interpreter();
+    interpreter.warn_shadow = true;
+    assert!(warnings_of(&mut interpreter, "var x = 1; var x = 2; { var x = 3; } fun f() { var x = 4; }").is_empty());
+    // Nor do variables in separate scopes that aren't nested.
+    assert!(warnings_of(&mut interpreter, "{ var y = 1; } { var y = 2; }").is_empty());
+    // But a function body is nested in the scope around it.
+    assert_eq!(warnings_of(&mut interpreter, "{ var z = 1; fun f() { var z = 2; } }").len(), 1);
+}
+
+#[test]
+fn for_loop_counters_increment_and_stay_in_the_loop() {
+    let source = "var seen = [];
+        for (var i = 0; i < 3; i = i + 1) { push(seen, i); }
+        print seen;
+        for (var i = 10; i > 0; i = i - 4) print i;";
+    assert_eq!(common::output_of(source), "[0, 1, 2]\n10\n6\n2\n");
+    assert_eq!(common::error_of("for (var i = 0; i < 3; i = i + 1) {} print i;"), "Undefined variable 'i'.");
+}
+
+#[test]
+fn for_loop_counters_do_not_touch_an_outer_variable() {
+    let source = "var i = \"outer\";
+        for (var i = 0; i < 2; i = i + 1) print i;
+        print i;
+        { var i = \"block\"; for (var i = 5; i < 6; i++) print i; print i; }";
+    assert_eq!(common::output_of(source), "0\n1\nouter\n5\nblock\n");
+}
+
+#[test]
+fn closures_see_the_for_loop_counter_as_it_is_now() {
+    // The counter is one variable for the whole loop, so every closure sees its final value.
+    let source = "var fs = [];
+        for (var i = 0; i < 3; i = i + 1) push(fs, fun () { return i; });
+        print fs[0]() + fs[2]();";
+    assert_eq!(common::output_of(source), "6\n");
+}
+
+#[test]
+fn a_captured_counter_increments_across_calls() {
+    let source = "fun make_counter() { var count = 0; fun next() { count = count + 1; return count; } return next; }
+        var counter = make_counter();
+        print counter(); print counter(); print counter();
+        var other = make_counter();
+        print other(); print counter();";
+    assert_eq!(common::output_of(source), "1\n2\n3\n1\n4\n");
+}
+
+#[test]
+fn variables_resolve_to_the_scope_at_definition_time() {
+    let source = "var x = 5; { fun g() { return x; } var x = 6; print g(); print x; }";
+    assert_eq!(common::output_of(source), "5\n6\n");
+    let source = "var a = \"global\"; { fun show() { print a; } show(); var a = \"block\"; show(); }";
+    assert_eq!(common::output_of(source), "global\nglobal\n");
+}
+
+#[test]
+fn a_local_variable_cannot_be_read_in_its_own_initializer() {
+    assert_eq!(common::error_of("{ var a = 1; { var a = a; } }"), "Can't read local variable in its own initializer.");
+    // Another name from an outer scope is fine.
+    assert_eq!(common::output_of("{ var a = 1; { var b = a + 1; print b; } }"), "2\n");
+    // At the top level, the global has to exist already.
+    assert_eq!(common::error_of("var a = a;"), "Undefined variable 'a'.");
+    assert_eq!(common::output_of("var a = 1; var a = a + 1; print a;"), "2\n");
+}
+
+#[test]
+fn redeclaring_a_variable_is_an_error_only_in_a_local_scope() {
+    let errors = toy_interpreter::run_source("{ var x = 1;\n  var x = 2; }").unwrap_err();
+    assert_eq!(errors[0].to_string(), "[line 2, col 7] Error at 'x': Already a variable with this name in this scope.");
+    assert_eq!(common::output_of("var x = 1; var x = 2; print x;"), "2\n");
+    assert_eq!(common::output_of("{ var x = 1; { var x = 2; print x; } print x; }"), "2\n1\n");
+}
@@ -0,0 +1,68 @@
+// Strings and the natives that work on them.
+mod common;
+
+use common::{error_of, output_of};
+
+#[test]
+fn chars_splits_a_string_into_characters() {
+    let source = "var cs = chars(\"héllo\");
+        print len(cs);
+        print cs;
+        var vowels = 0;
+        for (var i = 0; i < len(cs); i = i + 1) {
+            print cs[i];
+            if (cs[i] == cs[4] or cs[i] == chars(\"é\")[0]) vowels = vowels + 1;
+        }
+        print vowels;";
+    assert_eq!(output_of(source), "5\n['h', 'é', 'l', 'l', 'o']\nh\né\nl\nl\no\n2\n");
+    assert_eq!(output_of("print chars(\"\"); print chars(\"a\\n\");"), "[]\n['a', '\\n']\n");
+    assert_eq!(error_of("chars(1);"), "Argument to 'chars' must be a string.");
+}
+
+#[test]
+fn characters_compare_with_each_other_but_not_with_strings() {
+    assert_eq!(output_of("var c = chars(\"ab\"); print c[0] < c[1]; print c[0] == chars(\"a\")[0];"), "true\ntrue\n");
+    assert_eq!(output_of("print chars(\"a\")[0] == \"a\"; print \"\" + chars(\"a\")[0] == \"a\";"), "false\ntrue\n");
+    assert_eq!(output_of("var c = chars(\"xy\"); print c[0] + c[1]; print max_by(chars(\"bca\"), fun (c) { return c; });"), "xy\nc\n");
+    assert_eq!(error_of("chars(\"a\")[0] < \"b\";"), "Operands must be two numbers, two strings or two characters.");
+}
+
+#[test]
+fn substr_takes_indices_like_slice() {
+    assert_eq!(output_of("print substr(\"hello\", 1, 3); print substr(\"hello\", -3, 5);"), "el\nllo\n");
+    assert_eq!(output_of("print substr(\"hello\", 0, -1); print substr(\"hello\", 3, 99);"), "hell\nlo\n");
+    assert_eq!(error_of("substr(\"hello\", \"1\", 2);"), "Second argument to 'substr' must be an integer.");
+}
+
+#[test]
+fn pad_aligns_strings() {
+    assert_eq!(output_of("print \"[\" + pad(\"ab\", 5) + \"]\";"), "[ab   ]\n");
+    assert_eq!(output_of("print \"[\" + pad(\"ab\", 5, \"left\") + \"]\";"), "[ab   ]\n");
+    assert_eq!(output_of("print \"[\" + pad(\"ab\", 5, \"right\") + \"]\";"), "[   ab]\n");
+    assert_eq!(output_of("print \"[\" + pad(\"ab\", 5, \"center\") + \"]\";"), "[ ab  ]\n");
+    assert_eq!(output_of("print \"[\" + pad(\"ab\", 6, \"center\") + \"]\";"), "[  ab  ]\n");
+}
+
+#[test]
+fn pad_counts_characters_and_never_truncates() {
+    assert_eq!(output_of("print \"[\" + pad(\"héllo\", 6, \"right\") + \"]\";"), "[ héllo]\n");
+    assert_eq!(output_of("print pad(\"toolong\", 3, \"center\"); print len(pad(\"\", 0));"), "toolong\n0\n");
+}
+
+#[test]
+fn pad_rejects_bad_arguments() {
+    assert_eq!(error_of("pad(12, 5);"), "First argument to 'pad' must be a string.");
+    assert_eq!(error_of("pad(\"a\", -1);"), "Second argument to 'pad' must be a non-negative integer.");
+    assert_eq!(error_of("pad(\"a\", 3, \"middle\");"), "Third argument to 'pad' must be \"left\", \"right\" or \"center\".");
+    assert_eq!(error_of("pad(\"a\");"), "Expected 2 to 3 arguments but got 1.");
+}
+
+#[test]
+fn repr_writes_values_as_code() {
+    assert_eq!(output_of(r#"print repr("a\"b");"#), "\"a\\\"b\"\n");
+    assert_eq!(output_of(r#"print repr([1, "x"]);"#), "[1, \"x\"]\n");
+    assert_eq!(output_of(r#"print repr(nil); print repr(2.5); print repr({"k": "a\\b\n"});"#),
+        "nil\n2.5\n{\"k\": \"a\\\\b\\n\"}\n");
+    // What it gives reads back as the same value.
+    assert_eq!(output_of(r#"var s = "tab\there \"q\" \\"; print eval(repr(s)) == s;"#), "true\n");
+}
@@ -0,0 +1,57 @@
+// The `switch` statement.
+mod common;
+
+use common::{error_of, output_of};
+
+// Prints what `switch` does with `value`.
+fn switch_on(value: &str) -> String {
+    let source = format!("switch ({}) {{
+            case 1: print \"one\";
+            case \"1\": print \"string one\";
+            case 2: print \"two\"; print \"still two\";
+            default: print \"other\";
+        }}", value);
+    output_of(&source)
+}
+
+#[test]
+fn the_matching_case_runs_without_falling_through() {
+    assert_eq!(switch_on("1"), "one\n");
+    assert_eq!(switch_on("1 + 1"), "two\nstill two\n");
+}
+
+#[test]
+fn the_default_case_runs_if_nothing_matches() {
+    assert_eq!(switch_on("3"), "other\n");
+    assert_eq!(switch_on("nil"), "other\n");
+    assert_eq!(output_of("switch (3) { case 1: print \"one\"; } print \"after\";"), "after\n");
+}
+
+#[test]
+fn numbers_and_strings_are_compared_like_equality() {
+    assert_eq!(switch_on("\"1\""), "string one\n");
+    assert_eq!(switch_on("1.0"), "one\n");
+    assert_eq!(switch_on("\"2\""), "other\n");
+}
+
+#[test]
+fn case_values_are_evaluated_only_until_one_matches() {
+    let source = "var calls = 0;
+        fun value(x) { calls = calls + 1; return x; }
+        switch (2) { case value(1): case value(2): print \"two\"; case value(3): }
+        print calls;";
+    assert_eq!(output_of(source), "two\n2\n");
+}
+
+#[test]
+fn break_leaves_the_switch_but_not_a_loop_around_it() {
+    let source = "for (var i = 0; i < 3; i = i + 1) {
+            switch (i) { case 1: break; print \"unreachable\"; default: print i; }
+        }";
+    assert_eq!(output_of(source), "0\n2\n");
+}
+
+#[test]
+fn default_must_be_the_last_case() {
+    assert_eq!(error_of("switch (1) { default: case 1: }"), "Expected `case`, or `}` after `default` case.");
+}
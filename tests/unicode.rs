@@ -0,0 +1,37 @@
+// Non-ASCII source text: identifiers and string literals.
+mod common;
+
+use common::output_of;
+
+#[test]
+fn accented_identifiers() {
+    assert_eq!(output_of("var café = 1; print café;"), "1\n");
+    assert_eq!(output_of("var число = 2; var 名前 = число * 2; print 名前;"), "4\n");
+}
+
+// `cafe` followed by U+0301 COMBINING ACUTE ACCENT is a different identifier from the precomposed
+// `café`, but a valid one.
+#[test]
+fn decomposed_accents_continue_an_identifier() {
+    assert_eq!(output_of("var cafe\u{301} = 1; var café = 2; print cafe\u{301};"), "1\n");
+}
+
+#[test]
+fn symbols_are_not_identifier_characters() {
+    assert!(toy_interpreter::run_source("var x½ = 1;").is_err());
+    assert!(toy_interpreter::run_source("var 😀 = 1;").is_err());
+    // A combining mark can't start an identifier.
+    assert!(toy_interpreter::run_source("var \u{301}x = 1;").is_err());
+}
+
+#[test]
+fn emoji_in_string_literals() {
+    assert_eq!(output_of("print \"hi 😀!\";"), "hi 😀!\n");
+    assert_eq!(output_of("print len(\"😀é\"); print substr(\"a😀b\", 1, 2);"), "2\n😀\n");
+}
+
+#[test]
+fn columns_count_characters() {
+    let errors = toy_interpreter::run_source("print \"😀\" + ;").unwrap_err();
+    assert_eq!(errors[0].to_string(), "[line 1, col 13] Error at ';': Expected expression.");
+}